@@ -1,10 +1,16 @@
 use std::path::Path;
 
-use crate::domain::ApplicationConfig;
+use crate::domain::{ApplicationConfig, CoordinateFormat, Polygon};
 use crate::error::{Error, Result};
+use crate::parsers::{geojson, kml};
 
 /// Load application configuration from a YAML file.
 ///
+/// Polygons of location overrides with a `file` are loaded from that GeoJSON
+/// (`.geojson`, `.json`) or KML (`.kml`) file, relative to the configuration
+/// file. The marine regions file is resolved relative to the configuration
+/// file as well.
+///
 /// # Parameters
 ///
 /// - `path`: Path to a YAML file containing serialized [`ApplicationConfig`] data.
@@ -15,10 +21,90 @@ use crate::error::{Error, Result};
 ///
 /// # Errors
 ///
-/// Returns [`Error::Config`] if the file cannot be read or the YAML is invalid.
+/// Returns [`Error::Config`] if the file cannot be read or the YAML is invalid,
+/// if an override file cannot be read, is invalid or contains no polygons, or
+/// if the Lightroom GPS format is not one Lightroom understands.
 pub fn load_config(path: &Path) -> Result<ApplicationConfig> {
     let content = std::fs::read_to_string(path)
         .map_err(|e| Error::Config(format!("could not read {}: {e}", path.display())))?;
-    serde_saphyr::from_str(&content)
-        .map_err(|e| Error::Config(format!("invalid config {}: {e}", path.display())))
+    let mut config: ApplicationConfig = serde_saphyr::from_str(&content)
+        .map_err(|e| Error::Config(format!("invalid config {}: {e}", path.display())))?;
+
+    if !matches!(
+        config.lightroom.gps_format,
+        CoordinateFormat::Decimal | CoordinateFormat::Dms | CoordinateFormat::Ddm
+    ) {
+        return Err(Error::Config(format!(
+            "invalid config {}: Lightroom only understands the decimal, dms and ddm GPS formats",
+            path.display()
+        )));
+    }
+
+    let base = path.parent().unwrap_or(Path::new("."));
+    for (name, location) in config.locations.iter_mut() {
+        if let Some(file) = &location.file {
+            location.polygons = load_polygons(&base.join(file))
+                .map_err(|e| Error::Config(format!("location `{name}`: {e}")))?;
+        }
+    }
+    if let Some(path) = &mut config.body_of_water.marine_regions {
+        *path = base.join(&*path);
+    }
+
+    Ok(config)
+}
+
+/// Load all polygons from a GeoJSON or KML file.
+fn load_polygons(path: &Path) -> Result<Vec<Polygon>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| Error::Config(format!("could not read {}: {e}", path.display())))?;
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase);
+
+    let polygons = match extension.as_deref() {
+        Some("geojson" | "json") => geojson::parse_polygons(&content)?,
+        Some("kml") => kml::parse_polygons(&content)?,
+        _ => {
+            return Err(Error::Config(format!(
+                "{} is neither a GeoJSON nor a KML file",
+                path.display()
+            )));
+        }
+    };
+    if polygons.is_empty() {
+        return Err(Error::Config(format!(
+            "{} contains no polygons",
+            path.display()
+        )));
+    }
+
+    Ok(polygons)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lightroom_gps_format() {
+        let directory = std::env::temp_dir().join(format!("config-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("overrides.yaml");
+        let write = |gps_format| {
+            let mut config = ApplicationConfig::default();
+            config.lightroom.gps_format = gps_format;
+            std::fs::write(&path, serde_saphyr::to_string(&config).unwrap()).unwrap();
+        };
+
+        write(CoordinateFormat::Ddm);
+        let config = load_config(&path).unwrap();
+        assert_eq!(CoordinateFormat::Ddm, config.lightroom.gps_format);
+
+        write(CoordinateFormat::Utm);
+        assert!(matches!(load_config(&path), Err(Error::Config(_))));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
 }
@@ -32,15 +32,27 @@ impl DatabaseManager {
             std::fs::File::create(cache_path)?;
         }
 
-        let macdive_url = format!("sqlite://{}?mode=ro", macdive_path.display());
         let cache_url = format!("sqlite://{}", cache_path.display());
 
-        let macdive = Database::connect(&macdive_url).await?;
+        let macdive = Self::read_only(macdive_path).await?;
         let cache = Database::connect(&cache_url).await?;
 
         Ok(Self { macdive, cache })
     }
 
+    /// Open a read-only connection to an existing SQLite database.
+    ///
+    /// Used for the MacDive database and for other applications' databases,
+    /// such as Lightroom Classic catalogs, which must never be modified.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::error::Error::Database`] if the connection fails.
+    pub async fn read_only(path: &Path) -> Result<DbConn> {
+        let url = format!("sqlite://{}?mode=ro", path.display());
+        Ok(Database::connect(&url).await?)
+    }
+
     /// Returns a read-only connection to the MacDive database.
     pub fn macdive(&self) -> &DbConn {
         &self.macdive
@@ -1,9 +1,10 @@
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::{Display, Formatter};
 use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
 
-use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeDelta, Utc};
 use google_maps::LatLng;
 use rust_decimal::{Decimal, prelude::FromPrimitive};
 use rust_decimal_macros::dec;
@@ -82,15 +83,301 @@ impl DecimalToDms for LatLng {
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct ApplicationConfig {
     pub locations: HashMap<String, LocationOverride>,
     pub critters: CritterConfig,
+    #[serde(default)]
+    pub lightroom: LightroomConfig,
+    #[serde(default)]
+    pub geocoding: GeocodingConfig,
+    #[serde(default)]
+    pub body_of_water: BodyOfWaterConfig,
+    #[serde(default)]
+    pub elevation: ElevationConfig,
+    #[serde(default)]
+    pub export: ExportConfig,
 }
 
 impl ApplicationConfig {
+    /// All location overrides in the order they are matched.
+    ///
+    /// Overrides with a higher priority come first; among equal priorities
+    /// the smallest area comes first, then the override name, so overlapping
+    /// areas always resolve the same way.
     pub fn locations(&self) -> Vec<LocationOverride> {
-        self.locations.values().cloned().collect()
+        self.named_locations()
+            .into_iter()
+            .map(|(_, location)| location)
+            .collect()
+    }
+
+    /// All location overrides with their names, in the order they are matched.
+    ///
+    /// See [`ApplicationConfig::locations`].
+    pub fn named_locations(&self) -> Vec<(String, LocationOverride)> {
+        let mut locations: Vec<(&String, &LocationOverride, f64)> = self
+            .locations
+            .iter()
+            .map(|(name, location)| (name, location, location.area()))
+            .collect();
+        locations.sort_by(|(a_name, a, a_area), (b_name, b, b_area)| {
+            b.priority
+                .cmp(&a.priority)
+                .then(a_area.total_cmp(b_area))
+                .then(a_name.cmp(b_name))
+        });
+        locations
+            .into_iter()
+            .map(|(name, location, _)| (name.to_owned(), location.clone()))
+            .collect()
+    }
+}
+
+impl From<ApplicationConfig> for LightroomConfig {
+    fn from(config: ApplicationConfig) -> Self {
+        config.lightroom
+    }
+}
+
+/// Settings for generated Lightroom metadata presets.
+///
+/// All format strings may reference any [`DiveSite`] field as a placeholder,
+/// e.g. `{country}`, `{state}`, `{region}`, `{locality}`, `{body_of_water}`,
+/// `{name}` or `{uuid}`. See [`DiveSite::placeholder`] for the full list.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LightroomConfig {
+    /// Display title shown in Lightroom's preset menu.
+    pub title: String,
+    /// Internal (non-localized) preset name; defaults to the title.
+    pub internal_name: Option<String>,
+    /// File name relative to the metadata presets folder, without the
+    /// `.lrtemplate` extension. May contain `/` to place presets in subfolders.
+    pub file_name: String,
+    /// IPTC creator written into every preset.
+    pub creator: Option<String>,
+    /// Copyright notice written into every preset.
+    pub copyright: Option<String>,
+    /// Keywords written into every preset. Keywords referencing a placeholder
+    /// without a value for the site (e.g. `{body_of_water}`) are skipped.
+    pub keywords: Vec<String>,
+    /// Six digit IPTC scene code, e.g. `011300` for under-water. Unset by
+    /// default.
+    pub scene: Option<String>,
+    /// Notation of the GPS field. Lightroom only understands degree based
+    /// notations (`decimal`, `dms` and `ddm`); other notations are rejected
+    /// when the configuration is loaded.
+    pub gps_format: CoordinateFormat,
+}
+
+impl Default for LightroomConfig {
+    fn default() -> Self {
+        Self {
+            title: String::from("[Location] {region}: {name}"),
+            internal_name: None,
+            file_name: String::from("MacDive-{uuid}"),
+            creator: None,
+            copyright: None,
+            keywords: vec![String::from("{body_of_water}")],
+            scene: None,
+            gps_format: CoordinateFormat::Dms,
+        }
+    }
+}
+
+/// Notations for GPS coordinates.
+///
+/// See [`crate::util::coordinates`] for examples of each notation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CoordinateFormat {
+    /// Decimal degrees, e.g. `12.150000, -68.280000`.
+    Decimal,
+    /// Degrees, minutes and seconds, e.g. `12°9'0" N 68°16'48" W`.
+    #[default]
+    Dms,
+    /// Degrees and decimal minutes, e.g. `12°9.000' N 68°16.800' W`.
+    Ddm,
+    /// Universal Transverse Mercator, e.g. `19P 578336 1343244`.
+    Utm,
+    /// Military Grid Reference System, e.g. `19P EP 78336 43244`.
+    Mgrs,
+    /// Open Location Code, e.g. `774H5P2C+22`.
+    #[serde(rename = "pluscode")]
+    PlusCode,
+}
+
+/// Reverse geocoding services.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GeocodingProvider {
+    /// Google Maps Geocoding API, requires an API key.
+    #[default]
+    Google,
+    /// Nominatim (OpenStreetMap), no API key required.
+    Nominatim,
+    /// OpenCage geocoding API, requires an API key.
+    OpenCage,
+    /// Local GeoNames dumps, works offline.
+    GeoNames,
+}
+
+impl GeocodingProvider {
+    /// Returns `true` if the provider works without network access.
+    pub fn is_local(&self) -> bool {
+        matches!(self, GeocodingProvider::GeoNames)
+    }
+}
+
+impl Display for GeocodingProvider {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GeocodingProvider::Google => write!(f, "google"),
+            GeocodingProvider::Nominatim => write!(f, "nominatim"),
+            GeocodingProvider::OpenCage => write!(f, "opencage"),
+            GeocodingProvider::GeoNames => write!(f, "geonames"),
+        }
+    }
+}
+
+/// Settings for reverse geocoding dive sites.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GeocodingConfig {
+    /// The geocoding service, Google Maps by default.
+    pub provider: GeocodingProvider,
+    /// Alternative API endpoint, e.g. a self-hosted Nominatim instance.
+    /// Not supported for Google Maps.
+    pub base_url: Option<String>,
+    /// API key for Google Maps or OpenCage.
+    pub api_key: Option<String>,
+    /// Contact e-mail sent to Nominatim, as asked for by its usage policy.
+    pub email: Option<String>,
+    /// Directory with the GeoNames `citiesNNN.txt`, `admin1CodesASCII.txt`
+    /// and (optionally) `admin2Codes.txt` and `countryInfo.txt` dumps.
+    pub geonames_path: Option<PathBuf>,
+    /// Maximum distance in meters between a site and the nearest GeoNames
+    /// city to use it as locality, 10 km by default.
+    pub max_distance: Option<f64>,
+    /// Google Maps address component mapping per ISO country code, taking
+    /// precedence over the built-in mappings.
+    pub address_components: BTreeMap<String, AddressMapping>,
+}
+
+/// Settings for detecting the body of water of dive sites.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BodyOfWaterConfig {
+    /// GeoJSON file with named marine regions, e.g. the IHO sea areas from
+    /// marineregions.org, relative to the configuration file. Detection is
+    /// disabled without it.
+    pub marine_regions: Option<PathBuf>,
+    /// Feature property holding the name of a region.
+    pub name_property: String,
+    /// Maximum distance in meters between a site outside of all regions and
+    /// the nearest one, e.g. for sites just inside the coastline.
+    pub max_distance: f64,
+}
+
+impl Default for BodyOfWaterConfig {
+    fn default() -> Self {
+        Self {
+            marine_regions: None,
+            name_property: String::from("NAME"),
+            max_distance: 5_000.0,
+        }
+    }
+}
+
+/// Settings for exported and listed dive sites.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ExportConfig {
+    /// Notation of coordinates in reports and site descriptions.
+    pub coordinate_format: CoordinateFormat,
+}
+
+/// Settings for the elevation of dive sites.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ElevationConfig {
+    /// Directory with SRTM `.hgt` tiles (e.g. `N12W069.hgt`) to look up the
+    /// elevation of fresh water sites without an altitude.
+    pub srtm_path: Option<PathBuf>,
+    /// Sites above this many meters are flagged as altitude dives.
+    pub altitude_threshold: f32,
+}
+
+impl Default for ElevationConfig {
+    fn default() -> Self {
+        Self {
+            srtm_path: None,
+            altitude_threshold: 300.0,
+        }
+    }
+}
+
+/// Which Google Maps address component types fill the location fields of a
+/// dive site, each in order of preference.
+///
+/// Types are the codes used by the Geocoding API, e.g.
+/// `administrative_area_level_2`, `colloquial_area` or `natural_feature`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AddressMapping {
+    pub state: Vec<String>,
+    pub region: Vec<String>,
+    pub locality: Vec<String>,
+    /// Suffixes removed from region names, e.g. `County` or `Regency`.
+    pub region_suffixes: Vec<String>,
+    /// Drop region names that do not end with one of `region_suffixes`.
+    pub require_region_suffix: bool,
+}
+
+impl Default for AddressMapping {
+    /// Administrative level 1 as state, level 2 as region if it is a county,
+    /// and the locality.
+    fn default() -> Self {
+        Self {
+            state: vec![String::from("administrative_area_level_1")],
+            region: vec![String::from("administrative_area_level_2")],
+            locality: vec![String::from("locality")],
+            region_suffixes: vec![String::from("County")],
+            require_region_suffix: true,
+        }
+    }
+}
+
+impl AddressMapping {
+    /// Clean up a region name according to `region_suffixes`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use macdive_toolbox_core::domain::AddressMapping;
+    ///
+    /// let mapping = AddressMapping::default();
+    /// assert_eq!(Some(String::from("Monroe")), mapping.region_name("Monroe County"));
+    /// assert_eq!(None, mapping.region_name("Bonaire"));
+    /// ```
+    pub fn region_name(&self, name: &str) -> Option<String> {
+        let name = name.trim();
+        match self
+            .region_suffixes
+            .iter()
+            .find_map(|suffix| name.strip_suffix(suffix.as_str()))
+        {
+            Some(stripped) => Some(stripped.trim().to_string()),
+            None if self.require_region_suffix => None,
+            None => Some(name.to_string()),
+        }
+    }
+}
+
+impl From<ApplicationConfig> for GeocodingConfig {
+    fn from(config: ApplicationConfig) -> Self {
+        config.geocoding
     }
 }
 
@@ -100,6 +387,29 @@ pub type CritterNameSubstitutions = HashMap<String, String>;
 pub struct CritterConfig {
     pub name_substitutions: CritterNameSubstitutions,
     pub categories: CritterCategoryConfig,
+    #[serde(default)]
+    pub keywords: CritterKeywordConfig,
+}
+
+/// Settings for the exported Lightroom critter keyword hierarchy.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CritterKeywordConfig {
+    /// Use the critter categories (see [`TaxonGroupName`]) as the top level
+    /// instead of the phylum.
+    pub group_by_category: bool,
+    /// Top level keyword under which critters logged on a dive are written
+    /// into photo sidecars as candidates to be confirmed by hand.
+    pub candidate_branch: String,
+}
+
+impl Default for CritterKeywordConfig {
+    fn default() -> Self {
+        Self {
+            group_by_category: false,
+            candidate_branch: String::from("MacDive Candidates"),
+        }
+    }
 }
 
 impl From<ApplicationConfig> for CritterConfig {
@@ -246,9 +556,200 @@ impl Display for TaxonGroupName {
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+/// Test whether a point lies within a ring of `(longitude, latitude)` vertices.
+///
+/// Uses the ray casting algorithm: cast a horizontal ray from the point
+/// and count how many polygon edges it crosses. An odd count means the
+/// point is inside. The ring is implicitly closed (last vertex connects
+/// back to the first).
+fn ring_contains(vertices: &[(f64, f64)], latitude: f64, longitude: f64) -> bool {
+    if vertices.len() < 3 {
+        return false;
+    }
+
+    let (px, py) = (longitude, latitude);
+    let mut inside = false;
+    let n = vertices.len();
+
+    // Walk each edge of the polygon. For each edge from vertex j to
+    // vertex i, check whether a horizontal ray from (px, py) going
+    // in the +x direction crosses that edge.
+    let mut j = n - 1;
+    for i in 0..n {
+        let (xi, yi) = vertices[i];
+        let (xj, yj) = vertices[j];
+
+        // The edge straddles the ray's y-coordinate when exactly one
+        // endpoint is above py and the other is at or below py.
+        let intersects = (yi > py) != (yj > py) && px < (xj - xi) * (py - yi) / (yj - yi) + xi;
+
+        if intersects {
+            inside = !inside;
+        }
+        j = i;
+    }
+
+    inside
+}
+
+/// Approximate area of a ring in square meters.
+///
+/// Uses the shoelace formula on an equirectangular projection around the
+/// ring's mean latitude, which is accurate enough to compare override areas.
+fn ring_area(vertices: &[(f64, f64)]) -> f64 {
+    const METERS_PER_DEGREE: f64 = 111_195.0;
+
+    if vertices.len() < 3 {
+        return 0.0;
+    }
+    let mean_latitude = vertices.iter().map(|(_, lat)| lat).sum::<f64>() / vertices.len() as f64;
+    let scale = mean_latitude.to_radians().cos();
+
+    let n = vertices.len();
+    let twice_area: f64 = (0..n)
+        .map(|i| {
+            let (x1, y1) = vertices[i];
+            let (x2, y2) = vertices[(i + 1) % n];
+            x1 * scale * y2 - x2 * scale * y1
+        })
+        .sum();
+    (twice_area / 2.0).abs() * METERS_PER_DEGREE * METERS_PER_DEGREE
+}
+
+/// Side of the line `p`–`q` that `r` lies on: `1` (left), `-1` (right) or
+/// `0` (on the line, allowing for rounding errors).
+fn orientation(p: (f64, f64), q: (f64, f64), r: (f64, f64)) -> i8 {
+    const EPSILON: f64 = 1e-12;
+    let cross = (q.0 - p.0) * (r.1 - p.1) - (q.1 - p.1) * (r.0 - p.0);
+    if cross > EPSILON {
+        1
+    } else if cross < -EPSILON {
+        -1
+    } else {
+        0
+    }
+}
+
+/// Test whether the segments `a1`–`a2` and `b1`–`b2` properly cross.
+///
+/// Segments that only touch, or overlap along a common line, do not count.
+fn segments_cross(a1: (f64, f64), a2: (f64, f64), b1: (f64, f64), b2: (f64, f64)) -> bool {
+    let d1 = orientation(b1, b2, a1);
+    let d2 = orientation(b1, b2, a2);
+    let d3 = orientation(a1, a2, b1);
+    let d4 = orientation(a1, a2, b2);
+
+    d1 * d2 < 0 && d3 * d4 < 0
+}
+
+/// The edges of a ring, including the one closing it.
+fn ring_edges(ring: &[(f64, f64)]) -> impl Iterator<Item = ((f64, f64), (f64, f64))> + '_ {
+    let n = ring.len();
+    (0..n).map(move |i| (ring[i], ring[(i + 1) % n]))
+}
+
+/// Test whether `point` lies on one of the edges of the ring.
+fn ring_boundary_contains(ring: &[(f64, f64)], point: (f64, f64)) -> bool {
+    ring_edges(ring).any(|(a, b)| {
+        orientation(a, b, point) == 0
+            && point.0 >= a.0.min(b.0)
+            && point.0 <= a.0.max(b.0)
+            && point.1 >= a.1.min(b.1)
+            && point.1 <= a.1.max(b.1)
+    })
+}
+
+/// Test whether `point` lies inside the ring but not on its boundary.
+fn ring_interior_contains(ring: &[(f64, f64)], point: (f64, f64)) -> bool {
+    ring_contains(ring, point.1, point.0) && !ring_boundary_contains(ring, point)
+}
+
+/// A point strictly inside the ring.
+///
+/// Takes the middle of the first stretch of the horizontal line through the
+/// ring's vertex centroid that lies inside the ring.
+fn ring_interior_point(ring: &[(f64, f64)]) -> Option<(f64, f64)> {
+    let y = ring.iter().map(|(_, y)| y).sum::<f64>() / ring.len() as f64;
+    let mut crossings: Vec<f64> = ring_edges(ring)
+        .filter(|(a, b)| (a.1 > y) != (b.1 > y))
+        .map(|(a, b)| a.0 + (y - a.1) * (b.0 - a.0) / (b.1 - a.1))
+        .collect();
+    crossings.sort_by(f64::total_cmp);
+    let point = ((crossings.first()? + crossings.get(1)?) / 2.0, y);
+    ring_interior_contains(ring, point).then_some(point)
+}
+
+/// A polygon with optional holes, vertices as `(longitude, latitude)`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Polygon {
+    /// The outer boundary.
+    pub exterior: Vec<(f64, f64)>,
+    /// Areas cut out of the polygon.
+    #[serde(default)]
+    pub holes: Vec<Vec<(f64, f64)>>,
+}
+
+impl Polygon {
+    /// Test whether a GPS coordinate lies inside the exterior but in none of
+    /// the holes.
+    pub fn contains(&self, latitude: f64, longitude: f64) -> bool {
+        ring_contains(&self.exterior, latitude, longitude)
+            && !self
+                .holes
+                .iter()
+                .any(|hole| ring_contains(hole, latitude, longitude))
+    }
+
+    /// Test whether the exteriors of two polygons overlap.
+    ///
+    /// Only a common interior counts, so neighbouring polygons that share a
+    /// border or a corner do not overlap. Holes are ignored, so polygons that
+    /// only meet inside a hole are still reported as overlapping.
+    pub fn overlaps(&self, other: &Polygon) -> bool {
+        let (a, b) = (&self.exterior, &other.exterior);
+        if a.len() < 3 || b.len() < 3 {
+            return false;
+        }
+
+        // A vertex inside the other polygon, or an interior point of one
+        // polygon inside the other, e.g. for identical polygons whose
+        // vertices all lie on each other's border.
+        let inside = |ring: &[(f64, f64)], points: &[(f64, f64)]| {
+            points
+                .iter()
+                .chain(ring_interior_point(points).as_ref())
+                .any(|point| ring_interior_contains(ring, *point))
+        };
+        inside(a, b)
+            || inside(b, a)
+            || ring_edges(a)
+                .any(|(a1, a2)| ring_edges(b).any(|(b1, b2)| segments_cross(a1, a2, b1, b2)))
+    }
+
+    /// Approximate area in square meters, without the holes.
+    pub fn area(&self) -> f64 {
+        (ring_area(&self.exterior) - self.holes.iter().map(|h| ring_area(h)).sum::<f64>()).max(0.0)
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct LocationOverride {
+    /// Polygon typed in the configuration, vertices as `(longitude, latitude)`
+    #[serde(default)]
     pub area: Vec<(f64, f64)>,
+    /// Holes cut out of `area`
+    #[serde(default)]
+    pub holes: Vec<Vec<(f64, f64)>>,
+    /// GeoJSON or KML file with further (multi)polygons, relative to the
+    /// configuration file
+    pub file: Option<PathBuf>,
+    /// Polygons loaded from `file`
+    #[serde(skip)]
+    pub polygons: Vec<Polygon>,
+    /// Overrides with a higher priority win when areas overlap; among equal
+    /// priorities the smallest area wins
+    #[serde(default)]
+    pub priority: i32,
     /// The full name should be expressed as a verbal name and not as a code
     pub country: Option<String>,
     /// ISO country code of the location where the image was created
@@ -262,51 +763,42 @@ pub struct LocationOverride {
 }
 
 impl LocationOverride {
-    /// Test whether a GPS coordinate falls within this override's polygon.
-    ///
-    /// Uses the ray casting algorithm: cast a horizontal ray from the point
-    /// and count how many polygon edges it crosses. An odd count means the
-    /// point is inside. The polygon is implicitly closed (last vertex connects
-    /// back to the first).
+    /// All polygons of the override: the one typed in the configuration
+    /// followed by those loaded from `file`.
+    pub fn all_polygons(&self) -> impl Iterator<Item = Polygon> + '_ {
+        let inline = (!self.area.is_empty()).then(|| Polygon {
+            exterior: self.area.clone(),
+            holes: self.holes.clone(),
+        });
+        inline.into_iter().chain(self.polygons.iter().cloned())
+    }
+
+    /// Test whether a GPS coordinate falls within one of this override's
+    /// polygons (and none of their holes).
     ///
-    /// Vertices in `area` use geographic convention: `(longitude, latitude)`.
+    /// Vertices use geographic convention: `(longitude, latitude)`.
     ///
     /// # Arguments
     /// * `latitude` - WGS84 latitude in decimal degrees
     /// * `longitude` - WGS84 longitude in decimal degrees
     pub fn contains(&self, latitude: f64, longitude: f64) -> bool {
-        let vertices = &self.area;
-        if vertices.len() < 3 {
-            return false;
-        }
+        self.all_polygons()
+            .any(|polygon| polygon.contains(latitude, longitude))
+    }
 
-        let (px, py) = (longitude, latitude);
-        let mut inside = false;
-        let n = vertices.len();
-
-        // Walk each edge of the polygon. For each edge from vertex j to
-        // vertex i, check whether a horizontal ray from (px, py) going
-        // in the +x direction crosses that edge.
-        let mut j = n - 1;
-        for i in 0..n {
-            let (xi, yi) = vertices[i];
-            let (xj, yj) = vertices[j];
-
-            // The edge straddles the ray's y-coordinate when exactly one
-            // endpoint is above py and the other is at or below py.
-            let intersects = (yi > py) != (yj > py) && px < (xj - xi) * (py - yi) / (yj - yi) + xi;
-
-            if intersects {
-                inside = !inside;
-            }
-            j = i;
-        }
+    /// Approximate total area in square meters.
+    pub fn area(&self) -> f64 {
+        self.all_polygons().map(|polygon| polygon.area()).sum()
+    }
 
-        inside
+    /// Test whether any polygon of this override overlaps one of `other`.
+    pub fn overlaps(&self, other: &LocationOverride) -> bool {
+        self.all_polygons()
+            .any(|a| other.all_polygons().any(|b| a.overlaps(&b)))
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct DiveSite {
     /// Unique Identifier
     pub uuid: Uuid,
@@ -343,10 +835,87 @@ pub struct DiveSite {
     pub altitude: f32,
     /// The name of the body of water where the image was created.
     pub body_of_water: Option<String>,
+    /// Type of water at the dive site (e.g. `Salt` or `Fresh`).
+    pub water_type: Option<String>,
+    /// Difficulty of the dive site as recorded in MacDive (e.g. `Easy`).
+    pub difficulty: Option<String>,
+    /// Free-form notes about the dive site.
+    pub notes: Option<String>,
     /// MacDive Primary ID
     pub site_id: i64,
 }
 
+/// A dive logged in MacDive.
+#[derive(Debug, Clone)]
+pub struct Dive {
+    /// Unique Identifier, if MacDive has one
+    pub uuid: Option<Uuid>,
+    /// Dive number in the log book
+    pub number: Option<i64>,
+    /// MacDive primary ID of the dive site, if any
+    pub site_id: Option<i64>,
+    /// Local wall-clock time the dive started, as shown by the dive computer
+    pub start: NaiveDateTime,
+    /// Dive time
+    pub duration: TimeDelta,
+    /// MacDive Primary ID
+    pub dive_id: i64,
+}
+
+impl Dive {
+    /// Local wall-clock time the dive ended.
+    pub fn end(&self) -> NaiveDateTime {
+        self.start + self.duration
+    }
+}
+
+impl DiveSite {
+    /// Whether MacDive records the site as a fresh water site.
+    pub fn is_fresh_water(&self) -> bool {
+        self.water_type
+            .as_deref()
+            .is_some_and(|water| water.trim().eq_ignore_ascii_case("fresh"))
+    }
+
+    /// Resolve a format string placeholder to the value of the matching field.
+    ///
+    /// Supported placeholders are `uuid`, `name`, `country`, `iso_country_code`,
+    /// `state`, `region`, `locality`, `body_of_water`, `water_type` and `site_id`.
+    /// Missing optional values resolve to `Unknown`. Returns `None` for unknown names.
+    pub fn placeholder(&self, key: &str) -> Option<String> {
+        self.placeholder_value(key)
+            .map(|value| value.unwrap_or_else(|| String::from("Unknown")))
+    }
+
+    /// Like [`DiveSite::placeholder`], but distinguishes missing optional values.
+    ///
+    /// Returns `None` for unknown names and `Some(None)` if the field is known
+    /// but has no (or only a blank) value for this site.
+    pub fn placeholder_value(&self, key: &str) -> Option<Option<String>> {
+        let optional = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+        };
+
+        match key {
+            "uuid" => Some(Some(self.uuid.to_string())),
+            "name" => Some(Some(self.name.to_owned())),
+            "country" => Some(Some(self.country.to_owned())),
+            "iso_country_code" => Some(Some(self.iso_country_code.to_uppercase())),
+            "state" => Some(optional(&self.state)),
+            "region" => Some(optional(&self.region)),
+            "locality" => Some(optional(&self.locality)),
+            "body_of_water" => Some(optional(&self.body_of_water)),
+            "water_type" => Some(optional(&self.water_type)),
+            "site_id" => Some(Some(self.site_id.to_string())),
+            _ => None,
+        }
+    }
+}
+
 impl TryFrom<DiveSite> for LatLng {
     type Error = Error;
 
@@ -439,6 +1008,7 @@ mod tests {
             state: None,
             region: None,
             locality: None,
+            ..Default::default()
         }
     }
 
@@ -501,6 +1071,83 @@ mod tests {
         assert!(!bonaire.contains(12.17, -68.98));
     }
 
+    #[test]
+    fn test_contains_polygon_with_hole() {
+        let mut ring = override_with_area(vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)]);
+        ring.holes = vec![vec![(4.0, 4.0), (6.0, 4.0), (6.0, 6.0), (4.0, 6.0)]];
+        ring.polygons = vec![Polygon {
+            exterior: vec![(20.0, 0.0), (30.0, 0.0), (30.0, 10.0)],
+            holes: vec![],
+        }];
+
+        assert!(ring.contains(2.0, 2.0));
+        assert!(!ring.contains(5.0, 5.0));
+        assert!(ring.contains(2.0, 28.0));
+        // 100 - 4 + 50 square degrees, shrunk by the cosine of the latitude.
+        let square_degrees = ring.area() / 111_195.0_f64.powi(2);
+        assert!((145.0..146.0).contains(&square_degrees));
+    }
+
+    #[test]
+    fn test_polygons_overlap() {
+        let square = |x: f64, y: f64, size: f64| Polygon {
+            exterior: vec![(x, y), (x + size, y), (x + size, y + size), (x, y + size)],
+            holes: vec![],
+        };
+
+        // Crossing edges, no vertex of either square inside the other.
+        let cross = Polygon {
+            exterior: vec![(-1.0, 4.0), (11.0, 4.0), (11.0, 6.0), (-1.0, 6.0)],
+            holes: vec![],
+        };
+        assert!(square(0.0, 0.0, 10.0).overlaps(&cross));
+        // Fully contained.
+        assert!(square(0.0, 0.0, 10.0).overlaps(&square(2.0, 2.0, 1.0)));
+        assert!(square(2.0, 2.0, 1.0).overlaps(&square(0.0, 0.0, 10.0)));
+        // Disjoint.
+        assert!(!square(0.0, 0.0, 1.0).overlaps(&square(5.0, 5.0, 1.0)));
+        // Neighbours sharing a border, part of a border or a corner.
+        assert!(!square(0.0, 0.0, 10.0).overlaps(&square(10.0, 0.0, 10.0)));
+        assert!(!square(0.0, 0.0, 10.0).overlaps(&square(10.0, 2.0, 3.0)));
+        assert!(!square(0.0, 0.0, 10.0).overlaps(&square(10.0, 10.0, 3.0)));
+        // Identical, and sharing a border from the inside.
+        assert!(square(0.0, 0.0, 10.0).overlaps(&square(0.0, 0.0, 10.0)));
+        assert!(square(0.0, 0.0, 10.0).overlaps(&square(0.0, 0.0, 5.0)));
+    }
+
+    #[test]
+    fn test_locations_order() {
+        let square = |size: f64, priority: i32| LocationOverride {
+            priority,
+            locality: Some(format!("{size}/{priority}")),
+            ..override_with_area(vec![(0.0, 0.0), (size, 0.0), (size, size), (0.0, size)])
+        };
+        let config = ApplicationConfig {
+            locations: HashMap::from([
+                (String::from("large"), square(10.0, 0)),
+                (String::from("small"), square(1.0, 0)),
+                (String::from("important"), square(20.0, 1)),
+                (String::from("same"), square(1.0, 0)),
+            ]),
+            ..Default::default()
+        };
+
+        let order: Vec<Option<String>> = config
+            .locations()
+            .into_iter()
+            .map(|location| location.locality)
+            .collect();
+        assert_eq!(
+            vec![
+                Some(String::from("20/1")),
+                Some(String::from("1/0")),
+                Some(String::from("1/0")),
+                Some(String::from("10/0")),
+            ],
+            order
+        );
+    }
+
     // --- NSDate tests ---
 
     #[test]
@@ -12,8 +12,16 @@ pub enum Error {
     InvalidLongitude,
     #[error("invalid GPS coordinates")]
     InvalidGps,
+    #[error("invalid coordinates: {0}")]
+    InvalidCoordinates(String),
     #[error("geocoding API failed")]
     GeocodingFailed,
+    #[error("GeoNames data error: {0}")]
+    GeoNames(String),
+    #[error("SRTM elevation data error: {0}")]
+    Srtm(String),
+    #[error("dive site import error: {0}")]
+    SiteImport(String),
     #[error("configuration error: {0}")]
     Config(String),
     #[error("species name parse error: {0}")]
@@ -32,8 +40,12 @@ pub enum Error {
     Template(String),
     #[error("invalid UUID in Lightroom template")]
     InvalidUuid(#[from] uuid::Error),
-    #[error("error parsing existing Lightroom template")]
-    LightroomParsing,
+    #[error("error parsing existing Lightroom template: {0}")]
+    LightroomParsing(String),
+    #[error("EXIF error: {0}")]
+    Exif(String),
+    #[error("XMP sidecar error: {0}")]
+    Xmp(String),
     #[error("MTP device error: {0}")]
     Mtp(String),
     #[error("MTP storage error: folder not found: {0}")]
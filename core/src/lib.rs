@@ -2,6 +2,7 @@ pub mod config;
 pub mod db;
 pub mod domain;
 pub mod error;
+pub mod lrcat;
 pub mod macdive;
 pub mod parsers;
 pub mod services;
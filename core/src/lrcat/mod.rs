@@ -0,0 +1,7 @@
+//! Read-only access to Adobe Lightroom Classic catalogs (`.lrcat`).
+//!
+//! Catalogs are SQLite databases. Only the handful of tables needed to audit
+//! photo locations are queried, using raw SQL since the schema is owned by
+//! Lightroom and not modelled as SeaORM entities.
+
+pub mod queries;
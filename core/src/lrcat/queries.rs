@@ -0,0 +1,97 @@
+use chrono::NaiveDateTime;
+use sea_orm::{DbBackend, DbConn, FromQueryResult, Statement};
+
+use crate::error::Result;
+
+/// A photo in a Lightroom catalog with its GPS and IPTC location metadata.
+#[derive(Debug, Clone, FromQueryResult)]
+pub struct CatalogPhoto {
+    /// Lightroom's local image ID.
+    pub id: i64,
+    /// Absolute path of the master file.
+    pub path: String,
+    /// Capture time as stored by Lightroom (ISO 8601, local time).
+    pub capture_time: Option<String>,
+    /// WGS84 latitude in decimal degrees.
+    pub latitude: Option<f64>,
+    /// WGS84 longitude in decimal degrees.
+    pub longitude: Option<f64>,
+    /// IPTC sublocation.
+    pub location: Option<String>,
+    /// IPTC city.
+    pub city: Option<String>,
+    /// IPTC state/province.
+    pub state: Option<String>,
+    /// IPTC country name.
+    pub country: Option<String>,
+    /// ISO 3166-1 country code.
+    pub iso_country_code: Option<String>,
+}
+
+impl CatalogPhoto {
+    /// Local wall-clock capture time.
+    ///
+    /// Lightroom stores capture times like `2024-03-15T10:12:33.45`, optionally
+    /// followed by a UTC offset, which is ignored here since dive computers log
+    /// local time as well.
+    pub fn taken(&self) -> Option<NaiveDateTime> {
+        let value = self.capture_time.as_deref()?.trim();
+        let value = value.get(..19).unwrap_or(value);
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S").ok()
+    }
+
+    /// Returns `true` if the photo carries neither GPS nor IPTC location data.
+    pub fn has_no_location(&self) -> bool {
+        let empty = |value: &Option<String>| value.as_deref().is_none_or(|v| v.trim().is_empty());
+
+        (self.latitude.is_none() || self.longitude.is_none())
+            && empty(&self.location)
+            && empty(&self.city)
+            && empty(&self.state)
+            && empty(&self.country)
+            && empty(&self.iso_country_code)
+    }
+}
+
+/// Fetch all photos of a Lightroom catalog, ordered by capture time.
+///
+/// # Arguments
+///
+/// * `db` - A read-only connection to the `.lrcat` file.
+///
+/// # Errors
+///
+/// Returns [`crate::error::Error::Database`] if the query fails, e.g. because
+/// the file is not a Lightroom Classic catalog.
+pub async fn photos(db: &DbConn) -> Result<Vec<CatalogPhoto>> {
+    let sql = r#"
+        SELECT image.id_local AS id,
+               root.absolutePath || folder.pathFromRoot || file.idx_filename AS path,
+               image.captureTime AS capture_time,
+               CASE WHEN exif.hasGPS THEN exif.gpsLatitude END AS latitude,
+               CASE WHEN exif.hasGPS THEN exif.gpsLongitude END AS longitude,
+               location.value AS location,
+               city.value AS city,
+               state.value AS state,
+               country.value AS country,
+               iso.value AS iso_country_code
+          FROM Adobe_images image
+          JOIN AgLibraryFile file ON file.id_local = image.rootFile
+          JOIN AgLibraryFolder folder ON folder.id_local = file.folder
+          JOIN AgLibraryRootFolder root ON root.id_local = folder.rootFolder
+          LEFT JOIN AgHarvestedExifMetadata exif ON exif.image = image.id_local
+          LEFT JOIN AgHarvestedIptcMetadata iptc ON iptc.image = image.id_local
+          LEFT JOIN AgInternedIptcLocation location ON location.id_local = iptc.locationRef
+          LEFT JOIN AgInternedIptcCity city ON city.id_local = iptc.cityRef
+          LEFT JOIN AgInternedIptcState state ON state.id_local = iptc.stateRef
+          LEFT JOIN AgInternedIptcCountry country ON country.id_local = iptc.countryRef
+          LEFT JOIN AgInternedIptcIsoCountryCode iso ON iso.id_local = iptc.isoCountryCodeRef
+         ORDER BY image.captureTime
+    "#;
+
+    Ok(
+        CatalogPhoto::find_by_statement(Statement::from_string(DbBackend::Sqlite, sql))
+            .all(db)
+            .await?,
+    )
+}
@@ -1,6 +1,9 @@
 use crate::error::Result;
 use ::entity::prelude::*;
-use sea_orm::{ColumnTrait, DbConn, EntityTrait, QueryFilter};
+use sea_orm::{
+    ColumnTrait, Condition, ConnectionTrait, DbConn, DbErr, EntityTrait, FromQueryResult,
+    QueryFilter, QueryOrder, Statement,
+};
 
 /// Fetch all dive sites that have GPS coordinates.
 ///
@@ -22,6 +25,91 @@ pub async fn sites(db: &DbConn) -> Result<Vec<::entity::dive_site::Model>> {
         .await?)
 }
 
+/// Fetch all dive sites, including those without GPS coordinates.
+///
+/// # Arguments
+///
+/// * `db` - A read-only connection to the MacDive SQLite database.
+///
+/// # Errors
+///
+/// Returns [`crate::error::Error::Database`] if the query fails.
+pub async fn all_sites(db: &DbConn) -> Result<Vec<::entity::dive_site::Model>> {
+    Ok(DiveSite::find().all(db).await?)
+}
+
+/// Fetch all dives that have a start date, ordered by start date.
+///
+/// # Arguments
+///
+/// * `db` - A read-only connection to the MacDive SQLite database.
+///
+/// # Errors
+///
+/// Returns [`crate::error::Error::Database`] if the query fails.
+pub async fn dives(db: &DbConn) -> Result<Vec<::entity::dive::Model>> {
+    Ok(Dive::find()
+        .filter(
+            Condition::any()
+                .add(::entity::dive::Column::RawDate.is_not_null())
+                .add(::entity::dive::Column::Date.is_not_null()),
+        )
+        .order_by_asc(::entity::dive::Column::RawDate)
+        .all(db)
+        .await?)
+}
+
+/// Fetch all dives, including those without a start date.
+///
+/// # Arguments
+///
+/// * `db` - A read-only connection to the MacDive SQLite database.
+///
+/// # Errors
+///
+/// Returns [`crate::error::Error::Database`] if the query fails.
+pub async fn all_dives(db: &DbConn) -> Result<Vec<::entity::dive::Model>> {
+    Ok(Dive::find().all(db).await?)
+}
+
+/// Look up the Core Data entity number of the entity called `name`.
+///
+/// # Errors
+///
+/// Returns [`crate::error::Error::Database`] if the query fails or MacDive's
+/// data model has no such entity.
+async fn entity_number(db: &DbConn, name: &str) -> Result<i64> {
+    Ok(CoreDataEntity::find()
+        .filter(::entity::core_data_entity::Column::Name.eq(name))
+        .one(db)
+        .await?
+        .ok_or_else(|| DbErr::Custom(format!("Core Data entity `{name}` not found")))?
+        .ent)
+}
+
+/// Fetch all critter sightings, i.e. which critters were logged on which dive.
+///
+/// # Arguments
+///
+/// * `db` - A read-only connection to the MacDive SQLite database.
+///
+/// # Errors
+///
+/// Returns [`crate::error::Error::Database`] if the query fails.
+pub async fn dive_critters(db: &DbConn) -> Result<Vec<::entity::dive_critter::Model>> {
+    let (dive, critter) = (
+        entity_number(db, "Dive").await?,
+        entity_number(db, "Critter").await?,
+    );
+    let statement = Statement::from_string(
+        db.get_database_backend(),
+        ::entity::dive_critter::Model::select(dive, critter),
+    );
+    Ok(::entity::dive_critter::Model::find_by_statement(statement)
+        .all(db)
+        .await?)
+}
+
 /// Fetch all critters from the MacDive database.
 ///
 /// # Arguments
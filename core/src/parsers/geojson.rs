@@ -0,0 +1,233 @@
+//! Extract polygons from GeoJSON documents.
+//!
+//! Supports `Polygon` and `MultiPolygon` geometries, on their own or wrapped
+//! in a `Feature`, `FeatureCollection` or `GeometryCollection`. Other
+//! geometries (points, lines) are ignored.
+
+use serde_json::{Map, Value};
+
+use crate::domain::Polygon;
+use crate::error::{Error, Result};
+
+fn invalid(reason: &str) -> Error {
+    Error::Config(format!("invalid GeoJSON: {reason}"))
+}
+
+/// Parse a linear ring of `[longitude, latitude(, altitude)]` positions.
+fn parse_ring(value: &Value) -> Result<Vec<(f64, f64)>> {
+    value
+        .as_array()
+        .ok_or_else(|| invalid("ring is not an array"))?
+        .iter()
+        .map(|position| match position.as_array().map(Vec::as_slice) {
+            Some([lon, lat, ..]) => lon
+                .as_f64()
+                .zip(lat.as_f64())
+                .ok_or_else(|| invalid("position is not a number")),
+            _ => Err(invalid("position needs a longitude and latitude")),
+        })
+        .collect()
+}
+
+/// Parse the coordinates of a `Polygon`: the exterior ring followed by holes.
+fn parse_polygon(value: &Value) -> Result<Polygon> {
+    let mut rings = value
+        .as_array()
+        .ok_or_else(|| invalid("polygon is not an array"))?
+        .iter()
+        .map(parse_ring)
+        .collect::<Result<Vec<_>>>()?
+        .into_iter();
+
+    Ok(Polygon {
+        exterior: rings
+            .next()
+            .ok_or_else(|| invalid("polygon without rings"))?,
+        holes: rings.collect(),
+    })
+}
+
+fn collect_polygons(value: &Value, polygons: &mut Vec<Polygon>) -> Result<()> {
+    let coordinates = || {
+        value
+            .get("coordinates")
+            .ok_or_else(|| invalid("geometry without coordinates"))
+    };
+
+    match value.get("type").and_then(Value::as_str) {
+        Some("Polygon") => polygons.push(parse_polygon(coordinates()?)?),
+        Some("MultiPolygon") => {
+            for polygon in coordinates()?
+                .as_array()
+                .ok_or_else(|| invalid("multipolygon is not an array"))?
+            {
+                polygons.push(parse_polygon(polygon)?);
+            }
+        }
+        Some("Feature") => {
+            if let Some(geometry) = value.get("geometry").filter(|g| !g.is_null()) {
+                collect_polygons(geometry, polygons)?;
+            }
+        }
+        Some("FeatureCollection") => {
+            for feature in value
+                .get("features")
+                .and_then(Value::as_array)
+                .ok_or_else(|| invalid("feature collection without features"))?
+            {
+                collect_polygons(feature, polygons)?;
+            }
+        }
+        Some("GeometryCollection") => {
+            for geometry in value
+                .get("geometries")
+                .and_then(Value::as_array)
+                .ok_or_else(|| invalid("geometry collection without geometries"))?
+            {
+                collect_polygons(geometry, polygons)?;
+            }
+        }
+        Some(_) => {}
+        None => return Err(invalid("object without type")),
+    }
+
+    Ok(())
+}
+
+/// The polygons of a GeoJSON feature together with its properties.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolygonFeature {
+    pub properties: Map<String, Value>,
+    pub polygons: Vec<Polygon>,
+}
+
+fn collect_features(value: &Value, features: &mut Vec<PolygonFeature>) -> Result<()> {
+    match value.get("type").and_then(Value::as_str) {
+        Some("FeatureCollection") => {
+            for feature in value
+                .get("features")
+                .and_then(Value::as_array)
+                .ok_or_else(|| invalid("feature collection without features"))?
+            {
+                collect_features(feature, features)?;
+            }
+        }
+        Some(_) => {
+            let mut polygons = Vec::new();
+            collect_polygons(value, &mut polygons)?;
+            if !polygons.is_empty() {
+                features.push(PolygonFeature {
+                    properties: value
+                        .get("properties")
+                        .and_then(Value::as_object)
+                        .cloned()
+                        .unwrap_or_default(),
+                    polygons,
+                });
+            }
+        }
+        None => return Err(invalid("object without type")),
+    }
+
+    Ok(())
+}
+
+/// Parse all features with polygons of a GeoJSON document.
+///
+/// Bare geometries are returned as features without properties.
+///
+/// # Errors
+///
+/// Returns [`Error::Json`] if the content is not JSON, or [`Error::Config`] if
+/// it is not valid GeoJSON.
+pub fn parse_features(content: &str) -> Result<Vec<PolygonFeature>> {
+    let document: Value = serde_json::from_str(content)?;
+    let mut features = Vec::new();
+    collect_features(&document, &mut features)?;
+    Ok(features)
+}
+
+/// Parse all polygons of a GeoJSON document.
+///
+/// # Errors
+///
+/// Returns [`Error::Json`] if the content is not JSON, or [`Error::Config`] if
+/// it is not valid GeoJSON.
+pub fn parse_polygons(content: &str) -> Result<Vec<Polygon>> {
+    let document: Value = serde_json::from_str(content)?;
+    let mut polygons = Vec::new();
+    collect_polygons(&document, &mut polygons)?;
+    Ok(polygons)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_feature_collection() {
+        let content = r#"{
+            "type": "FeatureCollection",
+            "features": [
+                {
+                    "type": "Feature",
+                    "properties": { "name": "Marine Park" },
+                    "geometry": {
+                        "type": "MultiPolygon",
+                        "coordinates": [
+                            [
+                                [[0, 0], [10, 0], [10, 10], [0, 10], [0, 0]],
+                                [[4, 4], [6, 4], [6, 6], [4, 6], [4, 4]]
+                            ],
+                            [[[20, 0, 5], [30, 0, 5], [30, 10, 5], [20, 0, 5]]]
+                        ]
+                    }
+                },
+                {
+                    "type": "Feature",
+                    "properties": {},
+                    "geometry": { "type": "Point", "coordinates": [1, 1] }
+                }
+            ]
+        }"#;
+
+        let polygons = parse_polygons(content).unwrap();
+        assert_eq!(2, polygons.len());
+        assert_eq!(5, polygons[0].exterior.len());
+        assert_eq!(1, polygons[0].holes.len());
+        assert_eq!((30.0, 10.0), polygons[1].exterior[2]);
+    }
+
+    #[test]
+    fn test_parse_invalid_geometry() {
+        assert!(parse_polygons(r#"{ "type": "Polygon", "coordinates": [[[1]]] }"#).is_err());
+        assert!(parse_polygons(r#"{ "coordinates": [] }"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_features() {
+        let content = r#"{
+            "type": "FeatureCollection",
+            "features": [
+                {
+                    "type": "Feature",
+                    "properties": { "NAME": "Caribbean Sea" },
+                    "geometry": {
+                        "type": "Polygon",
+                        "coordinates": [[[0, 0], [10, 0], [10, 10], [0, 0]]]
+                    }
+                },
+                {
+                    "type": "Feature",
+                    "properties": { "NAME": "Nowhere" },
+                    "geometry": null
+                }
+            ]
+        }"#;
+
+        let features = parse_features(content).unwrap();
+        assert_eq!(1, features.len());
+        assert_eq!("Caribbean Sea", features[0].properties["NAME"]);
+        assert_eq!(4, features[0].polygons[0].exterior.len());
+    }
+}
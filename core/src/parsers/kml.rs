@@ -0,0 +1,153 @@
+//! Extract polygons from KML documents, e.g. areas drawn in Google Earth.
+//!
+//! Every `<Polygon>` in the document is returned, including those nested in
+//! `<MultiGeometry>`, `<Folder>` or `<Document>` elements. Other geometries
+//! are ignored.
+
+use quick_xml::Reader;
+use quick_xml::events::Event;
+
+use crate::domain::Polygon;
+use crate::error::{Error, Result};
+
+fn invalid(reason: impl std::fmt::Display) -> Error {
+    Error::Config(format!("invalid KML: {reason}"))
+}
+
+/// Parse a `<coordinates>` value: whitespace separated `lon,lat[,alt]` tuples.
+fn parse_coordinates(value: &str) -> Result<Vec<(f64, f64)>> {
+    value
+        .split_whitespace()
+        .map(|tuple| {
+            let mut parts = tuple.split(',').map(|part| part.trim().parse::<f64>());
+            match (parts.next(), parts.next()) {
+                (Some(Ok(lon)), Some(Ok(lat))) => Ok((lon, lat)),
+                _ => Err(invalid(format!("invalid coordinates `{tuple}`"))),
+            }
+        })
+        .collect()
+}
+
+/// The boundary of a polygon that is currently being read.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Boundary {
+    Outer,
+    Inner,
+}
+
+/// Parse all polygons of a KML document.
+///
+/// # Errors
+///
+/// Returns [`Error::Config`] if the document is not well-formed or contains
+/// invalid coordinates.
+pub fn parse_polygons(content: &str) -> Result<Vec<Polygon>> {
+    let mut reader = Reader::from_str(content);
+    let mut polygons = Vec::new();
+    let mut current: Option<Polygon> = None;
+    let mut boundary: Option<Boundary> = None;
+    let mut in_coordinates = false;
+    let mut text = String::new();
+
+    loop {
+        match reader.read_event().map_err(invalid)? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"Polygon" => current = Some(Polygon::default()),
+                b"outerBoundaryIs" => boundary = Some(Boundary::Outer),
+                b"innerBoundaryIs" => boundary = Some(Boundary::Inner),
+                b"coordinates" => {
+                    in_coordinates = true;
+                    text.clear();
+                }
+                _ => {}
+            },
+            Event::Text(e) if in_coordinates => {
+                text.push_str(&e.unescape().map_err(invalid)?);
+            }
+            Event::End(e) => match e.local_name().as_ref() {
+                b"coordinates" => {
+                    in_coordinates = false;
+                    if let (Some(polygon), Some(boundary)) = (current.as_mut(), boundary) {
+                        let ring = parse_coordinates(&text)?;
+                        match boundary {
+                            Boundary::Outer => polygon.exterior = ring,
+                            Boundary::Inner => polygon.holes.push(ring),
+                        }
+                    }
+                }
+                b"outerBoundaryIs" | b"innerBoundaryIs" => boundary = None,
+                b"Polygon" => {
+                    if let Some(polygon) = current.take() {
+                        if polygon.exterior.is_empty() {
+                            return Err(invalid("polygon without outer boundary"));
+                        }
+                        polygons.push(polygon);
+                    }
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(polygons)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_google_earth_polygon() {
+        let content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<kml xmlns="http://www.opengis.net/kml/2.2">
+<Document>
+  <Placemark>
+    <name>Lembeh Strait</name>
+    <LineString><coordinates>125.1,1.4,0 125.2,1.5,0</coordinates></LineString>
+  </Placemark>
+  <Placemark>
+    <name>Marine Park</name>
+    <MultiGeometry>
+      <Polygon>
+        <outerBoundaryIs>
+          <LinearRing>
+            <coordinates>
+              0,0,0 10,0,0 10,10,0 0,10,0 0,0,0
+            </coordinates>
+          </LinearRing>
+        </outerBoundaryIs>
+        <innerBoundaryIs>
+          <LinearRing><coordinates>4,4 6,4 6,6 4,6 4,4</coordinates></LinearRing>
+        </innerBoundaryIs>
+      </Polygon>
+      <Polygon>
+        <outerBoundaryIs>
+          <LinearRing><coordinates>20,0 30,0 30,10 20,0</coordinates></LinearRing>
+        </outerBoundaryIs>
+      </Polygon>
+    </MultiGeometry>
+  </Placemark>
+</Document>
+</kml>
+"#;
+
+        let polygons = parse_polygons(content).unwrap();
+        assert_eq!(2, polygons.len());
+        assert_eq!((10.0, 10.0), polygons[0].exterior[2]);
+        assert_eq!(
+            vec![(4.0, 4.0), (6.0, 4.0), (6.0, 6.0), (4.0, 6.0), (4.0, 4.0)],
+            polygons[0].holes[0]
+        );
+        assert!(polygons[1].holes.is_empty());
+    }
+
+    #[test]
+    fn test_parse_invalid_coordinates() {
+        let content = "<kml><Polygon><outerBoundaryIs><LinearRing>\
+            <coordinates>0,0 north,10</coordinates>\
+            </LinearRing></outerBoundaryIs></Polygon></kml>";
+        assert!(parse_polygons(content).is_err());
+    }
+}
@@ -0,0 +1,537 @@
+//! Parser for the subset of Lua table syntax used by Lightroom `.lrtemplate` files.
+//!
+//! Lightroom stores presets as a single Lua assignment such as
+//! `s = { id = "...", value = { ["com.adobe.city"] = "...", }, }`. This module
+//! parses such documents into a [`LuaValue`] tree and renders them back in the
+//! layout Lightroom itself writes.
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter, Write as _};
+
+use nom::Finish;
+use nom::Parser;
+use nom::branch::alt;
+use nom::bytes::complete::{is_not, tag, take_until, take_while, take_while1};
+use nom::character::complete::{char, multispace1, satisfy};
+use nom::combinator::{all_consuming, cut, map, map_res, opt, recognize, value};
+use nom::error::Error;
+use nom::multi::{many0, separated_list0};
+use nom::sequence::{delimited, pair, preceded, terminated};
+
+/// A key of an entry in a Lua table literal.
+///
+/// Lua distinguishes `[1]` from `["1"]`, so integer keys are kept apart from
+/// string keys to render them back the way they were written.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LuaKey {
+    Integer(i64),
+    String(String),
+}
+
+impl LuaKey {
+    /// Returns the key if it is a [`LuaKey::String`].
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            LuaKey::String(v) => Some(v),
+            LuaKey::Integer(_) => None,
+        }
+    }
+}
+
+impl From<&str> for LuaKey {
+    fn from(value: &str) -> Self {
+        LuaKey::String(value.to_string())
+    }
+}
+
+impl From<String> for LuaKey {
+    fn from(value: String) -> Self {
+        LuaKey::String(value)
+    }
+}
+
+impl From<i64> for LuaKey {
+    fn from(value: i64) -> Self {
+        LuaKey::Integer(value)
+    }
+}
+
+/// A value in a Lua table literal.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LuaValue {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    /// A table that only has positional entries, e.g. `{ "a", "b" }`.
+    Array(Vec<LuaValue>),
+    /// A table with named entries; positional entries are keyed by their
+    /// 1-based index.
+    Table(BTreeMap<LuaKey, LuaValue>),
+}
+
+impl LuaValue {
+    /// Returns the string content if this value is a [`LuaValue::String`].
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            LuaValue::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the table entries if this value is a [`LuaValue::Table`].
+    pub fn as_table(&self) -> Option<&BTreeMap<LuaKey, LuaValue>> {
+        match self {
+            LuaValue::Table(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Render the value with Lightroom's indentation, starting at `depth` tabs.
+    fn write_indented(&self, f: &mut impl std::fmt::Write, depth: usize) -> std::fmt::Result {
+        match self {
+            LuaValue::Nil => f.write_str("nil"),
+            LuaValue::Bool(v) => write!(f, "{v}"),
+            LuaValue::Number(v) => write!(f, "{v}"),
+            LuaValue::String(v) => f.write_str(&quote(v)),
+            LuaValue::Array(items) => {
+                f.write_str("{\n")?;
+                for item in items {
+                    f.write_str(&"\t".repeat(depth + 1))?;
+                    item.write_indented(f, depth + 1)?;
+                    f.write_str(",\n")?;
+                }
+                write!(f, "{}}}", "\t".repeat(depth))
+            }
+            LuaValue::Table(entries) => {
+                f.write_str("{\n")?;
+                for (key, item) in entries {
+                    f.write_str(&"\t".repeat(depth + 1))?;
+                    match key {
+                        LuaKey::Integer(key) => write!(f, "[{key}]")?,
+                        LuaKey::String(key) if is_identifier(key) => f.write_str(key)?,
+                        LuaKey::String(key) => write!(f, "[{}]", quote(key))?,
+                    }
+                    f.write_str(" = ")?;
+                    item.write_indented(f, depth + 1)?;
+                    f.write_str(",\n")?;
+                }
+                write!(f, "{}}}", "\t".repeat(depth))
+            }
+        }
+    }
+}
+
+impl Display for LuaValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.write_indented(f, 0)
+    }
+}
+
+/// Render a top-level assignment `name = value` the way Lightroom writes it.
+pub fn render_assignment(name: &str, value: &LuaValue) -> String {
+    let mut output = String::new();
+    // Writing into a `String` cannot fail.
+    let _ = write!(output, "{name} = ");
+    let _ = value.write_indented(&mut output, 0);
+    output.push('\n');
+    output
+}
+
+/// Quote a string as a Lua string literal.
+fn quote(s: &str) -> String {
+    let mut output = String::with_capacity(s.len() + 2);
+    output.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            _ => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
+
+/// Returns `true` if `s` can be written as a bare Lua identifier key.
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Whitespace and `--` comments (both line and `--[[ ]]` block comments).
+fn ws(input: &str) -> nom::IResult<&str, ()> {
+    value(
+        (),
+        many0(alt((
+            value((), multispace1),
+            value((), preceded(tag("--"), long_bracket)),
+            value((), preceded(tag("--"), opt(is_not("\r\n")))),
+        ))),
+    )
+    .parse(input)
+}
+
+fn token<'a, O>(
+    inner: impl Parser<&'a str, Output = O, Error = Error<&'a str>>,
+) -> impl Parser<&'a str, Output = O, Error = Error<&'a str>> {
+    delimited(ws, inner, ws)
+}
+
+fn identifier(input: &str) -> nom::IResult<&str, &str> {
+    recognize(pair(
+        satisfy(|c| c.is_ascii_alphabetic() || c == '_'),
+        take_while(|c: char| c.is_ascii_alphanumeric() || c == '_'),
+    ))
+    .parse(input)
+}
+
+/// A long bracket string such as `[[text]]` or `[==[text]==]`.
+fn long_bracket(input: &str) -> nom::IResult<&str, String> {
+    let (input, level) = delimited(char('['), take_while(|c| c == '='), char('[')).parse(input)?;
+    let close = format!("]{level}]");
+    let (input, content) = take_until(close.as_str()).parse(input)?;
+    let (input, _) = tag(close.as_str()).parse(input)?;
+    // A newline directly after the opening bracket is not part of the string.
+    let content = content
+        .strip_prefix("\r\n")
+        .or_else(|| content.strip_prefix('\n'))
+        .unwrap_or(content);
+    Ok((input, content.to_string()))
+}
+
+/// A single- or double-quoted string literal with Lua escape sequences.
+fn quoted_string(input: &str) -> nom::IResult<&str, String> {
+    fn body(delimiter: char) -> impl Fn(&str) -> nom::IResult<&str, String> {
+        move |input: &str| {
+            // Decimal escapes produce raw bytes, so the string is collected as
+            // bytes and only decoded once complete.
+            let mut output = Vec::new();
+            let push = |output: &mut Vec<u8>, c: char| {
+                output.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            };
+            let mut chars = input.char_indices();
+            while let Some((idx, c)) = chars.next() {
+                match c {
+                    c if c == delimiter => {
+                        let output = String::from_utf8(output).unwrap_or_else(|err| {
+                            String::from_utf8_lossy(err.as_bytes()).into_owned()
+                        });
+                        return Ok((&input[idx..], output));
+                    }
+                    '\\' => match chars.next() {
+                        Some((_, 'n')) => push(&mut output, '\n'),
+                        Some((_, 'r')) => push(&mut output, '\r'),
+                        Some((_, 't')) => push(&mut output, '\t'),
+                        Some((_, 'a')) => push(&mut output, '\u{7}'),
+                        Some((_, 'b')) => push(&mut output, '\u{8}'),
+                        Some((_, 'f')) => push(&mut output, '\u{c}'),
+                        Some((_, 'v')) => push(&mut output, '\u{b}'),
+                        Some((_, '\n')) => push(&mut output, '\n'),
+                        Some((start, d)) if d.is_ascii_digit() => {
+                            // Decimal escape of up to three digits (`\ddd`) for
+                            // a single byte; like Lua, values above 255 are
+                            // rejected.
+                            let digits: String = input[start..]
+                                .chars()
+                                .take(3)
+                                .take_while(|c| c.is_ascii_digit())
+                                .collect();
+                            for _ in 1..digits.len() {
+                                chars.next();
+                            }
+                            let byte = digits.parse::<u8>().map_err(|_| {
+                                nom::Err::Failure(Error::new(
+                                    &input[start..],
+                                    nom::error::ErrorKind::Digit,
+                                ))
+                            })?;
+                            output.push(byte);
+                        }
+                        Some((_, other)) => push(&mut output, other),
+                        None => break,
+                    },
+                    c => push(&mut output, c),
+                }
+            }
+            Err(nom::Err::Error(Error::new(
+                input,
+                nom::error::ErrorKind::Char,
+            )))
+        }
+    }
+
+    alt((
+        delimited(char('"'), body('"'), char('"')),
+        delimited(char('\''), body('\''), char('\'')),
+    ))
+    .parse(input)
+}
+
+fn string(input: &str) -> nom::IResult<&str, String> {
+    alt((quoted_string, long_bracket)).parse(input)
+}
+
+fn number(input: &str) -> nom::IResult<&str, f64> {
+    alt((
+        map_res(
+            preceded(
+                alt((tag("0x"), tag("0X"))),
+                take_while1(|c: char| c.is_ascii_hexdigit()),
+            ),
+            |digits: &str| i64::from_str_radix(digits, 16).map(|v| v as f64),
+        ),
+        map_res(
+            recognize((
+                opt(char('-')),
+                take_while1(|c: char| c.is_ascii_digit() || c == '.'),
+                opt((
+                    alt((char('e'), char('E'))),
+                    opt(alt((char('+'), char('-')))),
+                    take_while1(|c: char| c.is_ascii_digit()),
+                )),
+            )),
+            str::parse::<f64>,
+        ),
+    ))
+    .parse(input)
+}
+
+/// A table field: `[key] = value`, `name = value` or a positional `value`.
+enum Field {
+    Keyed(LuaKey, LuaValue),
+    Positional(LuaValue),
+}
+
+fn field(input: &str) -> nom::IResult<&str, Field> {
+    alt((
+        map(
+            (
+                delimited(
+                    token(char('[')),
+                    alt((map(string, LuaKey::String), integer_key)),
+                    token(char(']')),
+                ),
+                token(char('=')),
+                cut(lua_value),
+            ),
+            |(key, _, value)| Field::Keyed(key, value),
+        ),
+        map(
+            (token(identifier), token(char('=')), cut(lua_value)),
+            |(key, _, value)| Field::Keyed(LuaKey::from(key), value),
+        ),
+        map(lua_value, Field::Positional),
+    ))
+    .parse(input)
+}
+
+fn table(input: &str) -> nom::IResult<&str, LuaValue> {
+    map(
+        delimited(
+            token(char('{')),
+            terminated(
+                separated_list0(token(separator), field),
+                opt(token(separator)),
+            ),
+            token(char('}')),
+        ),
+        |fields| {
+            if fields.iter().all(|f| matches!(f, Field::Positional(_))) && !fields.is_empty() {
+                return LuaValue::Array(
+                    fields
+                        .into_iter()
+                        .filter_map(|f| match f {
+                            Field::Positional(v) => Some(v),
+                            Field::Keyed(..) => None,
+                        })
+                        .collect(),
+                );
+            }
+
+            let mut entries = BTreeMap::new();
+            let mut index = 0i64;
+            for field in fields {
+                match field {
+                    Field::Keyed(key, value) => {
+                        entries.insert(key, value);
+                    }
+                    Field::Positional(value) => {
+                        index += 1;
+                        entries.insert(LuaKey::Integer(index), value);
+                    }
+                }
+            }
+            LuaValue::Table(entries)
+        },
+    )
+    .parse(input)
+}
+
+/// A numeric table key such as `[1]`.
+///
+/// Lua normalizes `[2.0]` to `[2]`; keys with a fractional part are not
+/// supported.
+fn integer_key(input: &str) -> nom::IResult<&str, LuaKey> {
+    map_res(number, |v| {
+        if v.fract() == 0.0 && v.abs() < i64::MAX as f64 {
+            Ok(LuaKey::Integer(v as i64))
+        } else {
+            Err(())
+        }
+    })
+    .parse(input)
+}
+
+/// Field separator inside a table: `,` or `;`.
+fn separator(input: &str) -> nom::IResult<&str, char> {
+    alt((char(','), char(';'))).parse(input)
+}
+
+fn lua_value(input: &str) -> nom::IResult<&str, LuaValue> {
+    token(alt((
+        table,
+        map(string, LuaValue::String),
+        value(LuaValue::Bool(true), keyword("true")),
+        value(LuaValue::Bool(false), keyword("false")),
+        value(LuaValue::Nil, keyword("nil")),
+        map(number, LuaValue::Number),
+    )))
+    .parse(input)
+}
+
+/// A reserved word that must not be followed by further identifier characters.
+fn keyword<'a>(
+    word: &'static str,
+) -> impl Parser<&'a str, Output = &'a str, Error = Error<&'a str>> {
+    terminated(
+        tag(word),
+        nom::combinator::not(satisfy(|c: char| c.is_ascii_alphanumeric() || c == '_')),
+    )
+}
+
+fn document(input: &str) -> nom::IResult<&str, (Option<String>, LuaValue)> {
+    all_consuming(alt((
+        map(
+            (token(identifier), token(char('=')), lua_value),
+            |(name, _, value)| (Some(name.to_string()), value),
+        ),
+        map(preceded(token(keyword("return")), lua_value), |value| {
+            (None, value)
+        }),
+    )))
+    .parse(input)
+}
+
+/// Parse a Lua document consisting of a single table assignment (`s = { ... }`)
+/// or a `return { ... }` statement.
+///
+/// Returns the assigned variable name (if any) and the parsed value.
+///
+/// # Errors
+///
+/// Returns [`crate::error::Error::LightroomParsing`] if the input is not a
+/// valid Lua table document.
+pub fn parse_document(input: &str) -> crate::error::Result<(Option<String>, LuaValue)> {
+    // Strip a UTF-8 byte order mark if present.
+    let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+
+    match document(input).finish() {
+        Ok((_remaining, result)) => Ok(result),
+        Err(Error { input, code }) => Err(crate::error::Error::LightroomParsing(format!(
+            "{:?} at: {}",
+            code,
+            input.chars().take(40).collect::<String>()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(input: &str) -> LuaValue {
+        parse_document(input).unwrap().1
+    }
+
+    #[test]
+    fn test_parse_scalars() {
+        let value = parse(
+            r#"s = { a = "x\"y", b = 'single', c = 1.5, d = -2, e = true, f = nil, g = 0x10 }"#,
+        );
+        let table = value.as_table().unwrap();
+        assert_eq!(Some("x\"y"), table[&LuaKey::from("a")].as_str());
+        assert_eq!(Some("single"), table[&LuaKey::from("b")].as_str());
+        assert_eq!(LuaValue::Number(1.5), table[&LuaKey::from("c")]);
+        assert_eq!(LuaValue::Number(-2.0), table[&LuaKey::from("d")]);
+        assert_eq!(LuaValue::Bool(true), table[&LuaKey::from("e")]);
+        assert_eq!(LuaValue::Nil, table[&LuaKey::from("f")]);
+        assert_eq!(LuaValue::Number(16.0), table[&LuaKey::from("g")]);
+    }
+
+    #[test]
+    fn test_parse_bracketed_keys_and_comments() {
+        let value = parse(
+            "-- Lightroom preset\ns = {\n\tvalue = {\n\t\t[\"com.adobe.city\"] = \"Kralendijk\"; -- city\n\t\t[1] = [[long\nstring]],\n\t},\n}\n",
+        );
+        let inner = value.as_table().unwrap()[&LuaKey::from("value")]
+            .as_table()
+            .unwrap();
+        assert_eq!(
+            Some("Kralendijk"),
+            inner[&LuaKey::from("com.adobe.city")].as_str()
+        );
+        assert_eq!(Some("long\nstring"), inner[&LuaKey::Integer(1)].as_str());
+    }
+
+    #[test]
+    fn test_integer_keys() {
+        let source = "s = {\n\t[1] = \"a\",\n\t[2] = \"b\",\n\t[\"3\"] = \"c\",\n}\n";
+        let (name, value) = parse_document(source).unwrap();
+        let table = value.as_table().unwrap();
+        assert_eq!(Some("a"), table[&LuaKey::Integer(1)].as_str());
+        assert_eq!(Some("c"), table[&LuaKey::from("3")].as_str());
+        assert_eq!(source, render_assignment(&name.unwrap(), &value));
+        assert!(parse_document("s = { [1.5] = 1 }").is_err());
+    }
+
+    #[test]
+    fn test_decimal_escapes() {
+        // "Curaçao" with the ç written as its two UTF-8 bytes.
+        let value = parse(r#"s = { a = "Cura\195\167ao", b = "\65\066" }"#);
+        let table = value.as_table().unwrap();
+        assert_eq!(Some("Curaçao"), table[&LuaKey::from("a")].as_str());
+        assert_eq!(Some("AB"), table[&LuaKey::from("b")].as_str());
+        assert!(parse_document(r#"s = { a = "\256" }"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_arrays_and_return() {
+        let (name, value) = parse_document("return { \"a\", \"b\", }").unwrap();
+        assert_eq!(None, name);
+        assert_eq!(
+            LuaValue::Array(vec![
+                LuaValue::String("a".to_string()),
+                LuaValue::String("b".to_string())
+            ]),
+            value
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(parse_document("s = { a = }").is_err());
+        assert!(parse_document("not lua").is_err());
+    }
+
+    #[test]
+    fn test_render_round_trip() {
+        let source = "s = {\n\tid = \"ABC\",\n\tvalue = {\n\t\t[\"com.adobe.city\"] = \"Say \\\"hi\\\"\",\n\t\tuuid = \"ABC\",\n\t},\n\tversion = 0,\n}\n";
+        let (name, value) = parse_document(source).unwrap();
+        assert_eq!(source, render_assignment(&name.unwrap(), &value));
+    }
+}
@@ -1 +1,5 @@
+pub mod geojson;
+pub mod kml;
+pub mod lua;
+pub mod sites;
 pub mod species;
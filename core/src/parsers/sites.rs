@@ -0,0 +1,545 @@
+//! Parse dive site lists from GPX, KML and CSV files.
+//!
+//! Dive operators share their sites in all sorts of formats. The parsers
+//! return new [`DiveSite`]s with a random UUID, unless the file carries one,
+//! and a primary key of `0`. Location fields the file has no value for are
+//! left empty, to be filled in by geocoding.
+//!
+//! Besides waypoint names and positions the parsers pick up the location and
+//! water fields written by [`crate::services::export`]: KML `ExtendedData` and
+//! CSV columns with the same names. The descriptions generated by the export
+//! are reduced to the notes they end with.
+//!
+//! Entries that cannot be read as a site, e.g. a row without a name or with
+//! invalid coordinates, are skipped and reported, so that one bad row does not
+//! hold up the rest of the file.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+
+use quick_xml::Reader;
+use quick_xml::events::{BytesStart, Event};
+use uuid::Uuid;
+
+use crate::domain::DiveSite;
+use crate::error::{Error, Result};
+use crate::util::coordinates::parse_coordinates;
+
+/// File formats dive sites can be imported from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SiteFileFormat {
+    Gpx,
+    Kml,
+    Csv,
+}
+
+impl SiteFileFormat {
+    /// Guess the format from the file extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "gpx" => Some(Self::Gpx),
+            "kml" => Some(Self::Kml),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// Dive sites read from a file.
+#[derive(Debug, Clone, Default)]
+pub struct ParsedSites {
+    pub sites: Vec<DiveSite>,
+    /// Why each skipped entry could not be read, e.g. `row 3: missing name`.
+    pub skipped: Vec<String>,
+}
+
+impl ParsedSites {
+    fn push(&mut self, entry: impl std::fmt::Display, site: Result<DiveSite>) {
+        match site {
+            Ok(site) => self.sites.push(site),
+            Err(Error::SiteImport(reason)) => self.skipped.push(format!("{entry}: {reason}")),
+            Err(e) => self.skipped.push(format!("{entry}: {e}")),
+        }
+    }
+}
+
+fn invalid(reason: impl std::fmt::Display) -> Error {
+    Error::SiteImport(reason.to_string())
+}
+
+/// Keep only the notes of a description generated by
+/// [`crate::services::export::site_description`], which lists the position
+/// and location fields before them.
+fn strip_generated_description(fields: &mut BTreeMap<String, String>) {
+    let generated = fields
+        .get("notes")
+        .is_some_and(|description| description.trim_start().starts_with("GPS: "));
+    if generated
+        && let Some(description) = fields.remove("notes")
+        && let Some((_, notes)) = description.split_once("\n\n")
+    {
+        fields.insert(String::from("notes"), notes.to_string());
+    }
+}
+
+/// Map a field name from a file to the name used by [`new_site`], e.g.
+/// `Body of Water` → `body_of_water` and `lng` → `longitude`.
+fn normalize_field(name: &str) -> String {
+    let name = name
+        .trim()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("_");
+    let canonical = match name.as_str() {
+        "site" | "site_name" | "dive_site" | "title" => "name",
+        "lat" => "latitude",
+        "lon" | "lng" | "long" => "longitude",
+        "gps" | "position" | "coordinate" | "location_gps" => "coordinates",
+        "ele" | "elevation" => "altitude",
+        "description" | "desc" | "comment" | "cmt" | "remarks" => "notes",
+        "city" | "town" | "village" => "locality",
+        "province" => "state",
+        "county" => "region",
+        "bodyofwater" | "water_body" => "body_of_water",
+        "water" | "watertype" => "water_type",
+        "level" => "difficulty",
+        _ => return name,
+    };
+    canonical.to_string()
+}
+
+/// Create a dive site from normalized fields.
+fn new_site(
+    mut fields: BTreeMap<String, String>,
+    latitude: f64,
+    longitude: f64,
+) -> Result<DiveSite> {
+    fields.retain(|_, value| !value.trim().is_empty());
+    let mut take = |key: &str| fields.remove(key).map(|value| value.trim().to_string());
+
+    let name = take("name").ok_or_else(|| {
+        invalid(format!(
+            "the site at {latitude:.6}, {longitude:.6} has no name"
+        ))
+    })?;
+    let uuid = match take("uuid") {
+        Some(uuid) => Uuid::parse_str(&uuid.to_lowercase())
+            .map_err(|_| invalid(format!("invalid UUID `{uuid}` of site `{name}`")))?,
+        None => Uuid::new_v4(),
+    };
+    let country = take("country").unwrap_or_default();
+    let iso_country_code = take("iso_country_code")
+        .or_else(|| {
+            celes::Country::from_str(&country)
+                .ok()
+                .map(|country| country.alpha2.to_string())
+        })
+        .unwrap_or_default();
+    let altitude = match take("altitude") {
+        Some(altitude) => altitude
+            .trim_end_matches('m')
+            .trim()
+            .parse::<f32>()
+            .map_err(|_| invalid(format!("invalid altitude `{altitude}` of site `{name}`")))?,
+        None => 0.0,
+    };
+
+    Ok(DiveSite {
+        uuid,
+        country,
+        iso_country_code: iso_country_code.to_uppercase(),
+        state: take("state"),
+        region: take("region"),
+        locality: take("locality"),
+        latitude,
+        longitude,
+        altitude,
+        body_of_water: take("body_of_water"),
+        water_type: take("water_type"),
+        difficulty: take("difficulty"),
+        notes: take("notes"),
+        site_id: 0,
+        name,
+    })
+}
+
+/// Parse dive sites from a file of the given format.
+///
+/// Entries without a name or with invalid coordinates are skipped, see
+/// [`ParsedSites::skipped`].
+///
+/// # Errors
+///
+/// Returns [`Error::SiteImport`] if the content is malformed.
+pub fn parse_sites(content: &str, format: SiteFileFormat) -> Result<ParsedSites> {
+    match format {
+        SiteFileFormat::Gpx => parse_gpx(content),
+        SiteFileFormat::Kml => parse_kml(content),
+        SiteFileFormat::Csv => parse_csv(content),
+    }
+}
+
+fn attribute(element: &BytesStart, name: &[u8]) -> Result<Option<String>> {
+    element
+        .try_get_attribute(name)
+        .map_err(invalid)?
+        .map(|attribute| {
+            attribute
+                .unescape_value()
+                .map(|value| value.into_owned())
+                .map_err(invalid)
+        })
+        .transpose()
+}
+
+/// Latitude and longitude in decimal degrees.
+type Position = (f64, f64);
+
+/// Parse the waypoints of a GPX document.
+///
+/// The name, elevation and description (or comment) of each `<wpt>` are
+/// used; routes and tracks are ignored.
+///
+/// # Errors
+///
+/// Returns [`Error::SiteImport`] if the document is not well-formed.
+pub fn parse_gpx(content: &str) -> Result<ParsedSites> {
+    let mut reader = Reader::from_str(content);
+    let mut parsed = ParsedSites::default();
+    let mut waypoints = 0;
+    // The position of the current waypoint, which may be invalid, and its fields.
+    let mut current: Option<(Result<Position>, BTreeMap<String, String>)> = None;
+    let mut field: Option<String> = None;
+
+    loop {
+        match reader.read_event().map_err(invalid)? {
+            Event::Start(e) if e.local_name().as_ref() == b"wpt" => {
+                waypoints += 1;
+                let coordinate = |name: &[u8]| -> Option<f64> {
+                    attribute(&e, name)
+                        .ok()
+                        .flatten()
+                        .and_then(|value| value.trim().parse().ok())
+                };
+                let position = match (coordinate(b"lat"), coordinate(b"lon")) {
+                    (Some(latitude), Some(longitude)) => {
+                        parse_coordinates(&format!("{latitude}, {longitude}"))
+                    }
+                    _ => Err(invalid("no valid lat and lon")),
+                };
+                current = Some((position, BTreeMap::new()));
+            }
+            Event::Start(e) if current.is_some() => {
+                field = match e.local_name().as_ref() {
+                    b"name" => Some(String::from("name")),
+                    b"ele" => Some(String::from("altitude")),
+                    b"desc" => Some(String::from("notes")),
+                    b"cmt" => Some(String::from("comment")),
+                    _ => None,
+                };
+            }
+            Event::Text(e) => {
+                if let (Some((_, fields)), Some(field)) = (current.as_mut(), &field) {
+                    fields.insert(field.clone(), e.unescape().map_err(invalid)?.into_owned());
+                }
+            }
+            Event::CData(e) => {
+                if let (Some((_, fields)), Some(field)) = (current.as_mut(), &field) {
+                    fields.insert(field.clone(), String::from_utf8_lossy(&e).into_owned());
+                }
+            }
+            Event::End(e) => {
+                field = None;
+                if e.local_name().as_ref() == b"wpt"
+                    && let Some((position, mut fields)) = current.take()
+                {
+                    strip_generated_description(&mut fields);
+                    // A description takes precedence over a comment.
+                    if let Some(comment) = fields.remove("comment") {
+                        fields.entry(String::from("notes")).or_insert(comment);
+                    }
+                    let site = position
+                        .and_then(|(latitude, longitude)| new_site(fields, latitude, longitude));
+                    parsed.push(format_args!("waypoint {waypoints}"), site);
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(parsed)
+}
+
+/// Parse the point placemarks of a KML document, e.g. from Google My Maps.
+///
+/// The name, description and `ExtendedData` of each `<Placemark>` with a
+/// `<Point>` are used; placemarks with other geometries are ignored.
+///
+/// # Errors
+///
+/// Returns [`Error::SiteImport`] if the document is not well-formed.
+pub fn parse_kml(content: &str) -> Result<ParsedSites> {
+    let mut reader = Reader::from_str(content);
+    let mut parsed = ParsedSites::default();
+    let mut placemarks = 0;
+    let mut current: Option<BTreeMap<String, String>> = None;
+    let mut in_point = false;
+    let mut data: Option<String> = None;
+    let mut field: Option<String> = None;
+
+    loop {
+        match reader.read_event().map_err(invalid)? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"Placemark" => {
+                    placemarks += 1;
+                    current = Some(BTreeMap::new());
+                }
+                b"Point" => in_point = true,
+                b"Data" => data = attribute(&e, b"name")?.map(|name| normalize_field(&name)),
+                b"name" | b"description" if current.is_some() && data.is_none() => {
+                    field = Some(normalize_field(&String::from_utf8_lossy(
+                        e.local_name().as_ref(),
+                    )));
+                }
+                b"value" => field = data.clone(),
+                b"coordinates" if in_point => field = Some(String::from("coordinates")),
+                _ => field = None,
+            },
+            Event::Text(e) => {
+                if let (Some(fields), Some(field)) = (current.as_mut(), &field) {
+                    fields
+                        .entry(field.clone())
+                        .or_default()
+                        .push_str(&e.unescape().map_err(invalid)?);
+                }
+            }
+            Event::CData(e) => {
+                if let (Some(fields), Some(field)) = (current.as_mut(), &field) {
+                    fields
+                        .entry(field.clone())
+                        .or_default()
+                        .push_str(&String::from_utf8_lossy(&e));
+                }
+            }
+            Event::End(e) => {
+                field = None;
+                match e.local_name().as_ref() {
+                    b"Point" => in_point = false,
+                    b"Data" => data = None,
+                    b"Placemark" => {
+                        let Some(mut fields) = current.take() else {
+                            continue;
+                        };
+                        let Some(point) = fields.remove("coordinates") else {
+                            continue;
+                        };
+                        strip_generated_description(&mut fields);
+                        // KML points are `lon,lat[,alt]`.
+                        let mut parts = point.trim().split(',').map(str::trim);
+                        let site = match (parts.next(), parts.next()) {
+                            (Some(longitude), Some(latitude)) => {
+                                if let Some(altitude) = parts.next() {
+                                    fields
+                                        .entry(String::from("altitude"))
+                                        .or_insert_with(|| altitude.to_string());
+                                }
+                                parse_coordinates(&format!("{latitude}, {longitude}")).and_then(
+                                    |(latitude, longitude)| new_site(fields, latitude, longitude),
+                                )
+                            }
+                            _ => Err(invalid(format!("invalid point `{}`", point.trim()))),
+                        };
+                        parsed.push(format_args!("placemark {placemarks}"), site);
+                    }
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(parsed)
+}
+
+/// Parse a spreadsheet exported as CSV with a header row.
+///
+/// The position is either in a single `coordinates` (or `gps`, `position`)
+/// column or in separate `latitude` and `longitude` columns, in any notation
+/// [`parse_coordinates`] understands. Other columns are matched by name, e.g.
+/// `Name`, `Country`, `Region`, `Body of Water` or `Notes`; unknown columns
+/// are ignored.
+///
+/// # Errors
+///
+/// Returns [`Error::SiteImport`] if the file cannot be read as CSV or has no
+/// position columns.
+pub fn parse_csv(content: &str) -> Result<ParsedSites> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+    let headers: Vec<String> = reader
+        .headers()
+        .map_err(invalid)?
+        .iter()
+        .map(normalize_field)
+        .collect();
+    let has = |name: &str| headers.iter().any(|header| header == name);
+    let has_position = has("coordinates") || (has("latitude") && has("longitude"));
+    if !has_position {
+        return Err(invalid(
+            "the CSV file needs a coordinates column or latitude and longitude columns",
+        ));
+    }
+
+    let mut parsed = ParsedSites::default();
+    for (row, record) in reader.records().enumerate() {
+        // Row numbers as shown in a spreadsheet, after the header row.
+        let row = format!("row {}", row + 2);
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                parsed.skipped.push(format!("{row}: {e}"));
+                continue;
+            }
+        };
+        let mut fields: BTreeMap<String, String> = headers
+            .iter()
+            .cloned()
+            .zip(record.iter().map(String::from))
+            .collect();
+        if fields.values().all(|value| value.is_empty()) {
+            continue;
+        }
+
+        let position = match fields
+            .remove("coordinates")
+            .filter(|value| !value.is_empty())
+        {
+            Some(position) => position,
+            None => format!(
+                "{}, {}",
+                fields.remove("latitude").unwrap_or_default(),
+                fields.remove("longitude").unwrap_or_default()
+            ),
+        };
+        let site = parse_coordinates(&position)
+            .and_then(|(latitude, longitude)| new_site(fields, latitude, longitude));
+        parsed.push(row, site);
+    }
+
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::CoordinateFormat;
+    use crate::services::export::render_kml;
+
+    #[test]
+    fn test_parse_gpx() {
+        let content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" xmlns="http://www.topografix.com/GPX/1/1">
+  <metadata><name>Bonaire</name></metadata>
+  <wpt lat="12.1583" lon="-68.2824">
+    <ele>2</ele>
+    <name>Salt Pier</name>
+    <cmt>Ask for permission</cmt>
+    <desc>Watch out for &lt;ships&gt;</desc>
+  </wpt>
+  <wpt lat="12.2195" lon="-68.3520"><name>Karpata</name></wpt>
+</gpx>
+"#;
+        let sites = parse_gpx(content).unwrap().sites;
+        assert_eq!(2, sites.len());
+        assert_eq!("Salt Pier", sites[0].name);
+        assert_eq!((12.1583, -68.2824), (sites[0].latitude, sites[0].longitude));
+        assert_eq!(2.0, sites[0].altitude);
+        assert_eq!(Some("Watch out for <ships>"), sites[0].notes.as_deref());
+        assert_eq!(0, sites[1].site_id);
+        assert!(sites[1].notes.is_none());
+
+        let parsed = parse_gpx(
+            r#"<gpx>
+  <wpt lat="12.1" lon="-68.2"></wpt>
+  <wpt lat="north" lon="-68.2"><name>Broken</name></wpt>
+  <wpt lat="12.2195" lon="-68.3520"><name>Karpata</name></wpt>
+</gpx>"#,
+        )
+        .unwrap();
+        assert_eq!(1, parsed.sites.len());
+        assert_eq!(2, parsed.skipped.len());
+        assert!(parsed.skipped[0].starts_with("waypoint 1: "));
+        assert!(parsed.skipped[1].starts_with("waypoint 2: "));
+        assert!(parse_gpx("<gpx><wpt></gpx>").is_err());
+    }
+
+    #[test]
+    fn test_parse_kml_round_trip() {
+        let site = DiveSite {
+            uuid: Uuid::new_v4(),
+            country: String::from("Bonaire"),
+            iso_country_code: String::from("BQ"),
+            region: Some(String::from("Bonaire")),
+            locality: Some(String::from("Kralendijk")),
+            name: String::from("Salt Pier"),
+            latitude: 12.1583,
+            longitude: -68.2824,
+            body_of_water: Some(String::from("Caribbean Sea")),
+            water_type: Some(String::from("Salt")),
+            difficulty: Some(String::from("Easy")),
+            ..Default::default()
+        };
+        let kml = render_kml(std::slice::from_ref(&site), CoordinateFormat::Dms).unwrap();
+
+        let sites = parse_kml(&kml).unwrap().sites;
+        assert_eq!(1, sites.len());
+        assert_eq!(site.uuid, sites[0].uuid);
+        assert_eq!(site.region, sites[0].region);
+        assert_eq!(site.difficulty, sites[0].difficulty);
+        assert_eq!("BQ", sites[0].iso_country_code);
+        assert_eq!(None, sites[0].notes);
+
+        let site = DiveSite {
+            notes: Some(String::from("Shore entry\n\nAsk for permission")),
+            ..site
+        };
+        let kml = render_kml(std::slice::from_ref(&site), CoordinateFormat::Dms).unwrap();
+        let sites = parse_kml(&kml).unwrap().sites;
+        assert_eq!(site.notes, sites[0].notes);
+    }
+
+    #[test]
+    fn test_parse_csv() {
+        let content = "Site Name,GPS,Country,Body of Water\n\
+                       Blue Corner,\"7°8.170' N, 134°13.250' E\",Palau,Philippine Sea\n\
+                       ,,,\n\
+                       German Channel,7.1717 134.3067,Palau,\n";
+        let sites = parse_csv(content).unwrap().sites;
+        assert_eq!(2, sites.len());
+        assert_eq!("Blue Corner", sites[0].name);
+        assert!((sites[0].latitude - 7.136167).abs() < 1e-6);
+        assert!((sites[0].longitude - 134.220833).abs() < 1e-6);
+        assert_eq!("PW", sites[0].iso_country_code);
+        assert_eq!(Some("Philippine Sea"), sites[0].body_of_water.as_deref());
+        assert!(sites[1].body_of_water.is_none());
+
+        let content = "name,lat,lng\nKarpata,12.2195,-68.3520\nBroken,north,west\n";
+        let parsed = parse_csv(content).unwrap();
+        assert_eq!("Karpata", parsed.sites[0].name);
+        assert_eq!(1, parsed.skipped.len());
+        assert!(
+            parsed.skipped[0].starts_with("row 3: "),
+            "{:?}",
+            parsed.skipped
+        );
+        assert!(parse_csv("name,depth\nKarpata,12\n").is_err());
+    }
+}
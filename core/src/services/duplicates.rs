@@ -0,0 +1,288 @@
+//! Find near-duplicate dive sites that are candidates for merging.
+//!
+//! Importing dives from different computers and buddies tends to create the
+//! same site several times, with slightly different names and positions a few
+//! meters apart (e.g. "Salt Pier" and "Salt Pier, Bonaire").
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::domain::DiveSite;
+use crate::util::disjoint_set::DisjointSet;
+use crate::util::geo::haversine_distance;
+
+/// A dive site within a group of duplicates.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DuplicateSite {
+    /// Primary key of the site in the MacDive database.
+    pub site_id: i64,
+    pub uuid: Uuid,
+    pub name: String,
+    pub country: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Number of dives that reference the site.
+    pub dives: usize,
+    /// Distance in meters to the site to keep.
+    pub distance: f64,
+    /// Name similarity with the site to keep, between 0 and 1.
+    pub similarity: f64,
+}
+
+/// Sites that look like the same place.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DuplicateGroup {
+    /// The site to keep, i.e. the one most dives reference.
+    pub keep: DuplicateSite,
+    /// The sites to merge into it, most dives first.
+    pub merge: Vec<DuplicateSite>,
+}
+
+/// Lowercase words of a name without punctuation.
+fn words(name: &str) -> Vec<String> {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Levenshtein distance in characters.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Similarity of two site names between 0 (unrelated) and 1 (the same).
+///
+/// Names are compared by their normalized edit distance, ignoring case and
+/// punctuation. Sites are often qualified with their island or town, so a name
+/// whose words all appear in the other one counts as at least halfway alike,
+/// plus half the share of the other name's words it covers. A single generic
+/// word like "Reef" therefore does not match every reef.
+///
+/// # Examples
+///
+/// ```
+/// use macdive_toolbox_core::services::duplicates::name_similarity;
+///
+/// assert!(name_similarity("Salt Pier", "Salt Pier, Bonaire") > 0.8);
+/// assert!(name_similarity("Reef", "Coral Reef") < 0.8);
+/// assert!(name_similarity("1000 Steps", "1000 Step") > 0.8);
+/// assert!(name_similarity("Salt Pier", "Town Pier") < 0.8);
+/// ```
+pub fn name_similarity(a: &str, b: &str) -> f64 {
+    let (a, b) = (words(a), words(b));
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let (shorter, longer) = if a.len() <= b.len() {
+        (&a, &b)
+    } else {
+        (&b, &a)
+    };
+    let subset = if shorter.iter().all(|word| longer.contains(word)) {
+        0.5 + 0.5 * shorter.len() as f64 / longer.len() as f64
+    } else {
+        0.0
+    };
+
+    let (a, b): (Vec<char>, Vec<char>) =
+        (a.join(" ").chars().collect(), b.join(" ").chars().collect());
+    let edit = 1.0 - edit_distance(&a, &b) as f64 / a.len().max(b.len()) as f64;
+    subset.max(edit)
+}
+
+/// Group dive sites that are no further apart than `max_distance` meters and
+/// whose names are at least `min_similarity` alike.
+///
+/// Groups grow transitively. Within a group the site referenced by the most
+/// dives is the one to keep, with ties going to the oldest record.
+///
+/// # Arguments
+///
+/// * `sites` - All dive sites.
+/// * `dive_counts` - Number of dives per site, keyed by the site's primary key.
+/// * `max_distance` - Maximum distance in meters between two duplicates.
+/// * `min_similarity` - Minimum [`name_similarity`] of two duplicates.
+pub fn find_duplicates(
+    sites: &[DiveSite],
+    dive_counts: &HashMap<i64, usize>,
+    max_distance: f64,
+    min_similarity: f64,
+) -> Vec<DuplicateGroup> {
+    let mut sets = DisjointSet::new(sites.len());
+    for (i, a) in sites.iter().enumerate() {
+        for (j, b) in sites.iter().enumerate().skip(i + 1) {
+            if haversine_distance(a.latitude, a.longitude, b.latitude, b.longitude) <= max_distance
+                && name_similarity(&a.name, &b.name) >= min_similarity
+            {
+                sets.union(i, j);
+            }
+        }
+    }
+
+    let dives = |site: &DiveSite| dive_counts.get(&site.site_id).copied().unwrap_or_default();
+    let mut groups: Vec<DuplicateGroup> = sets
+        .groups()
+        .into_iter()
+        .filter(|indices| indices.len() > 1)
+        .map(|mut indices| {
+            indices.sort_by_key(|index| (Reverse(dives(&sites[*index])), sites[*index].site_id));
+            let keep = &sites[indices[0]];
+            let entry = |site: &DiveSite| DuplicateSite {
+                site_id: site.site_id,
+                uuid: site.uuid,
+                name: site.name.clone(),
+                country: site.country.clone(),
+                latitude: site.latitude,
+                longitude: site.longitude,
+                dives: dives(site),
+                distance: haversine_distance(
+                    keep.latitude,
+                    keep.longitude,
+                    site.latitude,
+                    site.longitude,
+                )
+                .round(),
+                similarity: (name_similarity(&keep.name, &site.name) * 100.0).round() / 100.0,
+            };
+            DuplicateGroup {
+                keep: entry(keep),
+                merge: indices[1..]
+                    .iter()
+                    .map(|index| entry(&sites[*index]))
+                    .collect(),
+            }
+        })
+        .collect();
+    groups.sort_by(|a, b| a.keep.name.cmp(&b.keep.name));
+    groups
+}
+
+/// Find the site in `existing` that `site` duplicates, if any.
+///
+/// A site with the same UUID is always a duplicate. Otherwise the closest
+/// site no further away than `max_distance` meters whose name is at least
+/// `min_similarity` alike is returned.
+pub fn find_existing<'a>(
+    site: &DiveSite,
+    existing: &'a [DiveSite],
+    max_distance: f64,
+    min_similarity: f64,
+) -> Option<&'a DiveSite> {
+    if let Some(same) = existing.iter().find(|other| other.uuid == site.uuid) {
+        return Some(same);
+    }
+
+    existing
+        .iter()
+        .map(|other| {
+            let distance = haversine_distance(
+                site.latitude,
+                site.longitude,
+                other.latitude,
+                other.longitude,
+            );
+            (other, distance)
+        })
+        .filter(|(other, distance)| {
+            *distance <= max_distance && name_similarity(&site.name, &other.name) >= min_similarity
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(other, _)| other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn site(site_id: i64, name: &str, latitude: f64, longitude: f64) -> DiveSite {
+        DiveSite {
+            uuid: Uuid::new_v4(),
+            country: String::from("Bonaire"),
+            iso_country_code: String::from("BQ"),
+            name: String::from(name),
+            latitude,
+            longitude,
+            site_id,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_find_existing() {
+        let existing = [
+            site(1, "Salt Pier", 12.0828, -68.2836),
+            site(2, "Salt City", 12.0830, -68.2838),
+        ];
+        let found = |site: &DiveSite| find_existing(site, &existing, 100.0, 0.8).map(|s| s.site_id);
+
+        assert_eq!(
+            Some(1),
+            found(&site(0, "Salt Pier, Bonaire", 12.0825, -68.2836))
+        );
+        assert_eq!(None, found(&site(0, "Salt Pier", 12.2850, -68.3950)));
+        assert_eq!(
+            Some(2),
+            found(&DiveSite {
+                uuid: existing[1].uuid,
+                ..site(0, "Renamed", 12.2850, -68.3950)
+            })
+        );
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        let chars = |value: &str| value.chars().collect::<Vec<_>>();
+        assert_eq!(3, edit_distance(&chars("kitten"), &chars("sitting")));
+        assert_eq!(4, edit_distance(&chars(""), &chars("pier")));
+    }
+
+    #[test]
+    fn test_name_similarity() {
+        assert_eq!(1.0, name_similarity("Salt Pier", "salt pier!"));
+        assert_eq!(0.75, name_similarity("Reef", "Coral Reef"));
+        assert_eq!(0.0, name_similarity("", "Karpata"));
+    }
+
+    #[test]
+    fn test_find_duplicates() {
+        let sites = [
+            site(1, "Salt Pier", 12.0828, -68.2836),
+            // 33 m further south.
+            site(2, "Salt Pier, Bonaire", 12.0825, -68.2836),
+            // Same name, but on the other side of the island.
+            site(3, "Salt Pier", 12.2850, -68.3950),
+            // Close by, but a different site.
+            site(4, "Salt City", 12.0830, -68.2838),
+            site(5, "Karpata", 12.2195, -68.3520),
+        ];
+        let dive_counts = HashMap::from([(1, 2), (2, 7), (4, 1)]);
+
+        let groups = find_duplicates(&sites, &dive_counts, 100.0, 0.8);
+        assert_eq!(1, groups.len());
+        let group = &groups[0];
+        assert_eq!(2, group.keep.site_id);
+        assert_eq!(7, group.keep.dives);
+        assert_eq!(
+            vec![(1, 2, 0.83)],
+            group
+                .merge
+                .iter()
+                .map(|site| (site.site_id, site.dives, site.similarity))
+                .collect::<Vec<_>>()
+        );
+        assert!((group.merge[0].distance - 33.0).abs() < 1.0);
+    }
+}
@@ -0,0 +1,242 @@
+//! Offline elevation lookups from SRTM `.hgt` tiles.
+//!
+//! Each tile covers one degree of latitude and longitude and is named after
+//! its south-west corner, e.g. `N12W069.hgt`. Samples are big-endian 16-bit
+//! integers in meters, stored row by row from north to south; SRTM1 tiles have
+//! 3601 and SRTM3 tiles 1201 samples per row.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::domain::{DiveSite, ElevationConfig};
+use crate::error::{Error, Result};
+
+/// Marker for samples without data, e.g. over water or in radar shadows.
+const VOID: i16 = -32768;
+
+/// A single elevation tile.
+#[derive(Debug)]
+struct Tile {
+    /// Samples per row and column.
+    size: usize,
+    samples: Vec<i16>,
+}
+
+impl Tile {
+    /// Parse the content of a `.hgt` file.
+    fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let count = bytes.len() / 2;
+        let size = count.isqrt();
+        if !bytes.len().is_multiple_of(2) || size < 2 || size * size != count {
+            return Err(Error::Srtm(format!(
+                "tile of {} bytes is not a square grid",
+                bytes.len()
+            )));
+        }
+
+        let samples = bytes
+            .chunks_exact(2)
+            .map(|pair| i16::from_be_bytes([pair[0], pair[1]]))
+            .collect();
+        Ok(Self { size, samples })
+    }
+
+    fn sample(&self, row: usize, column: usize) -> Option<f64> {
+        let value = self.samples[row * self.size + column];
+        (value != VOID).then_some(f64::from(value))
+    }
+
+    /// Bilinear interpolation at a position within the tile, given as the
+    /// fraction of a degree north and east of its south-west corner.
+    ///
+    /// Void samples are left out; returns `None` if all four neighbours are void.
+    fn elevation(&self, north: f64, east: f64) -> Option<f64> {
+        let last = (self.size - 1) as f64;
+        let row = ((1.0 - north) * last).clamp(0.0, last);
+        let column = (east * last).clamp(0.0, last);
+        let (top, left) = (row.floor() as usize, column.floor() as usize);
+        let (bottom, right) = ((top + 1).min(self.size - 1), (left + 1).min(self.size - 1));
+        let (dy, dx) = (row - top as f64, column - left as f64);
+
+        let (sum, weights) = [
+            (top, left, (1.0 - dy) * (1.0 - dx)),
+            (top, right, (1.0 - dy) * dx),
+            (bottom, left, dy * (1.0 - dx)),
+            (bottom, right, dy * dx),
+        ]
+        .into_iter()
+        .filter_map(|(row, column, weight)| Some((self.sample(row, column)?, weight)))
+        .fold((0.0, 0.0), |(sum, weights), (value, weight)| {
+            (sum + value * weight, weights + weight)
+        });
+
+        if weights > 0.0 {
+            Some(sum / weights)
+        } else {
+            // Only void samples, or the position is exactly on a void one.
+            None
+        }
+    }
+}
+
+/// Name of the tile containing a position, e.g. `N12W069.hgt`.
+///
+/// # Examples
+///
+/// ```
+/// use macdive_toolbox_core::services::elevation::tile_name;
+///
+/// assert_eq!("N12W069.hgt", tile_name(12.15, -68.28));
+/// assert_eq!("S09E124.hgt", tile_name(-8.2, 124.5));
+/// ```
+pub fn tile_name(latitude: f64, longitude: f64) -> String {
+    let (latitude, longitude) = (latitude.floor() as i32, longitude.floor() as i32);
+    format!(
+        "{}{:02}{}{:03}.hgt",
+        if latitude < 0 { 'S' } else { 'N' },
+        latitude.abs(),
+        if longitude < 0 { 'W' } else { 'E' },
+        longitude.abs()
+    )
+}
+
+/// A directory of SRTM tiles, loaded on demand.
+#[derive(Debug)]
+pub struct SrtmTiles {
+    directory: PathBuf,
+    /// Loaded tiles by name; `None` for tiles missing from the directory.
+    tiles: Mutex<HashMap<String, Option<Arc<Tile>>>>,
+}
+
+impl SrtmTiles {
+    /// Use the tiles in `directory`.
+    pub fn new(directory: &Path) -> Self {
+        Self {
+            directory: directory.to_path_buf(),
+            tiles: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Use the tiles configured in `config`, `None` if no directory is set.
+    pub fn from_config(config: &ElevationConfig) -> Option<Self> {
+        config.srtm_path.as_deref().map(Self::new)
+    }
+
+    fn tile(&self, name: &str) -> Result<Option<Arc<Tile>>> {
+        let mut tiles = self.tiles.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(tile) = tiles.get(name) {
+            return Ok(tile.clone());
+        }
+
+        let path = self.directory.join(name);
+        let tile = if path.exists() {
+            let bytes = std::fs::read(&path)?;
+            let tile = Tile::from_bytes(&bytes)
+                .map_err(|e| Error::Srtm(format!("{}: {e}", path.display())))?;
+            Some(Arc::new(tile))
+        } else {
+            None
+        };
+        tiles.insert(name.to_string(), tile.clone());
+        Ok(tile)
+    }
+
+    /// Elevation in meters at a position.
+    ///
+    /// Returns `None` if the tile is not available or has no data there.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Io`] if the tile cannot be read, or [`Error::Srtm`] if
+    /// it is not a valid `.hgt` file.
+    pub fn elevation(&self, latitude: f64, longitude: f64) -> Result<Option<f64>> {
+        let Some(tile) = self.tile(&tile_name(latitude, longitude))? else {
+            return Ok(None);
+        };
+        Ok(tile.elevation(latitude - latitude.floor(), longitude - longitude.floor()))
+    }
+
+    /// Fill in the altitude of a fresh water site without one.
+    ///
+    /// MacDive stores a missing altitude as zero, which is also correct for
+    /// sites at sea level. Only sites whose water type is fresh are therefore
+    /// filled, so coastal sites without a water type do not pick up the
+    /// elevation of the shore.
+    ///
+    /// # Errors
+    ///
+    /// See [`SrtmTiles::elevation`].
+    pub fn fill(&self, mut site: DiveSite) -> Result<DiveSite> {
+        if site.altitude == 0.0
+            && site.is_fresh_water()
+            && let Some(elevation) = self.elevation(site.latitude, site.longitude)?
+        {
+            site.altitude = elevation.round() as f32;
+        }
+        Ok(site)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn tile(samples: &[i16]) -> Tile {
+        let bytes: Vec<u8> = samples.iter().flat_map(|v| v.to_be_bytes()).collect();
+        Tile::from_bytes(&bytes).unwrap()
+    }
+
+    #[test]
+    fn test_interpolate_elevation() {
+        // North row first.
+        let tile = tile(&[200, 300, 400, 100, 200, 300, 0, 100, 200]);
+        assert_eq!(3, tile.size);
+        // Corners and center.
+        assert_eq!(Some(0.0), tile.elevation(0.0, 0.0));
+        assert_eq!(Some(400.0), tile.elevation(1.0, 1.0));
+        assert_eq!(Some(200.0), tile.elevation(0.5, 0.5));
+        // Halfway between the center and the sample north of it.
+        assert_eq!(Some(250.0), tile.elevation(0.75, 0.5));
+    }
+
+    #[test]
+    fn test_skip_void_samples() {
+        let tile = tile(&[VOID, VOID, VOID, VOID, 100, VOID, VOID, VOID, VOID]);
+        assert_eq!(Some(100.0), tile.elevation(0.4, 0.6));
+        assert_eq!(None, tile.elevation(0.0, 0.0));
+    }
+
+    #[test]
+    fn test_fill_fresh_water_only() {
+        let directory = std::env::temp_dir().join(format!("srtm-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let bytes: Vec<u8> = [412i16; 9].iter().flat_map(|v| v.to_be_bytes()).collect();
+        std::fs::write(directory.join("N12W069.hgt"), bytes).unwrap();
+        let tiles = SrtmTiles::new(&directory);
+
+        let site = |water_type: Option<&str>| DiveSite {
+            country: String::from("Bonaire"),
+            iso_country_code: String::from("BQ"),
+            name: String::from("Goto Meer"),
+            latitude: 12.23,
+            longitude: -68.37,
+            water_type: water_type.map(String::from),
+            site_id: 1,
+            ..Default::default()
+        };
+        assert_eq!(412.0, tiles.fill(site(Some("Fresh"))).unwrap().altitude);
+        assert_eq!(0.0, tiles.fill(site(Some("Salt"))).unwrap().altitude);
+        assert_eq!(0.0, tiles.fill(site(None)).unwrap().altitude);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_tile() {
+        assert!(Tile::from_bytes(&[0; 10]).is_err());
+        assert!(Tile::from_bytes(&[0; 7]).is_err());
+    }
+}
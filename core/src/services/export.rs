@@ -0,0 +1,479 @@
+//! Export dive sites as GPX, KML, GeoJSON and Subsurface dive sites.
+//!
+//! Used to share site lists with boat crews and to load them into navigation
+//! and mapping apps. Every waypoint carries the site's location fields, water
+//! details and notes: as a description for GPX and KML, which most apps show
+//! as is, and additionally as `ExtendedData` for KML and properties for
+//! GeoJSON. The Subsurface export keeps the logbooks of both applications on
+//! the same curated sites.
+
+use std::collections::BTreeMap;
+use std::io;
+
+use quick_xml::Writer;
+use quick_xml::events::{BytesDecl, BytesText, Event};
+use serde_json::json;
+
+use crate::domain::{CoordinateFormat, DiveSite};
+use crate::error::Result;
+use crate::services::geojson::{feature_collection, site_feature};
+use crate::util::coordinates::format_coordinates;
+
+/// Labelled fields of a site in the order they are shown.
+///
+/// Fields without a value are left out.
+fn fields(site: &DiveSite) -> Vec<(&'static str, &'static str, String)> {
+    [
+        ("locality", "Locality", site.locality.clone()),
+        ("region", "Region", site.region.clone()),
+        ("state", "State", site.state.clone()),
+        ("country", "Country", Some(site.country.clone())),
+        ("body_of_water", "Body of water", site.body_of_water.clone()),
+        ("water_type", "Water type", site.water_type.clone()),
+        ("difficulty", "Difficulty", site.difficulty.clone()),
+        ("uuid", "UUID", Some(site.uuid.to_string())),
+    ]
+    .into_iter()
+    .filter_map(|(key, label, value)| {
+        value
+            .filter(|value| !value.is_empty())
+            .map(|value| (key, label, value))
+    })
+    .collect()
+}
+
+/// Plain text description of a site with one `Label: value` line per field,
+/// followed by the notes.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidCoordinates`](crate::error::Error::InvalidCoordinates)
+/// if the coordinates cannot be rendered in `format`.
+pub fn site_description(site: &DiveSite, format: CoordinateFormat) -> Result<String> {
+    let mut lines = vec![format!(
+        "GPS: {}",
+        format_coordinates(site.latitude, site.longitude, format)?
+    )];
+    if site.altitude != 0.0 {
+        lines.push(format!("Altitude: {} m", site.altitude));
+    }
+    for (_, label, value) in fields(site) {
+        lines.push(format!("{label}: {value}"));
+    }
+    if let Some(notes) = site
+        .notes
+        .as_deref()
+        .filter(|notes| !notes.trim().is_empty())
+    {
+        lines.push(String::new());
+        lines.push(notes.trim().to_string());
+    }
+    Ok(lines.join("\n"))
+}
+
+fn new_writer() -> Result<Writer<Vec<u8>>> {
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+    Ok(writer)
+}
+
+fn into_string(writer: Writer<Vec<u8>>) -> String {
+    let mut output = String::from_utf8_lossy(&writer.into_inner()).into_owned();
+    output.push('\n');
+    output
+}
+
+/// Render the sites as GPX 1.1 waypoints.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidCoordinates`](crate::error::Error::InvalidCoordinates)
+/// if a site's coordinates cannot be rendered in `format`.
+pub fn render_gpx(sites: &[DiveSite], format: CoordinateFormat) -> Result<String> {
+    let descriptions = sites
+        .iter()
+        .map(|site| site_description(site, format))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut writer = new_writer()?;
+    writer
+        .create_element("gpx")
+        .with_attributes([
+            ("version", "1.1"),
+            ("creator", "macdive-toolbox"),
+            ("xmlns", "http://www.topografix.com/GPX/1/1"),
+        ])
+        .write_inner_content(|writer| {
+            for (site, description) in sites.iter().zip(&descriptions) {
+                writer
+                    .create_element("wpt")
+                    .with_attributes([
+                        ("lat", site.latitude.to_string().as_str()),
+                        ("lon", site.longitude.to_string().as_str()),
+                    ])
+                    .write_inner_content(|writer| {
+                        if site.altitude != 0.0 {
+                            writer
+                                .create_element("ele")
+                                .write_text_content(BytesText::new(&site.altitude.to_string()))?;
+                        }
+                        writer
+                            .create_element("name")
+                            .write_text_content(BytesText::new(&site.name))?;
+                        writer
+                            .create_element("desc")
+                            .write_text_content(BytesText::new(description))?;
+                        writer
+                            .create_element("type")
+                            .write_text_content(BytesText::new("Dive Site"))?;
+                        Ok(())
+                    })?;
+            }
+            Ok(())
+        })?;
+    Ok(into_string(writer))
+}
+
+fn kml_placemark(
+    writer: &mut Writer<Vec<u8>>,
+    site: &DiveSite,
+    description: &str,
+) -> io::Result<()> {
+    writer
+        .create_element("Placemark")
+        .write_inner_content(|writer| {
+            writer
+                .create_element("name")
+                .write_text_content(BytesText::new(&site.name))?;
+            writer
+                .create_element("description")
+                .write_text_content(BytesText::new(description))?;
+            writer
+                .create_element("ExtendedData")
+                .write_inner_content(|writer| {
+                    for (key, _, value) in fields(site) {
+                        writer
+                            .create_element("Data")
+                            .with_attribute(("name", key))
+                            .write_inner_content(|writer| {
+                                writer
+                                    .create_element("value")
+                                    .write_text_content(BytesText::new(&value))?;
+                                Ok(())
+                            })?;
+                    }
+                    Ok(())
+                })?;
+            writer
+                .create_element("Point")
+                .write_inner_content(|writer| {
+                    writer
+                        .create_element("coordinates")
+                        .write_text_content(BytesText::new(&format!(
+                            "{},{},{}",
+                            site.longitude, site.latitude, site.altitude
+                        )))?;
+                    Ok(())
+                })?;
+            Ok(())
+        })?;
+    Ok(())
+}
+
+fn kml_folder(
+    writer: &mut Writer<Vec<u8>>,
+    name: &str,
+    content: impl FnOnce(&mut Writer<Vec<u8>>) -> io::Result<()>,
+) -> io::Result<()> {
+    writer
+        .create_element("Folder")
+        .write_inner_content(|writer| {
+            writer
+                .create_element("name")
+                .write_text_content(BytesText::new(name))?;
+            content(writer)
+        })?;
+    Ok(())
+}
+
+/// Render the sites as a KML document with a folder per country and, within
+/// it, per region.
+///
+/// Sites without a region are placed directly in their country's folder.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidCoordinates`](crate::error::Error::InvalidCoordinates)
+/// if a site's coordinates cannot be rendered in `format`.
+pub fn render_kml(sites: &[DiveSite], format: CoordinateFormat) -> Result<String> {
+    let mut countries = BTreeMap::<&str, BTreeMap<Option<&str>, Vec<(&DiveSite, String)>>>::new();
+    for site in sites {
+        let description = site_description(site, format)?;
+        countries
+            .entry(site.country.as_str())
+            .or_default()
+            .entry(site.region.as_deref().filter(|region| !region.is_empty()))
+            .or_default()
+            .push((site, description));
+    }
+    for regions in countries.values_mut() {
+        for sites in regions.values_mut() {
+            sites.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+        }
+    }
+
+    let mut writer = new_writer()?;
+    writer
+        .create_element("kml")
+        .with_attribute(("xmlns", "http://www.opengis.net/kml/2.2"))
+        .write_inner_content(|writer| {
+            writer
+                .create_element("Document")
+                .write_inner_content(|writer| {
+                    writer
+                        .create_element("name")
+                        .write_text_content(BytesText::new("Dive Sites"))?;
+                    for (country, regions) in &countries {
+                        kml_folder(writer, country, |writer| {
+                            for (region, sites) in regions {
+                                match region {
+                                    Some(region) => kml_folder(writer, region, |writer| {
+                                        for (site, description) in sites {
+                                            kml_placemark(writer, site, description)?;
+                                        }
+                                        Ok(())
+                                    })?,
+                                    None => {
+                                        for (site, description) in sites {
+                                            kml_placemark(writer, site, description)?;
+                                        }
+                                    }
+                                }
+                            }
+                            Ok(())
+                        })?;
+                    }
+                    Ok(())
+                })?;
+            Ok(())
+        })?;
+    Ok(into_string(writer))
+}
+
+/// Render the sites as a GeoJSON `FeatureCollection` of points.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidCoordinates`](crate::error::Error::InvalidCoordinates)
+/// if a site's coordinates cannot be rendered in `format`, or
+/// [`Error::Json`](crate::error::Error::Json) if serialization fails.
+pub fn render_geojson(sites: &[DiveSite], format: CoordinateFormat) -> Result<String> {
+    let features = sites
+        .iter()
+        .map(|site| {
+            let mut feature = site_feature(site);
+            feature["properties"]["description"] = json!(site_description(site, format)?);
+            Ok(feature)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(serde_json::to_string_pretty(&feature_collection(features))? + "\n")
+}
+
+/// Subsurface taxonomy categories, see `core/taxonomy.h` in Subsurface.
+const TC_OCEAN: u8 = 1;
+const TC_COUNTRY: u8 = 2;
+const TC_ADMIN_L1: u8 = 3;
+const TC_ADMIN_L2: u8 = 4;
+const TC_LOCALNAME: u8 = 5;
+/// Taxonomy origin for values entered by hand, which Subsurface does not
+/// replace when it looks up a site again.
+const GEOMANUAL: u8 = 1;
+
+/// Subsurface identifies dive sites by a 32 bit number written in hex; the
+/// leading bits of the MacDive UUID keep it stable across exports.
+fn subsurface_uuid(site: &DiveSite) -> String {
+    format!("{:08x}", (site.uuid.as_u128() >> 96) as u32)
+}
+
+/// Render the sites as a Subsurface logbook with only a `<divesites>` section.
+///
+/// Each site carries its name, GPS position, water type, difficulty and
+/// altitude as the description, the notes, and its body of water, country,
+/// state, region and locality as taxonomy `<geo>` entries.
+///
+/// # Errors
+///
+/// Returns [`Error::Io`](crate::error::Error::Io) if the XML cannot be written.
+pub fn render_subsurface(sites: &[DiveSite]) -> Result<String> {
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer
+        .create_element("divelog")
+        .with_attributes([("program", "macdive-toolbox"), ("version", "3")])
+        .write_inner_content(|writer| {
+            writer
+                .create_element("divesites")
+                .write_inner_content(|writer| {
+                    for site in sites {
+                        subsurface_site(writer, site)?;
+                    }
+                    Ok(())
+                })?;
+            writer.create_element("dives").write_empty()?;
+            Ok(())
+        })?;
+    Ok(into_string(writer))
+}
+
+fn subsurface_site(writer: &mut Writer<Vec<u8>>, site: &DiveSite) -> io::Result<()> {
+    let description = [
+        site.water_type
+            .as_deref()
+            .map(|water| format!("{water} water")),
+        site.difficulty.clone(),
+        (site.altitude != 0.0).then(|| format!("Altitude {} m", site.altitude)),
+    ]
+    .into_iter()
+    .flatten()
+    .filter(|value| !value.trim().is_empty())
+    .collect::<Vec<_>>()
+    .join(", ");
+    let taxonomy = [
+        (TC_OCEAN, site.body_of_water.as_deref()),
+        (TC_COUNTRY, Some(site.country.as_str())),
+        (TC_ADMIN_L1, site.state.as_deref()),
+        (TC_ADMIN_L2, site.region.as_deref()),
+        (TC_LOCALNAME, site.locality.as_deref()),
+    ];
+
+    let uuid = subsurface_uuid(site);
+    let gps = format!("{:.6} {:.6}", site.latitude, site.longitude);
+    let mut element = writer.create_element("site").with_attributes([
+        ("uuid", uuid.as_str()),
+        ("name", site.name.as_str()),
+        ("gps", gps.as_str()),
+    ]);
+    if !description.is_empty() {
+        element = element.with_attribute(("description", description.as_str()));
+    }
+    element.write_inner_content(|writer| {
+        if let Some(notes) = site
+            .notes
+            .as_deref()
+            .filter(|notes| !notes.trim().is_empty())
+        {
+            writer
+                .create_element("notes")
+                .write_text_content(BytesText::new(notes.trim()))?;
+        }
+        for (category, value) in taxonomy {
+            let Some(value) = value.filter(|value| !value.is_empty()) else {
+                continue;
+            };
+            writer
+                .create_element("geo")
+                .with_attributes([
+                    ("cat", category.to_string().as_str()),
+                    ("origin", GEOMANUAL.to_string().as_str()),
+                    ("value", value),
+                ])
+                .write_empty()?;
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn site(name: &str, region: Option<&str>) -> DiveSite {
+        DiveSite {
+            country: String::from("Bonaire"),
+            iso_country_code: String::from("BQ"),
+            region: region.map(String::from),
+            locality: Some(String::from("Kralendijk")),
+            name: String::from(name),
+            latitude: 12.15,
+            longitude: -68.28,
+            body_of_water: Some(String::from("Caribbean Sea")),
+            water_type: Some(String::from("Salt")),
+            difficulty: Some(String::from("Easy")),
+            notes: Some(String::from("Shore entry <ladder>")),
+            site_id: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_site_description() {
+        assert_eq!(
+            "GPS: 12.150000, -68.280000\n\
+             Locality: Kralendijk\n\
+             Country: Bonaire\n\
+             Body of water: Caribbean Sea\n\
+             Water type: Salt\n\
+             Difficulty: Easy\n\
+             UUID: 00000000-0000-0000-0000-000000000000\n\
+             \n\
+             Shore entry <ladder>",
+            site_description(&site("Salt Pier", None), CoordinateFormat::Decimal).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_render_subsurface() {
+        let mut site = site("Salt Pier", Some("South"));
+        site.uuid = Uuid::parse_str("4a3b2c1d-0000-4000-8000-000000000000").unwrap();
+        let xml = render_subsurface(&[site]).unwrap();
+
+        assert!(xml.contains(
+            r#"<site uuid="4a3b2c1d" name="Salt Pier" gps="12.150000 -68.280000" description="Salt water, Easy">"#
+        ));
+        assert!(xml.contains("<notes>Shore entry &lt;ladder&gt;</notes>"));
+        assert!(xml.contains(r#"<geo cat="1" origin="1" value="Caribbean Sea"/>"#));
+        assert!(xml.contains(r#"<geo cat="4" origin="1" value="South"/>"#));
+        assert!(!xml.contains(r#"cat="3""#));
+        assert!(xml.contains("<dives/>"));
+    }
+
+    #[test]
+    fn test_render_gpx() {
+        let gpx = render_gpx(&[site("Salt Pier", None)], CoordinateFormat::Decimal).unwrap();
+        assert!(gpx.contains(r#"<wpt lat="12.15" lon="-68.28">"#));
+        assert!(gpx.contains("<name>Salt Pier</name>"));
+        assert!(gpx.contains("Shore entry &lt;ladder&gt;</desc>"));
+    }
+
+    #[test]
+    fn test_render_kml_folders() {
+        let sites = [
+            site("Salt Pier", Some("South")),
+            site("Karpata", Some("North")),
+            site("Kralendijk Pier", None),
+        ];
+        let kml = render_kml(&sites, CoordinateFormat::Decimal).unwrap();
+
+        let folders: Vec<&str> = kml
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("<name>"))
+            .map(|line| line.trim_end_matches("</name>"))
+            .collect();
+        assert_eq!(
+            vec![
+                "Dive Sites",
+                "Bonaire",
+                "Kralendijk Pier",
+                "North",
+                "Karpata",
+                "South",
+                "Salt Pier"
+            ],
+            folders
+        );
+        assert!(kml.contains(r#"<Data name="difficulty">"#));
+        assert_eq!(3, kml.matches("<Placemark>").count());
+    }
+}
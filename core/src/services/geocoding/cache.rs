@@ -0,0 +1,395 @@
+//! SeaORM-based cache for reverse geocoding results.
+//!
+//! Dive sites rarely move, so addresses are cached per site UUID and rounded
+//! coordinates. Cache entries are considered valid for 180 days; in offline
+//! mode older entries are still used. Only the fields the service returned
+//! are cached, and lookups without any result are not cached at all, so that
+//! they are retried. Local providers are not cached. Sites that are not in
+//! MacDive yet are looked up by position only and never stored.
+
+use async_trait::async_trait;
+use entity::{geocode_cache, prelude::GeocodeCache};
+use sea_orm::prelude::*;
+use sea_orm::{QueryOrder, Select, Set, sea_query::OnConflict};
+use tracing::instrument;
+
+use crate::domain::{DiveSite, GeocodingConfig, GeocodingProvider};
+use crate::error::Result;
+
+use super::{Geocoder, geocoder};
+
+/// Cache TTL: addresses older than this many days are looked up again.
+const CACHE_TTL_DAYS: i64 = 180;
+
+/// Round a coordinate to four decimal places (about 11 m), so that small
+/// edits of a site's position still hit the cache.
+fn round_coordinate(value: f64) -> f64 {
+    (value * 10_000.0).round() / 10_000.0
+}
+
+/// A copy of `site` without any address fields.
+fn without_address(site: &DiveSite) -> DiveSite {
+    DiveSite {
+        country: String::new(),
+        iso_country_code: String::new(),
+        state: None,
+        region: None,
+        locality: None,
+        ..site.clone()
+    }
+}
+
+/// Copy the address fields `address` has a value for onto `site`.
+fn merge_address(site: DiveSite, address: DiveSite) -> DiveSite {
+    let (country, iso_country_code) = if address.country.is_empty() {
+        (site.country, site.iso_country_code)
+    } else {
+        (address.country, address.iso_country_code)
+    };
+    DiveSite {
+        country,
+        iso_country_code,
+        state: address.state.or(site.state),
+        region: address.region.or(site.region),
+        locality: address.locality.or(site.locality),
+        ..site
+    }
+}
+
+/// Copy the address fields of a cache entry onto `site`.
+fn apply_cached(site: DiveSite, cached: geocode_cache::Model) -> DiveSite {
+    let address = DiveSite {
+        country: cached.country,
+        iso_country_code: cached.iso_country_code,
+        state: cached.state,
+        region: cached.region,
+        locality: cached.locality,
+        ..without_address(&site)
+    };
+    merge_address(site, address)
+}
+
+/// A [`Geocoder`] that answers from the local cache before asking the
+/// configured geocoding service.
+///
+/// Without a service (offline mode), cached addresses of any age are applied
+/// and sites without a cache entry are returned unchanged.
+pub struct CachedGeocoder<'a> {
+    db: &'a DbConn,
+    provider: GeocodingProvider,
+    inner: Option<Box<dyn Geocoder>>,
+}
+
+impl<'a> CachedGeocoder<'a> {
+    /// Create a caching geocoder for the service selected in `config`.
+    ///
+    /// Returns `None` when online and the service needs an API key but none
+    /// is configured, in which case sites keep the location recorded in
+    /// MacDive. Local services are also used in offline mode.
+    ///
+    /// # Arguments
+    ///
+    /// * `db` - Database connection for the cache
+    /// * `config` - Geocoding service settings
+    /// * `offline` - When true, only use cached addresses
+    ///
+    /// # Errors
+    ///
+    /// See [`geocoder`].
+    pub fn new(db: &'a DbConn, config: &GeocodingConfig, offline: bool) -> Result<Option<Self>> {
+        let inner = if offline && !config.provider.is_local() {
+            None
+        } else {
+            geocoder(config)?
+        };
+        if inner.is_none() && !offline {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            db,
+            provider: config.provider,
+            inner,
+        }))
+    }
+
+    /// Look up the cached address of `site`.
+    ///
+    /// Entries of another provider or older than [`CACHE_TTL_DAYS`] are
+    /// ignored unless running offline.
+    #[instrument(name = "geocode-cache-lookup", skip(self, site), fields(site = %site.name))]
+    async fn cached(&self, site: &DiveSite) -> Result<Option<geocode_cache::Model>> {
+        let query = self
+            .at_position(site)
+            .filter(geocode_cache::Column::SiteUuid.eq(site.uuid.to_string()));
+
+        Ok(query.one(self.db).await?)
+    }
+
+    /// Look up the most recent cached address of any site at the position of
+    /// `site`.
+    #[instrument(name = "geocode-cache-position", skip(self, site), fields(site = %site.name))]
+    async fn cached_position(&self, site: &DiveSite) -> Result<Option<geocode_cache::Model>> {
+        let query = self
+            .at_position(site)
+            .order_by_desc(geocode_cache::Column::GeocodedAt);
+
+        Ok(query.one(self.db).await?)
+    }
+
+    /// Cache entries at the rounded position of `site`, restricted to usable
+    /// entries as described in [`cached`](Self::cached).
+    fn at_position(&self, site: &DiveSite) -> Select<GeocodeCache> {
+        let mut query = GeocodeCache::find()
+            .filter(geocode_cache::Column::Latitude.eq(round_coordinate(site.latitude)))
+            .filter(geocode_cache::Column::Longitude.eq(round_coordinate(site.longitude)));
+        if self.inner.is_some() {
+            let cutoff = chrono::Utc::now() - chrono::Duration::days(CACHE_TTL_DAYS);
+            query = query
+                .filter(geocode_cache::Column::Provider.eq(self.provider.to_string()))
+                .filter(geocode_cache::Column::GeocodedAt.gte(cutoff));
+        }
+        query
+    }
+
+    /// Insert or update the address of a geocoded site.
+    #[instrument(name = "geocode-cache-store", skip(self, site), fields(site = %site.name))]
+    async fn store(&self, site: &DiveSite) -> Result<()> {
+        let cache_record = geocode_cache::ActiveModel {
+            site_uuid: Set(site.uuid.to_string()),
+            latitude: Set(round_coordinate(site.latitude)),
+            longitude: Set(round_coordinate(site.longitude)),
+            provider: Set(self.provider.to_string()),
+            country: Set(site.country.to_owned()),
+            iso_country_code: Set(site.iso_country_code.to_owned()),
+            state: Set(site.state.to_owned()),
+            region: Set(site.region.to_owned()),
+            locality: Set(site.locality.to_owned()),
+            geocoded_at: Set(chrono::Utc::now()),
+            ..Default::default()
+        };
+
+        GeocodeCache::insert(cache_record)
+            .on_conflict(
+                OnConflict::columns([
+                    geocode_cache::Column::SiteUuid,
+                    geocode_cache::Column::Latitude,
+                    geocode_cache::Column::Longitude,
+                ])
+                .update_columns([
+                    geocode_cache::Column::Provider,
+                    geocode_cache::Column::Country,
+                    geocode_cache::Column::IsoCountryCode,
+                    geocode_cache::Column::State,
+                    geocode_cache::Column::Region,
+                    geocode_cache::Column::Locality,
+                    geocode_cache::Column::GeocodedAt,
+                ])
+                .to_owned(),
+            )
+            .exec(self.db)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Geocoder for CachedGeocoder<'_> {
+    /// Apply the cached address of `site`, or geocode and cache it on a miss.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Database`](crate::error::Error::Database) if a cache
+    /// read or write fails, or any error of the geocoding service.
+    async fn reverse_geocode(&self, site: DiveSite) -> Result<DiveSite> {
+        if self.provider.is_local()
+            && let Some(inner) = &self.inner
+        {
+            return inner.reverse_geocode(site).await;
+        }
+
+        if let Some(cached) = self.cached(&site).await? {
+            return Ok(apply_cached(site, cached));
+        }
+
+        let Some(inner) = &self.inner else {
+            tracing::debug!(site = site.name, "No cached address in offline mode");
+            return Ok(site);
+        };
+
+        // Geocode without the address recorded in MacDive, so that only what
+        // the service returned ends up in the cache.
+        let address = inner.reverse_geocode(without_address(&site)).await?;
+        if address.country.is_empty()
+            && address.state.is_none()
+            && address.region.is_none()
+            && address.locality.is_none()
+        {
+            tracing::debug!(site = site.name, "No address found, not caching");
+            return Ok(site);
+        }
+        self.store(&address).await?;
+        Ok(merge_address(site, address))
+    }
+
+    /// Apply the most recent cached address of any site at the position of
+    /// `site`, or geocode it on a miss without caching the result.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Database`](crate::error::Error::Database) if the
+    /// cache read fails, or any error of the geocoding service.
+    async fn lookup(&self, site: DiveSite) -> Result<DiveSite> {
+        if self.provider.is_local()
+            && let Some(inner) = &self.inner
+        {
+            return inner.reverse_geocode(site).await;
+        }
+
+        if let Some(cached) = self.cached_position(&site).await? {
+            return Ok(apply_cached(site, cached));
+        }
+
+        match &self.inner {
+            Some(inner) => inner.reverse_geocode(site).await,
+            None => {
+                tracing::debug!(site = site.name, "No cached address in offline mode");
+                Ok(site)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use migration::{Migrator, MigratorTrait};
+    use sea_orm::Database;
+    use uuid::Uuid;
+
+    use super::*;
+
+    /// A geocoder that counts its lookups and knows a single address.
+    struct FakeGeocoder<'a> {
+        lookups: &'a AtomicUsize,
+        locality: Option<&'static str>,
+    }
+
+    #[async_trait]
+    impl Geocoder for FakeGeocoder<'_> {
+        async fn reverse_geocode(&self, site: DiveSite) -> Result<DiveSite> {
+            self.lookups.fetch_add(1, Ordering::SeqCst);
+            Ok(DiveSite {
+                locality: self.locality.map(String::from).or(site.locality),
+                ..site
+            })
+        }
+    }
+
+    async fn cache() -> DbConn {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        Migrator::up(&db, None).await.unwrap();
+        db
+    }
+
+    fn site() -> DiveSite {
+        DiveSite {
+            uuid: Uuid::new_v4(),
+            country: String::from("Bonaire"),
+            iso_country_code: String::from("BQ"),
+            state: Some(String::from("Bonaire")),
+            name: String::from("Salt Pier"),
+            latitude: 12.083,
+            longitude: -68.283,
+            site_id: 1,
+            ..Default::default()
+        }
+    }
+
+    fn geocoder<'a>(
+        db: &'a DbConn,
+        lookups: &'static AtomicUsize,
+        locality: Option<&'static str>,
+    ) -> CachedGeocoder<'a> {
+        CachedGeocoder {
+            db,
+            provider: GeocodingProvider::Nominatim,
+            inner: Some(Box::new(FakeGeocoder { lookups, locality })),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache_hit_and_miss() {
+        static LOOKUPS: AtomicUsize = AtomicUsize::new(0);
+        let db = cache().await;
+        let geocoder = geocoder(&db, &LOOKUPS, Some("Kralendijk"));
+        let site = site();
+
+        let first = geocoder.reverse_geocode(site.clone()).await.unwrap();
+        assert_eq!(Some("Kralendijk"), first.locality.as_deref());
+        // MacDive's values are kept, but not cached.
+        assert_eq!("Bonaire", first.country);
+        assert_eq!(Some("Bonaire"), first.state.as_deref());
+        let cached = geocoder.cached(&site).await.unwrap().unwrap();
+        assert_eq!(None, cached.state);
+
+        let second = geocoder.reverse_geocode(site.clone()).await.unwrap();
+        assert_eq!(first.locality, second.locality);
+        assert_eq!(first.state, second.state);
+        assert_eq!(1, LOOKUPS.load(Ordering::SeqCst));
+
+        // Moving the site misses the cache.
+        let moved = DiveSite {
+            latitude: 12.2,
+            ..site
+        };
+        geocoder.reverse_geocode(moved).await.unwrap();
+        assert_eq!(2, LOOKUPS.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_empty_lookup_is_not_cached() {
+        static LOOKUPS: AtomicUsize = AtomicUsize::new(0);
+        let db = cache().await;
+        let geocoder = geocoder(&db, &LOOKUPS, None);
+        let site = site();
+
+        let found = geocoder.reverse_geocode(site.clone()).await.unwrap();
+        assert_eq!(None, found.locality);
+        assert_eq!(site.state, found.state);
+        assert!(geocoder.cached(&site).await.unwrap().is_none());
+        geocoder.reverse_geocode(site).await.unwrap();
+        assert_eq!(2, LOOKUPS.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_lookup_is_not_cached() {
+        static LOOKUPS: AtomicUsize = AtomicUsize::new(0);
+        let db = cache().await;
+        let geocoder = geocoder(&db, &LOOKUPS, Some("Kralendijk"));
+        let site = site();
+
+        let found = geocoder.lookup(site.clone()).await.unwrap();
+        assert_eq!(Some("Kralendijk"), found.locality.as_deref());
+        assert!(geocoder.cached_position(&site).await.unwrap().is_none());
+        assert_eq!(1, LOOKUPS.load(Ordering::SeqCst));
+
+        // An address cached for another site at the same position is used.
+        geocoder.reverse_geocode(site.clone()).await.unwrap();
+        let imported = DiveSite {
+            uuid: Uuid::new_v4(),
+            ..site
+        };
+        let found = geocoder.lookup(imported).await.unwrap();
+        assert_eq!(Some("Kralendijk"), found.locality.as_deref());
+        assert_eq!(2, LOOKUPS.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_round_coordinate() {
+        assert_eq!(12.1503, round_coordinate(12.150_347));
+        assert_eq!(-68.2771, round_coordinate(-68.277_149));
+    }
+}
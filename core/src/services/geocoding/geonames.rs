@@ -0,0 +1,348 @@
+//! Offline reverse geocoding from GeoNames text dumps.
+//!
+//! Uses the `citiesNNN.txt`, `admin1CodesASCII.txt` and (optionally)
+//! `admin2Codes.txt` and `countryInfo.txt` files from
+//! <https://download.geonames.org/export/dump/>.
+//! Cities are kept in an R-tree of points on the unit sphere, so that the
+//! Euclidean nearest neighbour is also the nearest city on the globe.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use async_trait::async_trait;
+use rstar::{AABB, PointDistance, RTree, RTreeObject};
+
+use crate::domain::DiveSite;
+use crate::error::{Error, Result};
+use crate::util::geo::haversine_distance;
+
+use super::{Geocoder, county_name};
+
+/// City dumps in order of preference (most complete first).
+pub const CITY_FILES: [&str; 4] = [
+    "cities500.txt",
+    "cities1000.txt",
+    "cities5000.txt",
+    "cities15000.txt",
+];
+const ADMIN1_FILE: &str = "admin1CodesASCII.txt";
+const ADMIN2_FILE: &str = "admin2Codes.txt";
+const COUNTRY_FILE: &str = "countryInfo.txt";
+
+/// Sites further than this many meters from any city (of their country, if
+/// known) keep their address unchanged.
+const MAX_REGION_DISTANCE: f64 = 500_000.0;
+
+/// A populated place from a GeoNames city dump.
+#[derive(Debug, Clone)]
+struct City {
+    name: String,
+    latitude: f64,
+    longitude: f64,
+    country_code: String,
+    admin1: String,
+    admin2: String,
+    position: [f64; 3],
+}
+
+impl RTreeObject for City {
+    type Envelope = AABB<[f64; 3]>;
+
+    fn envelope(&self) -> Self::Envelope {
+        AABB::from_point(self.position)
+    }
+}
+
+impl PointDistance for City {
+    fn distance_2(&self, point: &[f64; 3]) -> f64 {
+        self.position
+            .iter()
+            .zip(point)
+            .map(|(a, b)| (a - b).powi(2))
+            .sum()
+    }
+}
+
+/// Position of a WGS84 coordinate on the unit sphere.
+fn unit_vector(latitude: f64, longitude: f64) -> [f64; 3] {
+    let (phi, lambda) = (latitude.to_radians(), longitude.to_radians());
+    [
+        phi.cos() * lambda.cos(),
+        phi.cos() * lambda.sin(),
+        phi.sin(),
+    ]
+}
+
+/// Parse a GeoNames city dump (19 tab-separated columns per line).
+fn parse_cities(content: &str) -> Result<Vec<City>> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(number, line)| {
+            let error = || Error::GeoNames(format!("invalid city record on line {}", number + 1));
+            let columns: Vec<&str> = line.split('\t').collect();
+            if columns.len() < 12 {
+                return Err(error());
+            }
+            let latitude: f64 = columns[4].parse().map_err(|_| error())?;
+            let longitude: f64 = columns[5].parse().map_err(|_| error())?;
+
+            Ok(City {
+                name: columns[1].to_string(),
+                latitude,
+                longitude,
+                country_code: columns[8].to_string(),
+                admin1: columns[10].to_string(),
+                admin2: columns[11].to_string(),
+                position: unit_vector(latitude, longitude),
+            })
+        })
+        .collect()
+}
+
+/// Parse an admin code dump, mapping codes like `US.FL` to their names.
+fn parse_codes(content: &str) -> HashMap<String, String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.split('\t');
+            Some((columns.next()?.to_string(), columns.next()?.to_string()))
+        })
+        .collect()
+}
+
+/// Parse the country dump, mapping ISO codes to country names.
+///
+/// Lines starting with `#` are comments.
+fn parse_countries(content: &str) -> HashMap<String, String> {
+    content
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split('\t').collect();
+            Some((columns.first()?.to_string(), columns.get(4)?.to_string()))
+        })
+        .collect()
+}
+
+/// Geocoder backed by a local copy of the GeoNames dumps.
+///
+/// Sites within `max_distance` of a city in their country get that city as
+/// locality along with its country, state and region. Sites further away —
+/// usually those offshore — only get the country, state and region of the
+/// nearest city. Sites without a country code may match a city of any country.
+pub struct GeoNamesGeocoder {
+    cities: RTree<City>,
+    admin1: HashMap<String, String>,
+    admin2: HashMap<String, String>,
+    countries: HashMap<String, String>,
+    max_distance: f64,
+}
+
+impl GeoNamesGeocoder {
+    /// Load the GeoNames dumps from `directory`.
+    ///
+    /// The most complete city dump found (see [`CITY_FILES`]) is used.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::GeoNames`] if no city dump or no `admin1CodesASCII.txt`
+    /// is found or a city record is malformed, or [`Error::Io`] if a file
+    /// cannot be read.
+    pub fn load(directory: &Path, max_distance: f64) -> Result<Self> {
+        let cities = CITY_FILES
+            .iter()
+            .map(|file| directory.join(file))
+            .find(|path| path.is_file())
+            .ok_or_else(|| {
+                Error::GeoNames(format!("no city dump found in {}", directory.display()))
+            })?;
+        let admin1 = directory.join(ADMIN1_FILE);
+        if !admin1.is_file() {
+            return Err(Error::GeoNames(format!(
+                "{ADMIN1_FILE} not found in {}",
+                directory.display()
+            )));
+        }
+        let optional = |file: &str| match std::fs::read_to_string(directory.join(file)) {
+            Ok(content) => Ok(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+            Err(e) => Err(e),
+        };
+
+        Self::from_dumps(
+            &std::fs::read_to_string(cities)?,
+            &std::fs::read_to_string(admin1)?,
+            &optional(ADMIN2_FILE)?,
+            &optional(COUNTRY_FILE)?,
+            max_distance,
+        )
+    }
+
+    /// Build the geocoder from the content of the dumps.
+    pub(crate) fn from_dumps(
+        cities: &str,
+        admin1: &str,
+        admin2: &str,
+        countries: &str,
+        max_distance: f64,
+    ) -> Result<Self> {
+        Ok(Self {
+            cities: RTree::bulk_load(parse_cities(cities)?),
+            admin1: parse_codes(admin1),
+            admin2: parse_codes(admin2),
+            countries: parse_countries(countries),
+            max_distance,
+        })
+    }
+
+    /// Name of the country with the ISO code `code`.
+    ///
+    /// Falls back to the names `celes` knows when `countryInfo.txt` is missing.
+    fn country_name(&self, code: &str) -> Option<String> {
+        self.countries.get(code).cloned().or_else(|| {
+            celes::Country::from_alpha2(code)
+                .ok()
+                .map(|country| country.long_name.to_string())
+        })
+    }
+
+    /// Fill in locality, state and region of `site` from the nearest city.
+    fn locate(&self, mut site: DiveSite) -> DiveSite {
+        let position = unit_vector(site.latitude, site.longitude);
+        let nearest = self
+            .cities
+            .nearest_neighbor_iter(position)
+            .map(|city| {
+                let distance = haversine_distance(
+                    site.latitude,
+                    site.longitude,
+                    city.latitude,
+                    city.longitude,
+                );
+                (city, distance)
+            })
+            .take_while(|(_, distance)| *distance <= MAX_REGION_DISTANCE)
+            .find(|(city, _)| {
+                site.iso_country_code.is_empty()
+                    || city
+                        .country_code
+                        .eq_ignore_ascii_case(&site.iso_country_code)
+            });
+
+        let Some((city, distance)) = nearest else {
+            tracing::debug!(site = site.name, "No city nearby");
+            return site;
+        };
+
+        if let Some(country) = self.country_name(&city.country_code) {
+            site.country = country;
+            site.iso_country_code = city.country_code.to_uppercase();
+        }
+        let admin1 = format!("{}.{}", city.country_code, city.admin1);
+        if let Some(state) = self.admin1.get(&admin1) {
+            site.state = Some(state.to_owned());
+        }
+        if let Some(region) = self.admin2.get(&format!("{admin1}.{}", city.admin2)) {
+            site.region = Some(county_name(region));
+        }
+        if distance <= self.max_distance {
+            site.locality = Some(city.name.to_owned());
+        }
+
+        site
+    }
+}
+
+#[async_trait]
+impl Geocoder for GeoNamesGeocoder {
+    async fn reverse_geocode(&self, site: DiveSite) -> Result<DiveSite> {
+        Ok(self.locate(site))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    const CITIES: &str = "\
+4155995\tIslamorada\tIslamorada\t\t24.92431\t-80.62784\tP\tPPL\tUS\t\tFL\t087\t\t\t6119\t2\t5\tAmerica/New_York\t2011-05-14
+4160822\tKey Largo\tKey Largo\t\t25.08652\t-80.44728\tP\tPPL\tUS\t\tFL\t087\t\t\t10433\t2\t3\tAmerica/New_York\t2011-05-14
+4163407\tMarathon\tMarathon\t\t24.71375\t-81.09035\tP\tPPL\tUS\t\tFL\t087\t\t\t8297\t2\t2\tAmerica/New_York\t2011-05-14
+3513563\tKralendijk\tKralendijk\t\t12.15\t-68.26667\tP\tPPLC\tBQ\t\t00\t\t\t\t3081\t\t1\tAmerica/Kralendijk\t2019-09-05
+";
+    const ADMIN1: &str = "US.FL\tFlorida\tFlorida\t4155751\n";
+    const ADMIN2: &str = "US.FL.087\tMonroe County\tMonroe County\t4164035\n";
+    const COUNTRIES: &str = "\
+#ISO\tISO3\tISO-Numeric\tfips\tCountry
+BQ\tBES\t535\t\tBonaire, Saint Eustatius and Saba\t
+US\tUSA\t840\tUS\tUnited States\t
+";
+
+    fn site(latitude: f64, longitude: f64, iso_country_code: &str) -> DiveSite {
+        DiveSite {
+            uuid: Uuid::new_v4(),
+            iso_country_code: iso_country_code.to_string(),
+            name: String::from("Test Site"),
+            latitude,
+            longitude,
+            site_id: 1,
+            ..Default::default()
+        }
+    }
+
+    fn geocoder() -> GeoNamesGeocoder {
+        GeoNamesGeocoder::from_dumps(CITIES, ADMIN1, ADMIN2, COUNTRIES, 10_000.0).unwrap()
+    }
+
+    #[test]
+    fn test_nearest_city() {
+        // About 6 km off Key Largo.
+        let site = geocoder().locate(site(25.05, -80.40, "US"));
+        assert_eq!(Some("Key Largo"), site.locality.as_deref());
+        assert_eq!(Some("Florida"), site.state.as_deref());
+        assert_eq!(Some("Monroe"), site.region.as_deref());
+        assert_eq!("United States", site.country);
+    }
+
+    #[test]
+    fn test_site_without_country() {
+        let salt_pier = geocoder().locate(site(12.1503, -68.2771, ""));
+        assert_eq!(Some("Kralendijk"), salt_pier.locality.as_deref());
+        assert_eq!("BQ", salt_pier.iso_country_code);
+        assert_eq!("Bonaire, Saint Eustatius and Saba", salt_pier.country);
+
+        // Without countryInfo.txt the country still gets a name.
+        let geocoder = GeoNamesGeocoder::from_dumps(CITIES, ADMIN1, "", "", 10_000.0).unwrap();
+        let salt_pier = geocoder.locate(site(12.1503, -68.2771, ""));
+        assert!(!salt_pier.country.is_empty());
+    }
+
+    #[test]
+    fn test_region_only_offshore() {
+        // Far offshore in the Florida Straits.
+        let site = geocoder().locate(site(24.3, -80.9, "US"));
+        assert_eq!(None, site.locality);
+        assert_eq!(Some("Florida"), site.state.as_deref());
+    }
+
+    #[test]
+    fn test_only_cities_of_the_same_country() {
+        // Near Key Largo, but recorded as a Bonaire site.
+        let offshore = geocoder().locate(site(25.0104, -80.3745, "BQ"));
+        assert_eq!(None, offshore.locality);
+        assert_eq!(None, offshore.state);
+
+        let salt_pier = geocoder().locate(site(12.1503, -68.2771, "BQ"));
+        assert_eq!(Some("Kralendijk"), salt_pier.locality.as_deref());
+        assert_eq!(None, salt_pier.state);
+    }
+
+    #[test]
+    fn test_invalid_city_record() {
+        assert!(parse_cities("1\tSomewhere\tSomewhere\t\tnorth\t0\n").is_err());
+    }
+}
@@ -0,0 +1,447 @@
+//! Reverse geocoding through the Google Maps Geocoding API.
+//!
+//! Which address components become the state, region and locality of a site
+//! depends on the country, see [`AddressMapping`]. Built-in mappings cover
+//! the main dive destinations and can be replaced per country in the
+//! configuration file.
+
+use std::collections::BTreeMap;
+use std::convert::TryInto;
+
+use async_trait::async_trait;
+use google_maps::{ClientSettings, LatLng, PlaceType};
+
+use crate::domain::{AddressMapping, DiveSite};
+use crate::error::{Error, Result};
+
+use super::Geocoder;
+
+/// An address component of a geocoding result.
+#[derive(Debug, Clone)]
+struct Component {
+    /// Place type codes, e.g. `locality`.
+    types: Vec<String>,
+    long_name: String,
+    short_name: String,
+}
+
+/// Build a mapping that keeps region names without a suffix.
+fn mapping(
+    state: &[&str],
+    region: &[&str],
+    locality: &[&str],
+    suffixes: &[&str],
+) -> AddressMapping {
+    let owned = |values: &[&str]| values.iter().map(|v| v.to_string()).collect();
+    AddressMapping {
+        state: owned(state),
+        region: owned(region),
+        locality: owned(locality),
+        region_suffixes: owned(suffixes),
+        require_region_suffix: false,
+    }
+}
+
+/// Mappings for countries where the default administrative levels do not
+/// match how divers name places.
+fn builtin_mappings() -> BTreeMap<String, AddressMapping> {
+    const LEVEL_1: &str = "administrative_area_level_1";
+    const LEVEL_2: &str = "administrative_area_level_2";
+    const LEVEL_3: &str = "administrative_area_level_3";
+
+    let mut mappings = BTreeMap::new();
+    // Provinces, regencies (kabupaten) and cities (kota), districts
+    // (kecamatan) for sites outside of a town.
+    mappings.insert(
+        String::from("ID"),
+        mapping(
+            &[LEVEL_1],
+            &[LEVEL_2],
+            &[
+                "locality",
+                LEVEL_3,
+                "sublocality",
+                "colloquial_area",
+                "natural_feature",
+            ],
+            &["Regency", "City"],
+        ),
+    );
+    // Level 1 are the administrative regions (e.g. Central Visayas), divers
+    // think in provinces and islands.
+    mappings.insert(
+        String::from("PH"),
+        mapping(
+            &[LEVEL_2],
+            &["natural_feature", "colloquial_area", "archipelago"],
+            &["locality", "sublocality", LEVEL_3],
+            &[],
+        ),
+    );
+    // States (Sabah) and their divisions or districts.
+    mappings.insert(
+        String::from("MY"),
+        mapping(
+            &[LEVEL_1],
+            &[LEVEL_2],
+            &["locality", "sublocality", "colloquial_area"],
+            &["Division", "District"],
+        ),
+    );
+    mappings.insert(
+        String::from("TH"),
+        mapping(
+            &[LEVEL_1],
+            &[LEVEL_2],
+            &["locality", "sublocality", "natural_feature"],
+            &["District"],
+        ),
+    );
+    mappings.insert(
+        String::from("EG"),
+        mapping(
+            &[LEVEL_1],
+            &[LEVEL_2, "colloquial_area"],
+            &["locality", "natural_feature"],
+            &["Governorate"],
+        ),
+    );
+    // Atolls are the first administrative level.
+    mappings.insert(
+        String::from("MV"),
+        mapping(
+            &[LEVEL_1],
+            &["natural_feature", "colloquial_area"],
+            &["locality", "natural_feature"],
+            &[],
+        ),
+    );
+    // Small island nations and territories, mostly without meaningful
+    // administrative levels below the country.
+    for code in [
+        "AW", "BQ", "CW", "KY", "TC", "BZ", "FJ", "FM", "PW", "PG", "SB", "VU", "SC", "MU",
+    ] {
+        mappings.insert(
+            String::from(code),
+            mapping(
+                &[LEVEL_1],
+                &["natural_feature", "colloquial_area", LEVEL_2],
+                &[
+                    "locality",
+                    "sublocality",
+                    "colloquial_area",
+                    "natural_feature",
+                ],
+                &["County", "District"],
+            ),
+        );
+    }
+    mappings
+}
+
+/// Check that all component types of the mappings are known place types and
+/// that no country has more than one mapping.
+///
+/// Country codes are compared ignoring case, since they are uppercased
+/// before use.
+fn validate(mappings: &BTreeMap<String, AddressMapping>) -> Result<()> {
+    let mut codes = BTreeMap::new();
+    for (code, mapping) in mappings {
+        if let Some(other) = codes.insert(code.to_uppercase(), code) {
+            return Err(Error::Config(format!(
+                "address components mapped twice for {other} and {code}"
+            )));
+        }
+        for name in mapping
+            .state
+            .iter()
+            .chain(&mapping.region)
+            .chain(&mapping.locality)
+        {
+            if name.parse::<PlaceType>().ok() == Some(PlaceType::Other) {
+                return Err(Error::Config(format!(
+                    "unknown address component type `{name}` for {code}"
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The name of the first component with the first of `types` that occurs.
+fn find<'a>(components: &'a [Component], types: &[String]) -> Option<&'a str> {
+    types.iter().find_map(|kind| {
+        components
+            .iter()
+            .find(|component| component.types.contains(kind))
+            .map(|component| component.long_name.as_str())
+    })
+}
+
+/// The last component with the first of `types` that occurs.
+fn find_last<'a>(components: &'a [Component], types: &[String]) -> Option<&'a Component> {
+    types.iter().find_map(|kind| {
+        components
+            .iter()
+            .rfind(|component| component.types.contains(kind))
+    })
+}
+
+/// Geocoder backed by the Google Maps Geocoding API.
+pub struct GoogleGeocoder {
+    client: ClientSettings,
+    mappings: BTreeMap<String, AddressMapping>,
+    fallback: AddressMapping,
+}
+
+impl GoogleGeocoder {
+    /// Create a geocoder using the Google Maps API key `key`.
+    ///
+    /// `mappings` are keyed by ISO country code and replace the built-in
+    /// mapping of their country.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::GeocodingFailed`] if the client cannot be created, or
+    /// [`Error::Config`] if a mapping uses an unknown component type or a
+    /// country is mapped twice, e.g. as `ph` and `PH`.
+    pub fn new(key: &str, mappings: &BTreeMap<String, AddressMapping>) -> Result<Self> {
+        validate(mappings)?;
+        let mut all = builtin_mappings();
+        for (code, mapping) in mappings {
+            all.insert(code.to_uppercase(), mapping.clone());
+        }
+
+        let client = ClientSettings::try_new(key).map_err(|_e| Error::GeocodingFailed)?;
+        Ok(Self {
+            client,
+            mappings: all,
+            fallback: AddressMapping::default(),
+        })
+    }
+
+    /// Copy the address components of a result onto `site`.
+    ///
+    /// Components are expected in the order of the results, most specific
+    /// first.
+    fn apply(&self, mut site: DiveSite, components: &[Component]) -> DiveSite {
+        if let Some(country) = components
+            .iter()
+            .find(|component| component.types.iter().any(|t| t == "country"))
+        {
+            site.country = country.long_name.to_owned();
+            site.iso_country_code = country.short_name.to_owned();
+        }
+
+        let Some(mapping) = self.mappings.get(&site.iso_country_code.to_uppercase()) else {
+            return self.apply_fallback(site, components);
+        };
+        if let Some(state) = find(components, &mapping.state) {
+            site.state = Some(state.to_string());
+        }
+        if let Some(region) = find(components, &mapping.region) {
+            site.region = mapping.region_name(region);
+        }
+        if let Some(locality) = find(components, &mapping.locality) {
+            site.locality = Some(locality.to_string());
+        }
+        site
+    }
+
+    /// Copy the address components of a result onto a site in a country
+    /// without a mapping.
+    ///
+    /// Unlike mapped countries, the last matching component wins and the
+    /// locality uses the short name, as before mappings existed, so that the
+    /// addresses of existing sites do not change.
+    fn apply_fallback(&self, mut site: DiveSite, components: &[Component]) -> DiveSite {
+        let mapping = &self.fallback;
+        if let Some(state) = find_last(components, &mapping.state) {
+            site.state = Some(state.long_name.to_owned());
+        }
+        if let Some(region) = find_last(components, &mapping.region) {
+            site.region = mapping.region_name(&region.long_name);
+        }
+        if let Some(locality) = find_last(components, &mapping.locality) {
+            site.locality = Some(locality.short_name.to_owned());
+        }
+        site
+    }
+}
+
+#[async_trait]
+impl Geocoder for GoogleGeocoder {
+    /// Reverse-geocode a dive site using the Google Maps Geocoding API.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::GeocodingFailed`] if the HTTP request fails or the
+    /// response cannot be parsed.
+    /// Returns [`Error::InvalidLatitude`] or [`Error::InvalidLongitude`] if the
+    /// site's coordinates cannot be converted to a `LatLng`.
+    async fn reverse_geocode(&self, site: DiveSite) -> Result<DiveSite> {
+        let latlng: LatLng = site.clone().try_into()?;
+
+        let location = self
+            .client
+            .reverse_geocoding(latlng)
+            .with_result_types([
+                PlaceType::PlusCode,
+                PlaceType::Country,
+                PlaceType::NaturalFeature,
+                PlaceType::ColloquialArea,
+            ])
+            .execute()
+            .await
+            .map_err(|_e| Error::GeocodingFailed)?;
+
+        let components: Vec<Component> = location
+            .results
+            .into_iter()
+            .flat_map(|result| result.address_components)
+            .map(|component| Component {
+                types: component.types.iter().map(PlaceType::to_string).collect(),
+                long_name: component.long_name,
+                short_name: component.short_name,
+            })
+            .collect();
+
+        Ok(self.apply(site, &components))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn site() -> DiveSite {
+        DiveSite {
+            uuid: Uuid::new_v4(),
+            country: String::from("Unknown"),
+            iso_country_code: String::from("XX"),
+            region: Some(String::from("Old")),
+            name: String::from("Test Site"),
+            site_id: 1,
+            ..Default::default()
+        }
+    }
+
+    fn component(types: &[&str], name: &str) -> Component {
+        Component {
+            types: types.iter().map(|t| t.to_string()).collect(),
+            long_name: name.to_string(),
+            short_name: name.to_string(),
+        }
+    }
+
+    fn geocoder(mappings: &BTreeMap<String, AddressMapping>) -> GoogleGeocoder {
+        GoogleGeocoder::new("key", mappings).unwrap()
+    }
+
+    fn components(code: &str, country: &str, rest: &[Component]) -> Vec<Component> {
+        let mut components = rest.to_vec();
+        components.push(Component {
+            short_name: code.to_string(),
+            ..component(&["country", "political"], country)
+        });
+        components
+    }
+
+    #[test]
+    fn test_default_mapping() {
+        let components = components(
+            "US",
+            "United States",
+            &[
+                component(&["locality", "political"], "Key Largo"),
+                component(&["administrative_area_level_2"], "Monroe County"),
+                component(&["administrative_area_level_1"], "Florida"),
+            ],
+        );
+        let site = geocoder(&BTreeMap::new()).apply(site(), &components);
+        assert_eq!("US", site.iso_country_code);
+        assert_eq!("United States", site.country);
+        assert_eq!(Some("Florida"), site.state.as_deref());
+        assert_eq!(Some("Monroe"), site.region.as_deref());
+        assert_eq!(Some("Key Largo"), site.locality.as_deref());
+    }
+
+    #[test]
+    fn test_default_mapping_uses_last_component() {
+        let components = components(
+            "US",
+            "United States",
+            &[
+                component(&["locality", "political"], "Tavernier"),
+                Component {
+                    short_name: String::from("Key Largo"),
+                    ..component(&["locality", "political"], "Key Largo Town")
+                },
+            ],
+        );
+        let site = geocoder(&BTreeMap::new()).apply(site(), &components);
+        assert_eq!(Some("Key Largo"), site.locality.as_deref());
+    }
+
+    #[test]
+    fn test_builtin_mapping_with_fallbacks() {
+        // A site in a strait without a town nearby.
+        let components = components(
+            "ID",
+            "Indonesia",
+            &[
+                component(&["administrative_area_level_3"], "Lembeh Selatan"),
+                component(&["administrative_area_level_2"], "Bitung City"),
+                component(&["administrative_area_level_1"], "North Sulawesi"),
+            ],
+        );
+        let site = geocoder(&BTreeMap::new()).apply(site(), &components);
+        assert_eq!(Some("North Sulawesi"), site.state.as_deref());
+        assert_eq!(Some("Bitung"), site.region.as_deref());
+        assert_eq!(Some("Lembeh Selatan"), site.locality.as_deref());
+    }
+
+    #[test]
+    fn test_configured_mapping() {
+        let mut mappings = BTreeMap::new();
+        mappings.insert(
+            String::from("ph"),
+            AddressMapping {
+                region: vec![String::from("administrative_area_level_1")],
+                require_region_suffix: false,
+                ..Default::default()
+            },
+        );
+        let components = components(
+            "PH",
+            "Philippines",
+            &[
+                component(&["administrative_area_level_2"], "Bohol"),
+                component(&["administrative_area_level_1"], "Central Visayas"),
+            ],
+        );
+        let site = geocoder(&mappings).apply(site(), &components);
+        assert_eq!(Some("Central Visayas"), site.state.as_deref());
+        assert_eq!(Some("Central Visayas"), site.region.as_deref());
+
+        mappings.insert(String::from("PH"), AddressMapping::default());
+        assert!(GoogleGeocoder::new("key", &mappings).is_err());
+
+        mappings.remove("ph");
+        mappings.insert(
+            String::from("PH"),
+            AddressMapping {
+                region: vec![String::from("island_group")],
+                ..Default::default()
+            },
+        );
+        assert!(GoogleGeocoder::new("key", &mappings).is_err());
+    }
+
+    #[test]
+    fn test_builtin_mappings_are_valid() {
+        validate(&builtin_mappings()).unwrap();
+    }
+}
@@ -0,0 +1,341 @@
+//! Reverse geocoding services and location override matching.
+//!
+//! This module provides:
+//!
+//! - [`apply_overrides`] – applies user-defined polygon-based location overrides
+//!   to a [`DiveSite`], replacing country/state/region/locality fields when the
+//!   site's GPS coordinates fall within a configured polygon.
+//! - [`Geocoder`] – reverse-geocodes a [`DiveSite`] and fills in country,
+//!   state, region, and locality fields. Implemented for Google Maps
+//!   ([`GoogleGeocoder`]), Nominatim/OpenStreetMap ([`NominatimGeocoder`]) and
+//!   OpenCage ([`OpenCageGeocoder`]), and offline from GeoNames dumps
+//!   ([`GeoNamesGeocoder`]); [`geocoder`] picks one from the
+//!   [`GeocodingConfig`].
+//! - [`CachedGeocoder`] – wraps the configured geocoder with a local cache in
+//!   the application database, which is also used in offline mode.
+
+mod cache;
+mod geonames;
+mod google;
+mod nominatim;
+mod opencage;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use crate::domain::{DiveSite, GeocodingConfig, GeocodingProvider, LocationOverride};
+use crate::error::{Error, Result};
+
+pub use cache::CachedGeocoder;
+pub use geonames::GeoNamesGeocoder;
+pub use google::GoogleGeocoder;
+pub use nominatim::NominatimGeocoder;
+pub use opencage::OpenCageGeocoder;
+
+/// A reverse geocoding service.
+#[async_trait]
+pub trait Geocoder: Send + Sync {
+    /// Fill in the country, ISO country code, state, region, and locality of
+    /// `site` from its GPS coordinates.
+    ///
+    /// Fields the service has no value for are left unchanged, e.g. for sites
+    /// offshore.
+    async fn reverse_geocode(&self, site: DiveSite) -> Result<DiveSite>;
+
+    /// Like [`reverse_geocode`](Self::reverse_geocode), but for positions
+    /// that should not be remembered, e.g. sites that are not in MacDive yet.
+    ///
+    /// Only caching geocoders behave differently.
+    async fn lookup(&self, site: DiveSite) -> Result<DiveSite> {
+        self.reverse_geocode(site).await
+    }
+}
+
+/// Default maximum distance in meters between a site and a GeoNames city.
+const DEFAULT_MAX_DISTANCE: f64 = 10_000.0;
+
+/// Create the geocoder selected in `config`.
+///
+/// Returns `None` if the provider requires an API key but none is configured,
+/// in which case dive sites keep the location recorded in MacDive.
+///
+/// # Errors
+///
+/// Returns [`Error::Config`] if a base URL is configured for Google Maps,
+/// which does not support alternative endpoints, or no GeoNames directory is
+/// configured. Returns [`Error::GeocodingFailed`] if the HTTP client cannot be
+/// created, or [`Error::GeoNames`] if the GeoNames dumps cannot be loaded.
+pub fn geocoder(config: &GeocodingConfig) -> Result<Option<Box<dyn Geocoder>>> {
+    let key = config.api_key.as_deref().filter(|key| !key.is_empty());
+
+    Ok(match config.provider {
+        GeocodingProvider::Google => {
+            if config.base_url.is_some() {
+                return Err(Error::Config(String::from(
+                    "the Google Maps geocoder does not support a custom base URL",
+                )));
+            }
+            match key {
+                Some(key) => Some(Box::new(GoogleGeocoder::new(
+                    key,
+                    &config.address_components,
+                )?)),
+                None => None,
+            }
+        }
+        GeocodingProvider::Nominatim => Some(Box::new(NominatimGeocoder::new(
+            config.base_url.as_deref(),
+            config.email.as_deref(),
+        ))),
+        GeocodingProvider::OpenCage => key.map(|key| {
+            Box::new(OpenCageGeocoder::new(config.base_url.as_deref(), key)) as Box<dyn Geocoder>
+        }),
+        GeocodingProvider::GeoNames => {
+            let path = config.geonames_path.as_deref().ok_or_else(|| {
+                Error::Config(String::from(
+                    "no directory with the GeoNames dumps configured",
+                ))
+            })?;
+            Some(Box::new(GeoNamesGeocoder::load(
+                path,
+                config.max_distance.unwrap_or(DEFAULT_MAX_DISTANCE),
+            )?))
+        }
+    })
+}
+
+/// Address components of an OpenStreetMap-based geocoding result.
+///
+/// Shared by Nominatim and OpenCage, which both use the OpenStreetMap
+/// address keys.
+#[derive(Debug, Default, Deserialize)]
+struct Address {
+    country: Option<String>,
+    country_code: Option<String>,
+    state: Option<String>,
+    county: Option<String>,
+    city: Option<String>,
+    town: Option<String>,
+    village: Option<String>,
+    municipality: Option<String>,
+}
+
+impl Address {
+    /// Copy the address onto `site`, keeping fields the address has no value for.
+    fn apply(self, mut site: DiveSite) -> DiveSite {
+        if let Some(country) = self.country {
+            site.country = country;
+        }
+        if let Some(code) = self.country_code {
+            site.iso_country_code = code.to_uppercase();
+        }
+        if let Some(state) = self.state {
+            site.state = Some(state);
+        }
+        if let Some(county) = self.county {
+            site.region = Some(county_name(&county));
+        }
+        if let Some(locality) = self
+            .city
+            .or(self.town)
+            .or(self.village)
+            .or(self.municipality)
+        {
+            site.locality = Some(locality);
+        }
+        site
+    }
+}
+
+/// Strip a trailing "County" from an administrative area name.
+///
+/// Names without the suffix, as used outside of the US, are kept as they are.
+fn county_name(name: &str) -> String {
+    let name = name.trim();
+    name.strip_suffix("County")
+        .unwrap_or(name)
+        .trim()
+        .to_string()
+}
+
+/// Find the first location override whose polygon contains the given GPS point.
+///
+/// Coordinates use the geographic convention: `x` maps to longitude and `y`
+/// maps to latitude, matching the `geo` crate's `Coord` layout.
+///
+/// # Arguments
+///
+/// * `latitude` – WGS84 latitude in decimal degrees.
+/// * `longitude` – WGS84 longitude in decimal degrees.
+/// * `overrides` – Slice of user-configured location overrides to search, in
+///   the order of [`ApplicationConfig::locations`](crate::domain::ApplicationConfig::locations).
+///
+/// # Returns
+///
+/// A reference to the first matching [`LocationOverride`], or `None` if no
+/// polygon contains the given point.
+fn find_override(
+    latitude: f64,
+    longitude: f64,
+    overrides: &[LocationOverride],
+) -> Option<&LocationOverride> {
+    overrides
+        .iter()
+        .find(|location| location.contains(latitude, longitude))
+}
+
+/// Apply user-defined location overrides to a dive site.
+///
+/// If the site's GPS coordinates fall within one of the configured polygons,
+/// the matching override's country, ISO country code, state, region, and
+/// locality fields are copied onto the site (only fields that are `Some` in
+/// the override are applied).
+///
+/// # Arguments
+///
+/// * `site` – The [`DiveSite`] to update. Consumed and returned by value.
+/// * `overrides` – Slice of polygon-based location overrides to check against.
+///
+/// # Returns
+///
+/// The (potentially modified) site. Returns `Err` only if a future validation
+/// step is added; currently always succeeds.
+///
+/// # Examples
+///
+/// ```
+/// use macdive_toolbox_core::domain::{DiveSite, LocationOverride};
+/// use macdive_toolbox_core::services::geocoding::apply_overrides;
+/// use uuid::Uuid;
+///
+/// let site = DiveSite {
+///     uuid: Uuid::new_v4(),
+///     country: String::from("Unknown"),
+///     iso_country_code: String::from("XX"),
+///     name: String::from("Test Site"),
+///     latitude: 12.0,
+///     longitude: 34.0,
+///     site_id: 1,
+///     ..Default::default()
+/// };
+///
+/// let result = apply_overrides(site, &[]).unwrap();
+/// assert_eq!(result.country, "Unknown");
+/// ```
+pub fn apply_overrides(mut site: DiveSite, overrides: &[LocationOverride]) -> Result<DiveSite> {
+    if let Some(loc) = find_override(site.latitude, site.longitude, overrides) {
+        if let Some(country) = &loc.country {
+            site.country = country.to_owned();
+        }
+        if let Some(code) = &loc.iso_country_code {
+            site.iso_country_code = code.to_owned();
+        }
+        if let Some(state) = &loc.state {
+            site.state = Some(state.to_owned());
+        }
+        if let Some(region) = &loc.region {
+            site.region = Some(region.to_owned());
+        }
+        if let Some(locality) = &loc.locality {
+            site.locality = Some(locality.to_owned());
+        }
+    }
+
+    Ok(site)
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn site() -> DiveSite {
+        DiveSite {
+            uuid: Uuid::new_v4(),
+            country: String::from("Bonaire"),
+            iso_country_code: String::from("BQ"),
+            region: Some(String::from("Leeward")),
+            name: String::from("Salt Pier"),
+            latitude: 12.083,
+            longitude: -68.283,
+            site_id: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_apply_address() {
+        let address: Address = serde_json::from_str(
+            r#"{
+                "town": "Kralendijk",
+                "municipality": "Bonaire",
+                "state": "Caribisch Nederland",
+                "country": "Caribisch Nederland",
+                "country_code": "bq"
+            }"#,
+        )
+        .unwrap();
+
+        let site = address.apply(site());
+        assert_eq!("Caribisch Nederland", site.country);
+        assert_eq!("BQ", site.iso_country_code);
+        assert_eq!(Some("Caribisch Nederland"), site.state.as_deref());
+        assert_eq!(Some("Kralendijk"), site.locality.as_deref());
+        assert_eq!(Some("Leeward"), site.region.as_deref());
+
+        let address = Address {
+            county: Some(String::from("Provincia di Savona")),
+            ..Default::default()
+        };
+        assert_eq!(
+            Some("Provincia di Savona"),
+            address.apply(site).region.as_deref()
+        );
+    }
+
+    #[test]
+    fn test_county_name() {
+        assert_eq!("Monroe", county_name("Monroe County "));
+        assert_eq!("Bonaire", county_name("Bonaire"));
+    }
+
+    #[test]
+    fn test_select_geocoder() {
+        let config = |provider, api_key: Option<&str>, base_url: Option<&str>| GeocodingConfig {
+            provider,
+            base_url: base_url.map(str::to_string),
+            api_key: api_key.map(str::to_string),
+            ..Default::default()
+        };
+
+        assert!(
+            geocoder(&config(GeocodingProvider::Google, None, None))
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            geocoder(&config(GeocodingProvider::OpenCage, Some(""), None))
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            geocoder(&config(
+                GeocodingProvider::Nominatim,
+                None,
+                Some("http://localhost:8080/")
+            ))
+            .unwrap()
+            .is_some()
+        );
+        assert!(
+            geocoder(&config(
+                GeocodingProvider::Google,
+                Some("key"),
+                Some("http://x")
+            ))
+            .is_err()
+        );
+        assert!(geocoder(&config(GeocodingProvider::GeoNames, None, None)).is_err());
+    }
+}
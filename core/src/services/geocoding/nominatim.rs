@@ -0,0 +1,103 @@
+//! Reverse geocoding through Nominatim, the OpenStreetMap geocoder.
+
+use std::sync::LazyLock;
+
+use async_trait::async_trait;
+use reqwest::header::USER_AGENT;
+use serde::Deserialize;
+use tracing::instrument;
+
+use crate::domain::{APPLICATION_NAME, DiveSite};
+use crate::error::Result;
+use crate::util::rate_limit::{ApiRateLimiter, create_rate_limiter, wait_for_permit};
+
+use super::{Address, Geocoder};
+
+const NOMINATIM_URL: &str = "https://nominatim.openstreetmap.org";
+
+/// Shared HTTP client reused across all Nominatim requests.
+static HTTP_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(reqwest::Client::new);
+
+/// Rate limiter for Nominatim (the public instance allows one request per second).
+static NOMINATIM_API_LIMIT: LazyLock<ApiRateLimiter> = LazyLock::new(|| create_rate_limiter(60));
+
+/// Response of the Nominatim `/reverse` endpoint.
+#[derive(Debug, Deserialize)]
+struct ReverseResponse {
+    #[serde(default)]
+    address: Address,
+    error: Option<String>,
+}
+
+/// Geocoder backed by a Nominatim instance.
+///
+/// Needs no API key. The public instance at `nominatim.openstreetmap.org` is
+/// used unless a base URL, e.g. of a self-hosted instance, is given.
+pub struct NominatimGeocoder {
+    base_url: String,
+    email: Option<String>,
+}
+
+impl NominatimGeocoder {
+    /// Create a geocoder for the Nominatim instance at `base_url`.
+    ///
+    /// # Arguments
+    ///
+    /// * `base_url` - Base URL of the instance, the public one if `None`.
+    /// * `email` - Contact address sent along with every request, as asked
+    ///   for by the usage policy of the public instance.
+    pub fn new(base_url: Option<&str>, email: Option<&str>) -> Self {
+        Self {
+            base_url: base_url
+                .unwrap_or(NOMINATIM_URL)
+                .trim_end_matches('/')
+                .to_string(),
+            email: email.map(str::to_string),
+        }
+    }
+}
+
+#[async_trait]
+impl Geocoder for NominatimGeocoder {
+    /// Reverse-geocode a dive site at city level.
+    ///
+    /// Sites Nominatim finds no address for (usually those far offshore) are
+    /// returned unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Http`](crate::error::Error::Http) if the request fails
+    /// or the response cannot be decoded.
+    #[instrument(name = "nominatim-reverse", skip(self, site), fields(site = %site.name))]
+    async fn reverse_geocode(&self, site: DiveSite) -> Result<DiveSite> {
+        wait_for_permit(&NOMINATIM_API_LIMIT).await;
+
+        let mut query = vec![
+            ("format", String::from("jsonv2")),
+            ("lat", site.latitude.to_string()),
+            ("lon", site.longitude.to_string()),
+            ("zoom", String::from("10")),
+            ("addressdetails", String::from("1")),
+        ];
+        if let Some(email) = &self.email {
+            query.push(("email", email.to_owned()));
+        }
+
+        let response = HTTP_CLIENT
+            .get(format!("{}/reverse", self.base_url))
+            .header(USER_AGENT, APPLICATION_NAME)
+            .query(&query)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<ReverseResponse>()
+            .await?;
+
+        if let Some(error) = response.error {
+            tracing::debug!(reason = error, "No address found");
+            return Ok(site);
+        }
+
+        Ok(response.address.apply(site))
+    }
+}
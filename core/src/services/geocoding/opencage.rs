@@ -0,0 +1,88 @@
+//! Reverse geocoding through the OpenCage geocoding API.
+
+use std::sync::LazyLock;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use tracing::instrument;
+
+use crate::domain::DiveSite;
+use crate::error::Result;
+use crate::util::rate_limit::{ApiRateLimiter, create_rate_limiter, wait_for_permit};
+
+use super::{Address, Geocoder};
+
+const OPENCAGE_URL: &str = "https://api.opencagedata.com";
+
+/// Shared HTTP client reused across all OpenCage requests.
+static HTTP_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(reqwest::Client::new);
+
+/// Rate limiter for OpenCage (the free plan allows one request per second).
+static OPENCAGE_API_LIMIT: LazyLock<ApiRateLimiter> = LazyLock::new(|| create_rate_limiter(60));
+
+/// A single result of the OpenCage geocoding API.
+#[derive(Debug, Deserialize)]
+struct GeocodeResult {
+    components: Address,
+}
+
+/// Response of the OpenCage `/geocode/v1/json` endpoint.
+#[derive(Debug, Deserialize)]
+struct GeocodeResponse {
+    results: Vec<GeocodeResult>,
+}
+
+/// Geocoder backed by the OpenCage geocoding API.
+pub struct OpenCageGeocoder {
+    base_url: String,
+    key: String,
+}
+
+impl OpenCageGeocoder {
+    /// Create a geocoder using the OpenCage API key `key`.
+    ///
+    /// The public API is used unless `base_url` is given.
+    pub fn new(base_url: Option<&str>, key: &str) -> Self {
+        Self {
+            base_url: base_url
+                .unwrap_or(OPENCAGE_URL)
+                .trim_end_matches('/')
+                .to_string(),
+            key: key.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl Geocoder for OpenCageGeocoder {
+    /// Reverse-geocode a dive site using the first OpenCage result.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Http`](crate::error::Error::Http) if the request fails
+    /// (including an invalid key or exceeded quota) or the response cannot be
+    /// decoded.
+    #[instrument(name = "opencage-reverse", skip(self, site), fields(site = %site.name))]
+    async fn reverse_geocode(&self, site: DiveSite) -> Result<DiveSite> {
+        wait_for_permit(&OPENCAGE_API_LIMIT).await;
+
+        let response = HTTP_CLIENT
+            .get(format!("{}/geocode/v1/json", self.base_url))
+            .query(&[
+                ("q", format!("{},{}", site.latitude, site.longitude)),
+                ("key", self.key.to_owned()),
+                ("no_annotations", String::from("1")),
+                ("limit", String::from("1")),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<GeocodeResponse>()
+            .await?;
+
+        Ok(match response.results.into_iter().next() {
+            Some(result) => result.components.apply(site),
+            None => site,
+        })
+    }
+}
@@ -0,0 +1,130 @@
+//! GeoJSON features for dive sites and location overrides.
+//!
+//! Used to export data for visual checks in tools like geojson.io or QGIS.
+//! Positions are written as `[longitude, latitude]` as required by RFC 7946.
+
+use serde_json::{Map, Value, json};
+
+use crate::domain::{DiveSite, LocationOverride, Polygon};
+
+fn ring(vertices: &[(f64, f64)]) -> Value {
+    let mut positions: Vec<Value> = vertices
+        .iter()
+        .map(|(lon, lat)| json!([lon, lat]))
+        .collect();
+    // GeoJSON rings must be closed.
+    if let (Some(first), Some(last)) = (vertices.first(), vertices.last())
+        && first != last
+    {
+        positions.push(json!([first.0, first.1]));
+    }
+    Value::Array(positions)
+}
+
+fn polygon(polygon: &Polygon) -> Value {
+    Value::Array(
+        std::iter::once(&polygon.exterior)
+            .chain(&polygon.holes)
+            .map(|vertices| ring(vertices))
+            .collect(),
+    )
+}
+
+fn feature(geometry: Value, properties: Map<String, Value>) -> Value {
+    json!({
+        "type": "Feature",
+        "geometry": geometry,
+        "properties": properties,
+    })
+}
+
+/// Insert `value` into `properties` unless it is `None`.
+fn insert_optional(properties: &mut Map<String, Value>, key: &str, value: Option<&str>) {
+    if let Some(value) = value {
+        properties.insert(key.to_string(), json!(value));
+    }
+}
+
+/// A point feature for a dive site with its location fields as properties.
+pub fn site_feature(site: &DiveSite) -> Value {
+    let mut properties = Map::new();
+    properties.insert(String::from("name"), json!(site.name));
+    properties.insert(String::from("uuid"), json!(site.uuid.to_string()));
+    properties.insert(String::from("country"), json!(site.country));
+    properties.insert(
+        String::from("iso_country_code"),
+        json!(site.iso_country_code),
+    );
+    properties.insert(String::from("altitude"), json!(site.altitude));
+    insert_optional(&mut properties, "state", site.state.as_deref());
+    insert_optional(&mut properties, "region", site.region.as_deref());
+    insert_optional(&mut properties, "locality", site.locality.as_deref());
+    insert_optional(
+        &mut properties,
+        "body_of_water",
+        site.body_of_water.as_deref(),
+    );
+    insert_optional(&mut properties, "water_type", site.water_type.as_deref());
+    insert_optional(&mut properties, "difficulty", site.difficulty.as_deref());
+    insert_optional(&mut properties, "notes", site.notes.as_deref());
+
+    feature(
+        json!({ "type": "Point", "coordinates": [site.longitude, site.latitude] }),
+        properties,
+    )
+}
+
+/// A (multi)polygon feature for a location override with the values it
+/// applies as properties.
+pub fn override_feature(name: &str, location: &LocationOverride) -> Value {
+    let polygons: Vec<Value> = location.all_polygons().map(|p| polygon(&p)).collect();
+    let geometry = match <[Value; 1]>::try_from(polygons) {
+        Ok([single]) => json!({ "type": "Polygon", "coordinates": single }),
+        Err(polygons) => json!({ "type": "MultiPolygon", "coordinates": polygons }),
+    };
+
+    let mut properties = Map::new();
+    properties.insert(String::from("name"), json!(name));
+    properties.insert(String::from("priority"), json!(location.priority));
+    insert_optional(&mut properties, "country", location.country.as_deref());
+    insert_optional(
+        &mut properties,
+        "iso_country_code",
+        location.iso_country_code.as_deref(),
+    );
+    insert_optional(&mut properties, "state", location.state.as_deref());
+    insert_optional(&mut properties, "region", location.region.as_deref());
+    insert_optional(&mut properties, "locality", location.locality.as_deref());
+
+    feature(geometry, properties)
+}
+
+/// Wrap features into a `FeatureCollection`.
+pub fn feature_collection(features: Vec<Value>) -> Value {
+    json!({ "type": "FeatureCollection", "features": features })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::geojson::parse_polygons;
+
+    #[test]
+    fn test_override_feature_round_trip() {
+        let location = LocationOverride {
+            area: vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)],
+            holes: vec![vec![(1.0, 1.0), (2.0, 1.0), (2.0, 2.0), (1.0, 1.0)]],
+            state: Some(String::from("Somewhere")),
+            ..Default::default()
+        };
+
+        let feature = override_feature("test", &location);
+        assert_eq!("Polygon", feature["geometry"]["type"]);
+        assert_eq!("Somewhere", feature["properties"]["state"]);
+        assert!(feature["properties"].get("region").is_none());
+
+        let polygons = parse_polygons(&feature.to_string()).unwrap();
+        assert_eq!(4, polygons[0].exterior.len());
+        assert_eq!(location.holes, polygons[0].holes);
+    }
+}
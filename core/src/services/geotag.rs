@@ -0,0 +1,203 @@
+//! Match photos to MacDive dives by their capture time.
+//!
+//! Cameras and dive computers both record local wall-clock time without a
+//! time zone, so capture times are compared against the dive's raw start
+//! time as shown by the dive computer rather than against UTC.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use chrono::{NaiveDate, NaiveDateTime, TimeDelta};
+
+use crate::domain::Dive;
+use crate::error::{Error, Result};
+
+/// How a photo was matched to a dive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiveMatch {
+    /// The photo was taken during the dive.
+    During,
+    /// The photo was taken outside of any dive; the value is the distance to
+    /// the nearest dive (negative if the photo was taken before the dive).
+    Nearest(TimeDelta),
+}
+
+/// Read the EXIF `DateTimeOriginal` of a JPEG, HEIF, PNG or TIFF-based RAW file.
+///
+/// # Errors
+///
+/// Returns [`Error::Io`] if the file cannot be opened, or [`Error::Exif`] if it
+/// has no (valid) capture time.
+pub fn capture_time(path: &Path) -> Result<NaiveDateTime> {
+    let file = File::open(path)?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut BufReader::new(file))
+        .map_err(|e| Error::Exif(e.to_string()))?;
+
+    let field = exif
+        .get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY)
+        .ok_or_else(|| Error::Exif(String::from("no DateTimeOriginal")))?;
+    let exif::Value::Ascii(ref values) = field.value else {
+        return Err(Error::Exif(String::from(
+            "DateTimeOriginal is not a string",
+        )));
+    };
+    let value = values
+        .first()
+        .ok_or_else(|| Error::Exif(String::from("DateTimeOriginal is empty")))?;
+    let dt = exif::DateTime::from_ascii(value).map_err(|e| Error::Exif(e.to_string()))?;
+
+    NaiveDate::from_ymd_opt(dt.year.into(), dt.month.into(), dt.day.into())
+        .and_then(|date| date.and_hms_opt(dt.hour.into(), dt.minute.into(), dt.second.into()))
+        .ok_or_else(|| Error::Exif(format!("invalid DateTimeOriginal `{dt}`")))
+}
+
+/// Parse a camera clock offset in the form `[+|-]HH:MM[:SS]`.
+///
+/// The offset is how far the camera clock is ahead of the dive computer, so
+/// `-00:02:30` means the camera was two and a half minutes behind.
+///
+/// # Errors
+///
+/// Returns [`Error::Config`] if the offset is malformed.
+///
+/// # Examples
+///
+/// ```
+/// use chrono::TimeDelta;
+/// use macdive_toolbox_core::services::geotag::parse_clock_offset;
+///
+/// assert_eq!(TimeDelta::seconds(-150), parse_clock_offset("-00:02:30").unwrap());
+/// assert_eq!(TimeDelta::hours(1), parse_clock_offset("01:00").unwrap());
+/// ```
+pub fn parse_clock_offset(value: &str) -> Result<TimeDelta> {
+    let error = || {
+        Error::Config(format!(
+            "invalid clock offset `{value}`, expected [+|-]HH:MM[:SS]"
+        ))
+    };
+
+    let (sign, rest) = match value.trim().strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, value.trim().trim_start_matches('+')),
+    };
+    let parts = rest
+        .split(':')
+        .map(|part| part.parse::<u32>().map_err(|_| error()))
+        .collect::<Result<Vec<u32>>>()?;
+    let (hours, minutes, seconds) = match parts[..] {
+        [hours, minutes] => (hours, minutes, 0),
+        [hours, minutes, seconds] => (hours, minutes, seconds),
+        _ => return Err(error()),
+    };
+    if minutes >= 60 || seconds >= 60 {
+        return Err(error());
+    }
+
+    Ok(TimeDelta::seconds(
+        sign * (i64::from(hours) * 3600 + i64::from(minutes) * 60 + i64::from(seconds)),
+    ))
+}
+
+/// Find the dive a photo taken at `taken` belongs to.
+///
+/// A dive whose time window contains the capture time always wins. Otherwise
+/// the dive with the nearest start or end is returned, provided it is no
+/// further away than `tolerance`.
+///
+/// # Arguments
+///
+/// * `dives` - Candidate dives in any order.
+/// * `taken` - Capture time of the photo, already corrected for the camera clock offset.
+/// * `tolerance` - Maximum distance to the nearest dive for photos taken between dives.
+pub fn match_dive(
+    dives: &[Dive],
+    taken: NaiveDateTime,
+    tolerance: TimeDelta,
+) -> Option<(&Dive, DiveMatch)> {
+    if let Some(dive) = dives
+        .iter()
+        .find(|dive| dive.start <= taken && taken <= dive.end())
+    {
+        return Some((dive, DiveMatch::During));
+    }
+
+    dives
+        .iter()
+        .map(|dive| {
+            let distance = if taken < dive.start {
+                taken - dive.start
+            } else {
+                taken - dive.end()
+            };
+            (dive, distance)
+        })
+        .filter(|(_, distance)| distance.abs() <= tolerance)
+        .min_by_key(|(_, distance)| distance.abs())
+        .map(|(dive, distance)| (dive, DiveMatch::Nearest(distance)))
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn time(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 3, 15)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    fn dives() -> Vec<Dive> {
+        [(1, time(9, 0)), (2, time(11, 0))]
+            .into_iter()
+            .map(|(number, start)| Dive {
+                uuid: Some(Uuid::new_v4()),
+                number: Some(number),
+                site_id: Some(number),
+                start,
+                duration: TimeDelta::minutes(50),
+                dive_id: number,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_match_during_dive() {
+        let dives = dives();
+        let (dive, kind) = match_dive(&dives, time(11, 20), TimeDelta::zero()).unwrap();
+        assert_eq!(Some(2), dive.number);
+        assert_eq!(DiveMatch::During, kind);
+    }
+
+    #[test]
+    fn test_match_nearest_dive() {
+        let dives = dives();
+        // 20 minutes after the first dive, 50 minutes before the second one.
+        let (dive, kind) = match_dive(&dives, time(10, 10), TimeDelta::hours(1)).unwrap();
+        assert_eq!(Some(1), dive.number);
+        assert_eq!(DiveMatch::Nearest(TimeDelta::minutes(20)), kind);
+
+        let (dive, kind) = match_dive(&dives, time(10, 50), TimeDelta::hours(1)).unwrap();
+        assert_eq!(Some(2), dive.number);
+        assert_eq!(DiveMatch::Nearest(TimeDelta::minutes(-10)), kind);
+    }
+
+    #[test]
+    fn test_match_outside_tolerance() {
+        assert!(match_dive(&dives(), time(14, 0), TimeDelta::hours(1)).is_none());
+    }
+
+    #[test]
+    fn test_parse_clock_offset() {
+        assert_eq!(
+            TimeDelta::seconds(3723),
+            parse_clock_offset("+01:02:03").unwrap()
+        );
+        assert!(parse_clock_offset("1:60").is_err());
+        assert!(parse_clock_offset("10").is_err());
+    }
+}
@@ -0,0 +1,198 @@
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::domain::DiveSite;
+use crate::lrcat::queries::CatalogPhoto;
+use crate::util::geo::haversine_distance;
+
+use super::diff::FieldChange;
+use super::preset::MetadataPreset;
+
+/// What is wrong with the location of a catalog photo.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "issue", rename_all = "snake_case")]
+pub enum LocationIssue {
+    /// The photo has neither GPS nor IPTC location data.
+    MissingLocation,
+    /// The photo's location disagrees with the dive site; `old` holds the
+    /// photo's value and `new` the expected one.
+    Mismatch { changes: Vec<FieldChange> },
+}
+
+/// A single line of the catalog audit worklist.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    /// Absolute path of the photo.
+    pub photo: String,
+    /// Local capture time, corrected for the camera clock offset.
+    pub taken: String,
+    /// Log book number of the matching dive.
+    pub dive: Option<i64>,
+    /// UUID of the dive site (and its metadata preset).
+    pub site: Uuid,
+    /// Title of the metadata preset to apply, if it has been exported.
+    pub preset: Option<String>,
+    /// The problem found.
+    #[serde(flatten)]
+    pub issue: LocationIssue,
+}
+
+/// Check the location of a catalog photo against the site of its dive.
+///
+/// Text fields are only compared when both the photo and the expected value
+/// are set, so partially tagged photos are not reported. The expected values
+/// come from the exported `preset` if there is one, since that is what would
+/// be applied, and from the site otherwise.
+///
+/// # Arguments
+///
+/// * `photo` - The photo from the Lightroom catalog.
+/// * `site` - The dive site of the dive the photo was taken on.
+/// * `preset` - The metadata preset on disk for the site, if any.
+/// * `max_distance` - Maximum distance in meters between the photo's GPS position and the site.
+pub fn audit_photo(
+    photo: &CatalogPhoto,
+    site: &DiveSite,
+    preset: Option<&MetadataPreset>,
+    max_distance: f64,
+) -> Option<LocationIssue> {
+    if photo.has_no_location() {
+        return Some(LocationIssue::MissingLocation);
+    }
+
+    let mut changes = vec![];
+    if let (Some(latitude), Some(longitude)) = (photo.latitude, photo.longitude)
+        && haversine_distance(latitude, longitude, site.latitude, site.longitude) > max_distance
+    {
+        changes.push(FieldChange {
+            field: "gps",
+            old: format!("{latitude:.5}, {longitude:.5}"),
+            new: format!("{:.5}, {:.5}", site.latitude, site.longitude),
+        });
+    }
+
+    let expected = |from_preset: Option<&String>, from_site: Option<&String>| {
+        preset
+            .map_or(from_site, |_| from_preset)
+            .cloned()
+            .unwrap_or_default()
+    };
+    let fields = [
+        (
+            "location",
+            &photo.location,
+            expected(preset.map(|p| &p.location), Some(&site.name)),
+        ),
+        (
+            "city",
+            &photo.city,
+            expected(preset.map(|p| &p.city), site.locality.as_ref()),
+        ),
+        (
+            "state",
+            &photo.state,
+            expected(preset.map(|p| &p.state), site.state.as_ref()),
+        ),
+        (
+            "country",
+            &photo.country,
+            expected(preset.map(|p| &p.country), Some(&site.country)),
+        ),
+        (
+            "iso_country_code",
+            &photo.iso_country_code,
+            expected(
+                preset.map(|p| &p.iso_country_code),
+                Some(&site.iso_country_code),
+            ),
+        ),
+    ];
+    for (field, actual, expected) in fields {
+        let actual = actual.as_deref().unwrap_or_default().trim();
+        if !actual.is_empty() && !expected.is_empty() && !actual.eq_ignore_ascii_case(&expected) {
+            changes.push(FieldChange {
+                field,
+                old: actual.to_string(),
+                new: expected,
+            });
+        }
+    }
+
+    (!changes.is_empty()).then_some(LocationIssue::Mismatch { changes })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn site() -> DiveSite {
+        DiveSite {
+            country: String::from("Bonaire"),
+            iso_country_code: String::from("BQ"),
+            locality: Some(String::from("Kralendijk")),
+            name: String::from("Salt Pier"),
+            latitude: 12.083,
+            longitude: -68.283,
+            site_id: 1,
+            ..Default::default()
+        }
+    }
+
+    fn photo() -> CatalogPhoto {
+        CatalogPhoto {
+            id: 1,
+            path: String::from("/photos/IMG_0001.CR2"),
+            capture_time: Some(String::from("2024-03-15T10:12:33.45+01:00")),
+            latitude: None,
+            longitude: None,
+            location: None,
+            city: None,
+            state: None,
+            country: None,
+            iso_country_code: None,
+        }
+    }
+
+    #[test]
+    fn test_capture_time() {
+        assert_eq!("2024-03-15 10:12:33", photo().taken().unwrap().to_string());
+    }
+
+    #[test]
+    fn test_missing_location() {
+        assert_eq!(
+            Some(LocationIssue::MissingLocation),
+            audit_photo(&photo(), &site(), None, 1000.0)
+        );
+    }
+
+    #[test]
+    fn test_matching_location() {
+        let photo = CatalogPhoto {
+            latitude: Some(12.0831),
+            longitude: Some(-68.2831),
+            location: Some(String::from("salt pier")),
+            iso_country_code: Some(String::from("bq")),
+            ..photo()
+        };
+        assert_eq!(None, audit_photo(&photo, &site(), None, 1000.0));
+    }
+
+    #[test]
+    fn test_mismatching_location() {
+        let photo = CatalogPhoto {
+            latitude: Some(12.2),
+            longitude: Some(-68.3),
+            city: Some(String::from("Rincon")),
+            ..photo()
+        };
+        let Some(LocationIssue::Mismatch { changes }) = audit_photo(&photo, &site(), None, 1000.0)
+        else {
+            panic!("expected a mismatch");
+        };
+        assert_eq!(
+            vec!["gps", "city"],
+            changes.iter().map(|c| c.field).collect::<Vec<_>>()
+        );
+    }
+}
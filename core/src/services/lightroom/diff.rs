@@ -0,0 +1,183 @@
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::preset::MetadataPreset;
+
+/// How a freshly built preset relates to the preset stored on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PresetStatus {
+    /// No preset with this UUID exists yet.
+    New,
+    /// The preset on disk already carries identical values.
+    Unchanged,
+    /// At least one field differs from the preset on disk.
+    Changed,
+}
+
+/// A single field whose value differs between the preset on disk and the new one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldChange {
+    /// Name of the changed field (e.g. `gps` or `city`).
+    pub field: &'static str,
+    /// Value currently stored on disk.
+    pub old: String,
+    /// Value that would be written.
+    pub new: String,
+}
+
+/// Comparison result for a single preset.
+#[derive(Debug, Clone, Serialize)]
+pub struct PresetDiff {
+    /// UUID of the preset (and dive site).
+    pub id: Uuid,
+    /// Title of the new preset.
+    pub title: String,
+    /// Overall status of the preset.
+    pub status: PresetStatus,
+    /// Field-level changes; empty unless `status` is [`PresetStatus::Changed`].
+    pub changes: Vec<FieldChange>,
+}
+
+impl PresetDiff {
+    /// Returns `true` if the preset needs to be written to disk.
+    pub fn needs_write(&self) -> bool {
+        self.status != PresetStatus::Unchanged
+    }
+}
+
+/// Compare a freshly built preset against the version currently on disk.
+///
+/// Only fields that are actually written into the `.lrtemplate` file are
+/// compared. The ISO country code is compared case-insensitively because the
+/// template always renders it in upper case.
+///
+/// # Arguments
+///
+/// * `existing` - The preset parsed from disk, or `None` if no file exists yet.
+/// * `preset` - The newly built preset.
+pub fn diff_preset(existing: Option<&MetadataPreset>, preset: &MetadataPreset) -> PresetDiff {
+    let Some(existing) = existing else {
+        return PresetDiff {
+            id: preset.id,
+            title: preset.title.clone(),
+            status: PresetStatus::New,
+            changes: vec![],
+        };
+    };
+
+    let fields: [(&'static str, String, String); 14] = [
+        ("file", existing.file_name.clone(), preset.file_name.clone()),
+        ("title", existing.title.clone(), preset.title.clone()),
+        (
+            "internal_name",
+            existing.internal_name.clone(),
+            preset.internal_name.clone(),
+        ),
+        ("gps", existing.gps.clone(), preset.gps.clone()),
+        (
+            "altitude",
+            existing.altitude.clone(),
+            preset.altitude.clone(),
+        ),
+        (
+            "location",
+            existing.location.clone(),
+            preset.location.clone(),
+        ),
+        ("city", existing.city.clone(), preset.city.clone()),
+        ("state", existing.state.clone(), preset.state.clone()),
+        ("country", existing.country.clone(), preset.country.clone()),
+        (
+            "iso_country_code",
+            existing.iso_country_code.to_uppercase(),
+            preset.iso_country_code.to_uppercase(),
+        ),
+        ("creator", existing.creator.clone(), preset.creator.clone()),
+        (
+            "copyright",
+            existing.copyright.clone(),
+            preset.copyright.clone(),
+        ),
+        (
+            "keywords",
+            existing.keywords.join(", "),
+            preset.keywords.join(", "),
+        ),
+        ("scene", existing.scene.clone(), preset.scene.clone()),
+    ];
+
+    let changes: Vec<FieldChange> = fields
+        .into_iter()
+        .filter(|(_, old, new)| old != new)
+        .map(|(field, old, new)| FieldChange { field, old, new })
+        .collect();
+
+    PresetDiff {
+        id: preset.id,
+        title: preset.title.clone(),
+        status: if changes.is_empty() {
+            PresetStatus::Unchanged
+        } else {
+            PresetStatus::Changed
+        },
+        changes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preset() -> MetadataPreset {
+        MetadataPreset {
+            id: Uuid::nil(),
+            gps: r#"12°9'0" N 68°16'48" W"#.to_string(),
+            title: "[Location] Bonaire: Salt Pier".to_string(),
+            internal_name: "[Location] Bonaire: Salt Pier".to_string(),
+            file_name: "MacDive-00000000-0000-0000-0000-000000000000".to_string(),
+            location: "Salt Pier".to_string(),
+            city: "Kralendijk".to_string(),
+            region: "Bonaire".to_string(),
+            state: "Bonaire".to_string(),
+            country: "Bonaire".to_string(),
+            iso_country_code: "bq".to_string(),
+            scene: "011300".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_diff_new_preset() {
+        let diff = diff_preset(None, &preset());
+        assert_eq!(PresetStatus::New, diff.status);
+        assert!(diff.needs_write());
+    }
+
+    #[test]
+    fn test_diff_unchanged_preset() {
+        let mut existing = preset();
+        // Region is not stored on disk and the ISO code is upper-cased.
+        existing.region = String::new();
+        existing.iso_country_code = "BQ".to_string();
+
+        let diff = diff_preset(Some(&existing), &preset());
+        assert_eq!(PresetStatus::Unchanged, diff.status);
+        assert!(!diff.needs_write());
+    }
+
+    #[test]
+    fn test_diff_changed_fields() {
+        let mut existing = preset();
+        existing.city = String::new();
+        existing.gps = r#"0°0'0" 0°0'0""#.to_string();
+
+        let diff = diff_preset(Some(&existing), &preset());
+        assert_eq!(PresetStatus::Changed, diff.status);
+        assert_eq!(
+            vec!["gps", "city"],
+            diff.changes.iter().map(|c| c.field).collect::<Vec<_>>()
+        );
+        assert_eq!("Kralendijk", diff.changes[1].new);
+    }
+}
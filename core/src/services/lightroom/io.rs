@@ -1,42 +1,92 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::io::Write as IoWrite;
-use std::path::Path;
-use std::sync::LazyLock;
-
-use regex::{Regex, RegexBuilder};
+use std::path::{Path, PathBuf};
 use uuid::Uuid;
 use walkdir::{DirEntry, WalkDir};
 
 use crate::error::{Error, Result};
 
 use super::preset::MetadataPreset;
+use super::template::{PRESET_GENERATOR, PresetFile};
+
+/// File name prefix used by the default naming scheme for presets created by this tool.
+const PRESET_FILE_PREFIX: &str = "MacDive-";
+
+/// File extension of Lightroom preset files.
+const PRESET_FILE_EXTENSION: &str = "lrtemplate";
+
+/// A metadata preset found on disk, together with its parsed content.
+#[derive(Debug, Clone)]
+pub struct ExistingPreset {
+    /// Directory entry of the `.lrtemplate` file.
+    pub entry: DirEntry,
+    /// Path of the file relative to the presets folder, without extension.
+    pub file_name: String,
+    /// The parsed preset file.
+    pub file: PresetFile,
+}
+
+impl ExistingPreset {
+    /// The fields of this preset that are managed by [`MetadataPreset`].
+    pub fn preset(&self) -> MetadataPreset {
+        MetadataPreset {
+            file_name: self.file_name.clone(),
+            ..MetadataPreset::from(&self.file)
+        }
+    }
+
+    /// Returns `true` if the preset was created by this tool.
+    ///
+    /// Presets using the default `MacDive-<uuid>` file name always qualify.
+    /// Since file names are configurable, any other preset qualifies only if it
+    /// carries the [`PRESET_GENERATOR`] marker. The content of a preset is never
+    /// used to guess, so hand-made location presets are left alone.
+    pub fn is_generated(&self) -> bool {
+        let default_name = self
+            .entry
+            .file_name()
+            .to_str()
+            .map(|name| name.starts_with(PRESET_FILE_PREFIX))
+            .unwrap_or(false);
+
+        default_name || self.file.generator.as_deref() == Some(PRESET_GENERATOR)
+    }
+}
+
+/// Convert a relative preset file name (using `/` separators, without
+/// extension) into a path below `root`.
+fn preset_path(root: &Path, file_name: &str) -> PathBuf {
+    // Not using `set_extension` since site names frequently contain dots.
+    root.join(format!("{file_name}.{PRESET_FILE_EXTENSION}"))
+        .components()
+        .collect()
+}
 
-/// Compiled regex for extracting the `id` UUID field from an existing `.lrtemplate` file.
-///
-/// Matches lines of the form:
-/// ```text
-///     id = "xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx",
-/// ```
-/// and captures the UUID into the named group `uuid`.
-static LRTEMPLATE_ID_RE: LazyLock<Regex> = LazyLock::new(|| {
-    RegexBuilder::new(concat!(
-        r#"^\s+id\s=\s"(?P<uuid>[0-9a-f]{8}-[0-9a-f]{4}-"#,
-        r#"[1-5][0-9a-f]{3}-[89AB][0-9a-f]{3}-[0-9a-f]{12})",$"#
-    ))
-    .multi_line(true)
-    .case_insensitive(true)
-    .build()
-    // SAFETY: The regex literal is verified at compile time via tests; panic here
-    // is an unrecoverable programmer error.
-    .expect("LRTEMPLATE_ID_RE is a valid regex")
-});
-
-/// Walk `path` recursively and return a map from UUID to [`DirEntry`] for every
-/// `.lrtemplate` file found.
+/// The inverse of [`preset_path`]: the file name of `file` relative to `root`,
+/// using `/` separators and without extension.
+fn preset_name(root: &Path, file: &Path) -> String {
+    let file_name = file
+        .strip_prefix(root)
+        .unwrap_or(file)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().to_string())
+        .collect::<Vec<String>>()
+        .join("/");
+    file_name
+        .strip_suffix(&format!(".{PRESET_FILE_EXTENSION}"))
+        .unwrap_or(&file_name)
+        .to_string()
+}
+
+/// Walk `path` recursively and return a map from UUID to [`ExistingPreset`] for
+/// every `.lrtemplate` file found.
 ///
 /// Only files ending with `.lrtemplate` are considered; all other entries are
-/// skipped.  Entries whose UUIDs cannot be extracted or parsed are returned as
-/// errors.
+/// skipped. Files that cannot be parsed or carry no valid `id` (for example
+/// hand-made presets in an unexpected format) are logged and skipped. If
+/// several files share an `id`, e.g. a copied preset, the first one in path
+/// order is used and the others are logged, since they are neither updated
+/// nor pruned.
 ///
 /// # Arguments
 ///
@@ -44,10 +94,8 @@ static LRTEMPLATE_ID_RE: LazyLock<Regex> = LazyLock::new(|| {
 ///
 /// # Errors
 ///
-/// Returns [`Error::Io`] if a file cannot be read, [`Error::LightroomParsing`]
-/// if the UUID field is missing, or [`Error::InvalidUuid`] if the UUID string
-/// cannot be parsed.
-pub fn read_existing_presets(path: &Path) -> Result<HashMap<Uuid, DirEntry>> {
+/// Returns [`Error::Io`] if a file cannot be read.
+pub fn read_existing_presets(path: &Path) -> Result<HashMap<Uuid, ExistingPreset>> {
     /// Returns `true` for directory entries and `.lrtemplate` files so that
     /// `WalkDir::filter_entry` prunes everything else early.
     fn is_dir_or_lrtemplate(entry: &DirEntry) -> bool {
@@ -62,24 +110,52 @@ pub fn read_existing_presets(path: &Path) -> Result<HashMap<Uuid, DirEntry>> {
             .unwrap_or(false)
     }
 
-    WalkDir::new(path)
+    let presets = WalkDir::new(path)
+        .sort_by_file_name()
         .into_iter()
         .filter_entry(is_dir_or_lrtemplate)
         .filter_map(|e| e.ok())
         .filter(|entry| !entry.path().is_dir())
-        .map(|entry| {
-            let content = std::fs::read_to_string(entry.path())?;
-            let uuid = LRTEMPLATE_ID_RE
-                .captures(&content)
-                .and_then(|v| v.name("uuid"))
-                .ok_or(Error::LightroomParsing)
-                .and_then(|v| {
-                    Uuid::parse_str(&v.as_str().to_lowercase()).map_err(Error::InvalidUuid)
-                })?;
-
-            Ok((uuid, entry))
+        .filter_map(|entry| {
+            let content = match std::fs::read_to_string(entry.path()) {
+                Ok(content) => content,
+                Err(e) => return Some(Err(Error::Io(e))),
+            };
+            let file_name = preset_name(path, entry.path());
+            match content.parse::<PresetFile>() {
+                Ok(file) => Some(Ok((
+                    file.id,
+                    ExistingPreset {
+                        entry,
+                        file_name,
+                        file,
+                    },
+                ))),
+                Err(e) => {
+                    tracing::warn!(
+                        path = entry.path().display().to_string(),
+                        "Skipping unreadable Lightroom preset: {e}"
+                    );
+                    None
+                }
+            }
         })
-        .collect::<Result<HashMap<Uuid, DirEntry>>>()
+        .collect::<Result<Vec<(Uuid, ExistingPreset)>>>()?;
+
+    let mut existing = HashMap::<Uuid, ExistingPreset>::new();
+    for (id, preset) in presets {
+        match existing.get(&id) {
+            Some(kept) => tracing::warn!(
+                kept = kept.entry.path().display().to_string(),
+                ignored = preset.entry.path().display().to_string(),
+                "Lightroom presets share the id {id}, remove one of them"
+            ),
+            None => {
+                existing.insert(id, preset);
+            }
+        }
+    }
+    Ok(existing)
 }
 
 /// Write a single Lightroom metadata preset to `path`.
@@ -100,17 +176,128 @@ pub fn write_preset(path: &Path, content: &str) -> Result<()> {
     Ok(())
 }
 
-/// Render and write all `presets` into `path`, reusing existing filenames where
-/// possible.
+/// Decide the file each preset is written to.
 ///
-/// If a preset's UUID already appears in `existing`, its current filename is
-/// reused; otherwise a new file named `MacDive-<uuid>.lrtemplate` is created.
+/// Presets whose file names collide, ignoring case like the default macOS file
+/// system does, get a unique `-<uuid>` suffix. Files of other sites already on
+/// disk always keep their name, since `presets` may only hold the presets that
+/// changed. Among the presets the one whose file already has the name keeps
+/// it, otherwise the one with the lowest UUID does, so that names stay stable
+/// between runs.
+fn preset_targets(
+    path: &Path,
+    presets: &[MetadataPreset],
+    existing: &HashMap<Uuid, ExistingPreset>,
+) -> Vec<PathBuf> {
+    let fold = |target: &Path| target.to_string_lossy().to_lowercase();
+    let suffixed = |preset: &MetadataPreset| {
+        let target = preset_path(path, &format!("{}-{}", preset.file_name, preset.id));
+        // Presets that already carry the suffix are not worth a warning.
+        if existing
+            .get(&preset.id)
+            .is_none_or(|current| fold(current.entry.path()) != fold(&target))
+        {
+            tracing::warn!(
+                target = target.display().to_string(),
+                "Preset file name is already taken, adding the site's UUID"
+            );
+        }
+        target
+    };
+    let mut targets: Vec<PathBuf> = presets
+        .iter()
+        .map(|preset| preset_path(path, &preset.file_name))
+        .collect();
+
+    let owners: HashMap<String, Uuid> = existing
+        .iter()
+        .map(|(id, current)| (fold(current.entry.path()), *id))
+        .collect();
+    for (preset, target) in presets.iter().zip(targets.iter_mut()) {
+        if owners
+            .get(&fold(target))
+            .is_some_and(|owner| *owner != preset.id)
+        {
+            *target = suffixed(preset);
+        }
+    }
+
+    let mut claims = HashMap::<String, Vec<usize>>::new();
+    for (index, target) in targets.iter().enumerate() {
+        claims.entry(fold(target)).or_default().push(index);
+    }
+    for (name, mut indices) in claims.into_iter().filter(|(_, v)| v.len() > 1) {
+        indices.sort_by_key(|index| {
+            let preset = &presets[*index];
+            let in_place = existing
+                .get(&preset.id)
+                .is_some_and(|current| fold(current.entry.path()) == name);
+            (!in_place, preset.id)
+        });
+        for index in &indices[1..] {
+            targets[*index] = suffixed(&presets[*index]);
+        }
+    }
+
+    targets
+}
+
+/// Set the file name of each preset to the one it is written to.
+///
+/// Presets whose file name is already taken get the `-<uuid>` suffix chosen
+/// by [`write_presets`], so that comparing them with the files on disk does
+/// not report the suffix as a change. `presets` should hold all presets, so
+/// that the names stay the same no matter which presets end up being written.
+///
+/// # Arguments
+///
+/// * `path` - Directory into which preset files are written.
+/// * `presets` - The presets whose file names are resolved.
+/// * `existing` - Map of known UUID → [`ExistingPreset`] pairs (from [`read_existing_presets`]).
+pub fn resolve_file_names(
+    path: &Path,
+    presets: &mut [MetadataPreset],
+    existing: &HashMap<Uuid, ExistingPreset>,
+) {
+    let targets = preset_targets(path, presets, existing);
+    for (preset, target) in presets.iter_mut().zip(targets) {
+        preset.file_name = preset_name(path, &target);
+    }
+}
+
+/// Returns `true` if `a` and `b` refer to the same file, e.g. because they only
+/// differ in case on a case-insensitive file system.
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt as _;
+        match (std::fs::metadata(a), std::fs::metadata(b)) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        a.to_string_lossy().to_lowercase() == b.to_string_lossy().to_lowercase()
+    }
+}
+
+/// Render and write all `presets` into `path`, moving existing files to
+/// their configured location where necessary.
+///
+/// Each preset is written to `<path>/<file_name>.lrtemplate`, creating
+/// subfolders as needed. Presets whose file names collide get the UUID of
+/// their site appended, so that no preset overwrites another. If a preset's
+/// UUID already appears in `existing` under a different name, the old file is
+/// renamed rather than duplicated; the rename is skipped (keeping the old name)
+/// if another file already occupies the target path. Folders emptied by a
+/// rename are removed.
 ///
 /// # Arguments
 ///
 /// * `path` - Directory into which preset files are written.
 /// * `presets` - Slice of [`MetadataPreset`] values to render and write.
-/// * `existing` - Map of known UUID → [`DirEntry`] pairs (from [`read_existing_presets`]).
+/// * `existing` - Map of known UUID → [`ExistingPreset`] pairs (from [`read_existing_presets`]).
 ///
 /// # Errors
 ///
@@ -118,21 +305,340 @@ pub fn write_preset(path: &Path, content: &str) -> Result<()> {
 pub fn write_presets(
     path: &Path,
     presets: &[MetadataPreset],
-    existing: &HashMap<Uuid, DirEntry>,
+    existing: &HashMap<Uuid, ExistingPreset>,
 ) -> Result<()> {
     use askama::Template as _;
 
-    for preset in presets {
+    let targets = preset_targets(path, presets, existing);
+    for (preset, mut target) in presets.iter().zip(targets) {
         let content = preset
             .render()
             .map_err(|e| Error::Template(e.to_string()))?;
-        let filename = existing
-            .get(&preset.id)
-            .and_then(|v| v.file_name().to_str().map(|v| v.to_string()))
-            .unwrap_or_else(|| format!("MacDive-{}.lrtemplate", &preset.id));
 
-        write_preset(path.join(filename).as_path(), &content)?;
+        if let Some(current) = existing.get(&preset.id)
+            && current.entry.path() != target
+        {
+            // A rename that only changes case finds the file itself in place.
+            if target.exists() && !is_same_file(current.entry.path(), &target) {
+                tracing::warn!(
+                    target = target.display().to_string(),
+                    "Not renaming preset, target file already exists"
+                );
+                target = current.entry.path().to_path_buf();
+            } else {
+                if let Some(parent) = target.parent() {
+                    crate::util::fs::create_dir(parent)?;
+                }
+                std::fs::rename(current.entry.path(), &target)?;
+                remove_empty_folders(current.entry.path(), path)?;
+            }
+        }
+
+        if let Some(parent) = target.parent() {
+            crate::util::fs::create_dir(parent)?;
+        }
+        write_preset(&target, &content)?;
+    }
+
+    Ok(())
+}
+
+/// Remove the now-empty parent folders of `file`, stopping at `root`.
+fn remove_empty_folders(file: &Path, root: &Path) -> Result<()> {
+    for folder in file.ancestors().skip(1) {
+        if folder == root || !folder.starts_with(root) {
+            break;
+        }
+        if std::fs::read_dir(folder)?.next().is_some() {
+            break;
+        }
+        std::fs::remove_dir(folder)?;
+    }
+    Ok(())
+}
+
+/// Find generated presets whose dive site no longer exists.
+///
+/// A preset is considered orphaned when it was created by this tool (see
+/// [`ExistingPreset::is_generated`]) and its UUID is not contained in `sites`. The
+/// result is sorted by file path so that listings are stable between runs.
+///
+/// # Arguments
+///
+/// * `existing` - Map of known UUID → [`ExistingPreset`] pairs (from [`read_existing_presets`]).
+/// * `sites` - UUIDs of all dive sites currently present in MacDive.
+pub fn orphaned_presets<'a>(
+    existing: &'a HashMap<Uuid, ExistingPreset>,
+    sites: &HashSet<Uuid>,
+) -> Vec<(&'a Uuid, &'a ExistingPreset)> {
+    let mut orphans: Vec<(&Uuid, &ExistingPreset)> = existing
+        .iter()
+        .filter(|(uuid, existing)| !sites.contains(uuid) && existing.is_generated())
+        .collect();
+    orphans.sort_by(|(_, lhs), (_, rhs)| lhs.entry.path().cmp(rhs.entry.path()));
+    orphans
+}
+
+/// Resolve `path` to an absolute path without symbolic links.
+///
+/// Only the part of the path that already exists can be resolved, the rest
+/// is appended as given.
+fn resolve_path(path: &Path) -> Result<PathBuf> {
+    let path = std::path::absolute(path)?;
+    for ancestor in path.ancestors() {
+        if let Ok(resolved) = ancestor.canonicalize() {
+            return Ok(resolved.join(path.strip_prefix(ancestor).unwrap_or(Path::new(""))));
+        }
+    }
+    Ok(path)
+}
+
+/// Make sure `quarantine` is usable as a quarantine folder for the presets in
+/// `presets`.
+///
+/// A quarantine folder inside the presets folder would be read back in by
+/// [`read_existing_presets`] and Lightroom, so it is rejected.
+///
+/// # Errors
+///
+/// Returns [`Error::Config`] if `quarantine` is the presets folder or inside
+/// it, or [`Error::Io`] if the current directory cannot be determined.
+pub fn check_quarantine(presets: &Path, quarantine: &Path) -> Result<()> {
+    if resolve_path(quarantine)?.starts_with(resolve_path(presets)?) {
+        return Err(Error::Config(format!(
+            "the quarantine folder {} must not be inside the presets folder {}",
+            quarantine.display(),
+            presets.display()
+        )));
+    }
+    Ok(())
+}
+
+/// Remove a preset file, optionally moving it into a quarantine folder instead.
+///
+/// When `quarantine` is given the file is moved into that directory at its
+/// path relative to the presets folder, creating subfolders as needed. A
+/// numeric suffix is added if a file of that name was quarantined before, so
+/// nothing is overwritten. Moving falls back to copy-and-delete when a plain
+/// rename is not possible (e.g. across volumes). See [`check_quarantine`] for
+/// where the quarantine folder may be.
+///
+/// # Arguments
+///
+/// * `preset` - The preset file to remove.
+/// * `quarantine` - Optional directory to move the file into instead of deleting it.
+///
+/// # Errors
+///
+/// Returns [`Error::Io`] if the file cannot be moved or deleted.
+pub fn remove_preset(preset: &ExistingPreset, quarantine: Option<&Path>) -> Result<()> {
+    let path = preset.entry.path();
+    match quarantine {
+        Some(folder) => {
+            let mut target = preset_path(folder, &preset.file_name);
+            let mut counter = 1;
+            while target.exists() {
+                target = preset_path(folder, &format!("{}-{counter}", preset.file_name));
+                counter += 1;
+            }
+            if let Some(parent) = target.parent() {
+                crate::util::fs::create_dir(parent)?;
+            }
+            if std::fs::rename(path, &target).is_err() {
+                std::fs::copy(path, &target)?;
+                std::fs::remove_file(path)?;
+            }
+        }
+        None => std::fs::remove_file(path)?,
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use askama::Template as _;
+
+    use super::*;
+
+    /// A fresh, empty directory below the system's temporary folder.
+    fn temp_dir(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("macdive-toolbox-{name}-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_is_generated() {
+        let root = temp_dir("generated");
+        let generated = MetadataPreset {
+            id: Uuid::new_v4(),
+            location: String::from("Salt Pier"),
+            ..Default::default()
+        };
+        write_preset(
+            &root.join("Bonaire - Salt Pier.lrtemplate"),
+            &generated.render().unwrap(),
+        )
+        .unwrap();
+        // A hand-made location preset with the same fields, but no marker.
+        let hand_made = Uuid::new_v4();
+        write_preset(
+            &root.join("Home Reef.lrtemplate"),
+            &format!(
+                "s = {{\n\tid = \"{hand_made}\",\n\ttitle = \"Home Reef\",\n\tvalue = {{\n\t\t[\"com.adobe.GPS\"] = \"12°9'0\\\" N 68°16'48\\\" W\",\n\t\t[\"com.adobe.location\"] = \"Home Reef\",\n\t}},\n}}\n"
+            ),
+        )
+        .unwrap();
+
+        let existing = read_existing_presets(&root).unwrap();
+        assert!(existing[&generated.id].is_generated());
+        assert!(!existing[&hand_made].is_generated());
+        assert_eq!(
+            vec![&generated.id],
+            orphaned_presets(&existing, &HashSet::new())
+                .into_iter()
+                .map(|(uuid, _)| uuid)
+                .collect::<Vec<_>>()
+        );
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_write_colliding_presets() {
+        let root = temp_dir("collisions");
+        let preset = |file_name: &str| MetadataPreset {
+            id: Uuid::new_v4(),
+            file_name: String::from(file_name),
+            ..Default::default()
+        };
+        let presets = [
+            preset("Bonaire/Salt Pier"),
+            preset("Bonaire/Salt Pier"),
+            preset("bonaire/salt pier"),
+        ];
+        write_presets(&root, &presets, &HashMap::new()).unwrap();
+        let existing = read_existing_presets(&root).unwrap();
+        assert_eq!(3, existing.len());
+        let keeper = presets.iter().map(|p| p.id).min().unwrap();
+        for preset in &presets {
+            let file_name = &existing[&preset.id].file_name;
+            if preset.id == keeper {
+                assert_eq!(&preset.file_name, file_name);
+            } else {
+                assert_eq!(&format!("{}-{}", preset.file_name, preset.id), file_name);
+            }
+        }
+
+        // Renaming only the case moves the file instead of keeping the old name.
+        let renamed = MetadataPreset {
+            file_name: String::from("BONAIRE/SALT PIER"),
+            ..presets.iter().find(|p| p.id == keeper).unwrap().clone()
+        };
+        write_presets(&root, std::slice::from_ref(&renamed), &existing).unwrap();
+        let existing = read_existing_presets(&root).unwrap();
+        assert_eq!("BONAIRE/SALT PIER", existing[&keeper].file_name);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_new_preset_colliding_with_existing_file() {
+        let root = temp_dir("existing-collision");
+        let preset = |file_name: &str| MetadataPreset {
+            id: Uuid::new_v4(),
+            file_name: String::from(file_name),
+            ..Default::default()
+        };
+        let (old, new) = (preset("Bonaire/Salt Pier"), preset("Bonaire/Salt Pier"));
+        write_presets(&root, std::slice::from_ref(&old), &HashMap::new()).unwrap();
+
+        let existing = read_existing_presets(&root).unwrap();
+        write_presets(&root, std::slice::from_ref(&new), &existing).unwrap();
+        let existing = read_existing_presets(&root).unwrap();
+        assert_eq!(2, existing.len());
+        assert_eq!("Bonaire/Salt Pier", existing[&old.id].file_name);
+        assert_eq!(
+            format!("Bonaire/Salt Pier-{}", new.id),
+            existing[&new.id].file_name
+        );
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_resolve_file_names() {
+        let root = temp_dir("resolve");
+        let preset = |file_name: &str| MetadataPreset {
+            id: Uuid::new_v4(),
+            file_name: String::from(file_name),
+            ..Default::default()
+        };
+        let mut presets = [preset("Bonaire/Salt Pier"), preset("Bonaire/Salt Pier")];
+        presets.sort_by_key(|preset| preset.id);
+        write_presets(&root, &presets, &HashMap::new()).unwrap();
+
+        let existing = read_existing_presets(&root).unwrap();
+        let mut resolved = presets.clone();
+        resolve_file_names(&root, &mut resolved, &existing);
+        for preset in &resolved {
+            assert_eq!(existing[&preset.id].file_name, preset.file_name);
+        }
+        assert_eq!(
+            format!("Bonaire/Salt Pier-{}", presets[1].id),
+            resolved[1].file_name
+        );
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_duplicate_ids() {
+        let root = temp_dir("duplicates");
+        let preset = MetadataPreset {
+            id: Uuid::new_v4(),
+            location: String::from("Salt Pier"),
+            ..Default::default()
+        };
+        let content = preset.render().unwrap();
+        write_preset(&root.join("MacDive-b.lrtemplate"), &content).unwrap();
+        write_preset(&root.join("MacDive-a.lrtemplate"), &content).unwrap();
+
+        let existing = read_existing_presets(&root).unwrap();
+        assert_eq!(1, existing.len());
+        assert_eq!("MacDive-a", existing[&preset.id].file_name);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_quarantine_presets() {
+        let root = temp_dir("quarantine");
+        let (presets, quarantine) = (root.join("presets"), root.join("quarantine"));
+        let preset = |file_name: &str| MetadataPreset {
+            id: Uuid::new_v4(),
+            file_name: String::from(file_name),
+            ..Default::default()
+        };
+        let orphans = [preset("Bonaire/Salt Pier"), preset("Curacao/Salt Pier")];
+        write_presets(&presets, &orphans, &HashMap::new()).unwrap();
+        // A file quarantined by an earlier run.
+        crate::util::fs::create_dir(&quarantine.join("Curacao")).unwrap();
+        std::fs::write(preset_path(&quarantine, "Curacao/Salt Pier"), "").unwrap();
+
+        for existing in read_existing_presets(&presets).unwrap().values() {
+            remove_preset(existing, Some(&quarantine)).unwrap();
+        }
+        assert!(preset_path(&quarantine, "Bonaire/Salt Pier").exists());
+        assert!(preset_path(&quarantine, "Curacao/Salt Pier-1").exists());
+        assert!(read_existing_presets(&presets).unwrap().is_empty());
+
+        assert!(check_quarantine(&presets, &quarantine).is_ok());
+        assert!(check_quarantine(&presets, &presets).is_err());
+        assert!(check_quarantine(&presets, &presets.join("Quarantine")).is_err());
+        assert!(check_quarantine(&presets, &presets.join("Bonaire/../Old")).is_err());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
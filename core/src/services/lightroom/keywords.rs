@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+
+/// A single keyword on a path through the keyword hierarchy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keyword {
+    /// The keyword as shown in Lightroom.
+    pub name: String,
+    /// Alternative names Lightroom also matches and exports the keyword for.
+    pub synonyms: Vec<String>,
+}
+
+impl Keyword {
+    /// Create a keyword without synonyms.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            synonyms: vec![],
+        }
+    }
+}
+
+/// A hierarchical keyword list in Lightroom's keyword import format.
+///
+/// Each keyword is written on its own line, indented with one tab per level.
+/// Synonyms follow their keyword in braces, indented one level deeper
+/// (shown with spaces instead of tabs):
+///
+/// ```text
+/// Chordata
+///     {Chordates}
+///     Elasmobranchii
+///         {Sharks and Rays}
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct KeywordTree {
+    children: BTreeMap<String, KeywordNode>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct KeywordNode {
+    synonyms: Vec<String>,
+    children: BTreeMap<String, KeywordNode>,
+}
+
+/// Strip characters that have a special meaning in keyword import files.
+fn clean(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| !c.is_control() && !matches!(c, '{' | '}' | '[' | ']'))
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+impl KeywordTree {
+    /// Add a path of keywords, from the top level down to the leaf.
+    ///
+    /// Keywords that already exist are merged and gain any new synonyms.
+    /// Synonyms equal to the keyword itself are dropped.
+    pub fn insert(&mut self, path: &[Keyword]) {
+        let mut children = &mut self.children;
+        for keyword in path {
+            let name = clean(&keyword.name);
+            if name.is_empty() {
+                continue;
+            }
+
+            let node = children.entry(name.clone()).or_default();
+            for synonym in keyword.synonyms.iter().map(|s| clean(s)) {
+                let duplicate = synonym.eq_ignore_ascii_case(&name)
+                    || node
+                        .synonyms
+                        .iter()
+                        .any(|s| s.eq_ignore_ascii_case(&synonym));
+                if !synonym.is_empty() && !duplicate {
+                    node.synonyms.push(synonym);
+                }
+            }
+            children = &mut node.children;
+        }
+    }
+
+    /// Returns `true` if no keywords have been added.
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// Render the tree in Lightroom's keyword import format.
+    pub fn render(&self) -> String {
+        fn render_children(
+            children: &BTreeMap<String, KeywordNode>,
+            depth: usize,
+            output: &mut String,
+        ) {
+            for (name, node) in children {
+                output.push_str(&"\t".repeat(depth));
+                output.push_str(name);
+                output.push('\n');
+                for synonym in &node.synonyms {
+                    output.push_str(&"\t".repeat(depth + 1));
+                    output.push_str(&format!("{{{synonym}}}\n"));
+                }
+                render_children(&node.children, depth + 1, output);
+            }
+        }
+
+        let mut output = String::new();
+        render_children(&self.children, 0, &mut output);
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_keyword_tree() {
+        let mut tree = KeywordTree::default();
+        tree.insert(&[
+            Keyword {
+                name: String::from("Chordata"),
+                synonyms: vec![String::from("Chordates")],
+            },
+            Keyword::new("Elasmobranchii"),
+            Keyword {
+                name: String::from("Carcharhinus melanopterus"),
+                synonyms: vec![
+                    String::from("Blacktip Reef Shark"),
+                    String::from("blacktip reef shark"),
+                ],
+            },
+        ]);
+        tree.insert(&[
+            Keyword::new("Chordata"),
+            Keyword {
+                name: String::from("Elasmobranchii"),
+                synonyms: vec![String::from("Sharks {and} Rays")],
+            },
+            Keyword::new("Aetobatus narinari"),
+        ]);
+
+        assert_eq!(
+            "Chordata\n\
+             \t{Chordates}\n\
+             \tElasmobranchii\n\
+             \t\t{Sharks and Rays}\n\
+             \t\tAetobatus narinari\n\
+             \t\tCarcharhinus melanopterus\n\
+             \t\t\t{Blacktip Reef Shark}\n",
+            tree.render()
+        );
+    }
+}
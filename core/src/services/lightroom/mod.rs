@@ -17,8 +17,19 @@
 //! write_presets(&output_dir, &presets, &existing)?;
 //! ```
 
+mod audit;
+mod diff;
 mod io;
+mod keywords;
 mod preset;
+mod template;
 
-pub use io::{read_existing_presets, write_preset, write_presets};
+pub use audit::{AuditEntry, LocationIssue, audit_photo};
+pub use diff::{FieldChange, PresetDiff, PresetStatus, diff_preset};
+pub use io::{
+    ExistingPreset, check_quarantine, orphaned_presets, read_existing_presets, remove_preset,
+    resolve_file_names, write_preset, write_presets,
+};
+pub use keywords::{Keyword, KeywordTree};
 pub use preset::MetadataPreset;
+pub use template::{ADOBE_KEY_PREFIX, PRESET_GENERATOR, PresetFile};
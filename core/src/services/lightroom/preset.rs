@@ -1,11 +1,12 @@
 use std::convert::TryFrom;
 
 use askama::Template;
-use google_maps::LatLng;
 use uuid::Uuid;
 
-use crate::domain::{DecimalToDms, DiveSite};
+use crate::domain::{DiveSite, LightroomConfig};
 use crate::error::{Error, Result};
+use crate::util::coordinates::format_coordinates;
+use crate::util::format::{format_placeholders, sanitize_file_name};
 
 /// Custom Askama template filters for Lightroom preset rendering.
 pub(super) mod filters {
@@ -40,16 +41,26 @@ pub(super) mod filters {
 ///
 /// The rendered output is a `.lrtemplate` file in Lua table syntax that carries
 /// IPTC/XMP location metadata for a dive site.
-#[derive(Template)]
+#[derive(Debug, Clone, Template)]
 #[template(path = "metadata_preset.lrtemplate", escape = "none")]
 pub struct MetadataPreset {
     /// The unique identifier for this preset (from the MacDive dive site UUID).
     pub id: Uuid,
     /// GPS coordinates in degrees-minutes-seconds notation (e.g. `37°46'10"N 122°28'36"W`).
     pub gps: String,
+    /// GPS altitude in meters, empty if unknown.
+    pub altitude: String,
     /// The display title shown in Lightroom's preset list.
     pub title: String,
+    /// The internal (non-localized) name of the preset.
+    pub internal_name: String,
+    /// Path of the preset file relative to the metadata presets folder,
+    /// using `/` as separator and without the `.lrtemplate` extension.
+    pub file_name: String,
     /// IPTC sublocation — the name of the specific dive site.
+    ///
+    /// Together with city, state and country this is also written as the
+    /// IPTC Extension *Location Shown*, since dive photos show the dive site.
     pub location: String,
     /// IPTC city — the nearest locality/city.
     pub city: String,
@@ -61,62 +72,259 @@ pub struct MetadataPreset {
     pub country: String,
     /// ISO 3166-1 alpha-2 country code.
     pub iso_country_code: String,
-    /// IPTC scene code (numeric identifier for the type of scene).
-    pub scene: u64,
+    /// IPTC creator, empty to leave the field untouched.
+    pub creator: String,
+    /// IPTC copyright notice, empty to leave the field untouched.
+    pub copyright: String,
+    /// IPTC keywords, including the body of water if configured.
+    pub keywords: Vec<String>,
+    /// Six digit IPTC scene code (e.g. `011300` for under-water), empty if unset.
+    pub scene: String,
     /// Template format version (always 0 for MacDive exports).
     #[allow(dead_code)]
     pub version: u64,
 }
 
-impl TryFrom<DiveSite> for MetadataPreset {
-    type Error = Error;
-
-    /// Convert a [`DiveSite`] domain object into a [`MetadataPreset`] ready for rendering.
+impl MetadataPreset {
+    /// Build a [`MetadataPreset`] for `site` using the configured naming templates.
+    ///
+    /// Each `/`-separated component of the file name template is rendered on its
+    /// own and the substituted values are sanitized, so that site names containing
+    /// slashes or other reserved characters cannot escape the presets folder.
     ///
     /// # Errors
     ///
     /// Returns [`Error::InvalidLatitude`] or [`Error::InvalidLongitude`] if the site's
-    /// coordinates cannot be converted to a [`LatLng`], or [`Error::InvalidGps`] if the
-    /// DMS conversion fails.
-    fn try_from(site: DiveSite) -> Result<Self> {
-        let latlng: LatLng = site.clone().try_into()?;
-        let region = site.region.unwrap_or_else(|| String::from("Unknown"));
+    /// coordinates are out of range, [`Error::InvalidCoordinates`] if they cannot be
+    /// rendered in the configured GPS format, or [`Error::Config`] if a format string
+    /// or the scene code is invalid.
+    pub fn from_site(site: DiveSite, config: &LightroomConfig) -> Result<Self> {
+        let gps = format_coordinates(site.latitude, site.longitude, config.gps_format)?;
+
+        let scene = config.scene.clone().unwrap_or_default();
+        if !scene.is_empty() && (scene.len() != 6 || !scene.bytes().all(|b| b.is_ascii_digit())) {
+            return Err(Error::Config(format!(
+                "IPTC scene code `{scene}` is not a six digit number"
+            )));
+        }
+
+        let title = format_placeholders(&config.title, |key| site.placeholder(key))?;
+        let internal_name = match &config.internal_name {
+            Some(template) => format_placeholders(template, |key| site.placeholder(key))?,
+            None => title.clone(),
+        };
+        let file_name = config
+            .file_name
+            .split('/')
+            .map(|component| {
+                format_placeholders(component, |key| {
+                    site.placeholder(key).map(|v| sanitize_file_name(&v))
+                })
+                .map(|v| sanitize_file_name(&v))
+            })
+            .filter(|component| !matches!(component.as_deref(), Ok("")))
+            .collect::<Result<Vec<String>>>()?
+            .join("/");
+        if file_name.is_empty() {
+            return Err(Error::Config(format!(
+                "preset file name template `{}` renders to an empty name",
+                config.file_name
+            )));
+        }
+
+        let mut keywords = Vec::with_capacity(config.keywords.len());
+        for template in &config.keywords {
+            // Skip keywords that reference a value the site does not have,
+            // rather than tagging photos with "Unknown".
+            let mut missing = false;
+            let keyword = format_placeholders(template, |key| {
+                let value = site.placeholder_value(key)?;
+                missing |= value.is_none();
+                Some(value.unwrap_or_default())
+            })?;
+            let keyword = keyword.trim();
+            if !missing
+                && !keyword.is_empty()
+                && !keywords
+                    .iter()
+                    .any(|k: &String| k.to_lowercase() == keyword.to_lowercase())
+            {
+                keywords.push(keyword.to_string());
+            }
+        }
 
         Ok(Self {
             id: site.uuid,
-            gps: latlng.to_dms()?,
-            title: format!(
-                "[Location] {region}: {name}",
-                region = &region,
-                name = &site.name
-            ),
+            gps,
+            // MacDive stores an unknown altitude as zero.
+            altitude: if site.altitude == 0.0 {
+                String::new()
+            } else {
+                site.altitude.to_string()
+            },
+            title,
+            internal_name,
+            file_name,
             city: site.locality.unwrap_or_default(),
-            region,
+            region: site.region.unwrap_or_else(|| String::from("Unknown")),
             country: site.country,
             iso_country_code: site.iso_country_code,
             location: site.name,
             state: site.state.unwrap_or_default(),
-            scene: Default::default(),
+            creator: config.creator.clone().unwrap_or_default(),
+            copyright: config.copyright.clone().unwrap_or_default(),
+            keywords,
+            scene,
             version: 0,
         })
     }
 }
 
+impl TryFrom<DiveSite> for MetadataPreset {
+    type Error = Error;
+
+    /// Convert a [`DiveSite`] domain object into a [`MetadataPreset`] using the
+    /// default [`LightroomConfig`] naming templates.
+    ///
+    /// # Errors
+    ///
+    /// See [`MetadataPreset::from_site`].
+    fn try_from(site: DiveSite) -> Result<Self> {
+        Self::from_site(site, &LightroomConfig::default())
+    }
+}
+
 impl Default for MetadataPreset {
     fn default() -> Self {
         Self {
             id: Uuid::nil(),
             // Null Island as a safe zero-coordinate default.
             gps: r#"0°00'00.0"N 0°00'00.0"E"#.to_string(),
+            altitude: String::new(),
             title: String::new(),
+            internal_name: String::new(),
+            file_name: String::new(),
             city: String::new(),
             region: String::new(),
             country: String::new(),
             iso_country_code: String::new(),
             location: String::new(),
             state: String::new(),
-            scene: 20000917,
+            creator: String::new(),
+            copyright: String::new(),
+            keywords: vec![],
+            scene: String::new(),
             version: 0,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn site() -> DiveSite {
+        DiveSite {
+            country: String::from("Bonaire"),
+            iso_country_code: String::from("bq"),
+            state: Some(String::from("Bonaire")),
+            locality: Some(String::from("Kralendijk")),
+            name: String::from("Salt Pier / South"),
+            latitude: 12.083,
+            longitude: -68.283,
+            body_of_water: Some(String::from("Caribbean Sea")),
+            site_id: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_default_naming() {
+        let preset = MetadataPreset::try_from(site()).unwrap();
+        assert_eq!("[Location] Unknown: Salt Pier / South", preset.title);
+        assert_eq!(preset.title, preset.internal_name);
+        assert_eq!(
+            "MacDive-00000000-0000-0000-0000-000000000000",
+            preset.file_name
+        );
+    }
+
+    #[test]
+    fn test_configured_naming() {
+        let config = LightroomConfig {
+            title: String::from("{iso_country_code} - {locality} - {name}"),
+            internal_name: Some(String::from("{uuid}")),
+            file_name: String::from("{country}/{state}/{body_of_water}: {name}"),
+            ..Default::default()
+        };
+        let preset = MetadataPreset::from_site(site(), &config).unwrap();
+        assert_eq!("BQ - Kralendijk - Salt Pier / South", preset.title);
+        assert_eq!(Uuid::nil().to_string(), preset.internal_name);
+        assert_eq!(
+            "Bonaire/Bonaire/Caribbean Sea- Salt Pier - South",
+            preset.file_name
+        );
+    }
+
+    #[test]
+    fn test_unknown_placeholder() {
+        let config = LightroomConfig {
+            title: String::from("{nmae}"),
+            ..Default::default()
+        };
+        assert!(matches!(
+            MetadataPreset::from_site(site(), &config),
+            Err(Error::Config(_))
+        ));
+    }
+
+    #[test]
+    fn test_iptc_fields() {
+        let config = LightroomConfig {
+            creator: Some(String::from("Jane Doe")),
+            scene: Some(String::from("011300")),
+            keywords: vec![
+                String::from("Diving"),
+                String::from("{body_of_water}"),
+                String::from("{water_type} Water"),
+                String::from("diving"),
+            ],
+            ..Default::default()
+        };
+        let preset = MetadataPreset::from_site(site(), &config).unwrap();
+        assert_eq!("Jane Doe", preset.creator);
+        assert_eq!("", preset.copyright);
+        assert_eq!("011300", preset.scene);
+        assert_eq!("", preset.altitude);
+        assert_eq!(vec!["Diving", "Caribbean Sea"], preset.keywords);
+
+        let rendered = preset.render().unwrap();
+        assert!(rendered.contains(r#"["com.adobe.keywords"] = "Diving, Caribbean Sea","#));
+        assert!(rendered.contains(r#"["com.adobe.scene"] = "011300","#));
+        assert!(!rendered.contains("com.adobe.copyright"));
+        assert!(!rendered.contains("com.adobe.GPSAltitude"));
+
+        let preset = MetadataPreset::from_site(
+            DiveSite {
+                altitude: 412.0,
+                ..site()
+            },
+            &LightroomConfig::default(),
+        )
+        .unwrap();
+        assert_eq!("412", preset.altitude);
+        assert_eq!("", preset.scene);
+    }
+
+    #[test]
+    fn test_invalid_scene() {
+        let config = LightroomConfig {
+            scene: Some(String::from("11300")),
+            ..Default::default()
+        };
+        assert!(matches!(
+            MetadataPreset::from_site(site(), &config),
+            Err(Error::Config(_))
+        ));
+    }
+}
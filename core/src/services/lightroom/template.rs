@@ -0,0 +1,294 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use uuid::Uuid;
+
+use crate::error::{Error, Result};
+use crate::parsers::lua::{LuaKey, LuaValue, parse_document, render_assignment};
+
+use super::preset::MetadataPreset;
+
+/// Prefix shared by all Lightroom metadata field keys (e.g. `com.adobe.city`).
+pub const ADOBE_KEY_PREFIX: &str = "com.adobe.";
+
+/// Value of the `generator` field that marks presets written by this tool.
+///
+/// Lightroom ignores unknown top-level fields, so the marker survives as long
+/// as the preset is not re-saved from within Lightroom.
+pub const PRESET_GENERATOR: &str = "macdive-toolbox";
+
+/// A parsed Lightroom `.lrtemplate` preset file.
+///
+/// Unlike [`MetadataPreset`], which only models the fields this tool writes,
+/// this type keeps every value found in the file so that presets created by
+/// Lightroom itself (or by hand) can be inspected, edited and written back
+/// without losing information.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PresetFile {
+    /// The unique identifier of the preset.
+    pub id: Uuid,
+    /// The tool that wrote the preset, [`PRESET_GENERATOR`] for presets
+    /// created by this tool.
+    pub generator: Option<String>,
+    /// The internal (non-localized) name of the preset.
+    pub internal_name: Option<String>,
+    /// The display title shown in Lightroom's preset list.
+    pub title: Option<String>,
+    /// The preset type, `Metadata` for metadata presets.
+    pub kind: Option<String>,
+    /// All entries of the `value` table, keyed by field name
+    /// (e.g. `com.adobe.city` or `uuid`).
+    pub values: BTreeMap<LuaKey, LuaValue>,
+    /// Template format version.
+    pub version: Option<f64>,
+    /// All other top-level entries, including known fields whose value has an
+    /// unexpected type.
+    pub extra: BTreeMap<LuaKey, LuaValue>,
+}
+
+impl PresetFile {
+    /// Look up a `com.adobe.*` metadata value by its short name (e.g. `city`).
+    pub fn adobe_value(&self, name: &str) -> Option<&LuaValue> {
+        self.values
+            .get(&LuaKey::from(format!("{ADOBE_KEY_PREFIX}{name}")))
+    }
+
+    /// Look up a `com.adobe.*` metadata value as a string.
+    ///
+    /// Numbers are converted to their textual representation since Lightroom
+    /// is not consistent about quoting numeric fields.
+    pub fn adobe_str(&self, name: &str) -> Option<String> {
+        match self.adobe_value(name)? {
+            LuaValue::String(v) => Some(v.to_owned()),
+            LuaValue::Number(v) => Some(v.to_string()),
+            _ => None,
+        }
+    }
+
+    /// All `com.adobe.*` metadata values, keyed by their short name.
+    pub fn adobe_values(&self) -> impl Iterator<Item = (&str, &LuaValue)> {
+        self.values
+            .iter()
+            .filter_map(|(key, value)| Some((key.as_str()?.strip_prefix(ADOBE_KEY_PREFIX)?, value)))
+    }
+
+    /// Render the preset back into `.lrtemplate` syntax.
+    pub fn render(&self) -> String {
+        let mut root = self.extra.clone();
+        if let Some(generator) = &self.generator {
+            root.insert(
+                LuaKey::from("generator"),
+                LuaValue::String(generator.to_owned()),
+            );
+        }
+        root.insert(
+            LuaKey::from("id"),
+            LuaValue::String(self.id.to_string().to_uppercase()),
+        );
+        if let Some(internal_name) = &self.internal_name {
+            root.insert(
+                LuaKey::from("internalName"),
+                LuaValue::String(internal_name.to_owned()),
+            );
+        }
+        if let Some(title) = &self.title {
+            root.insert(LuaKey::from("title"), LuaValue::String(title.to_owned()));
+        }
+        if let Some(kind) = &self.kind {
+            root.insert(LuaKey::from("type"), LuaValue::String(kind.to_owned()));
+        }
+        root.insert(LuaKey::from("value"), LuaValue::Table(self.values.clone()));
+        if let Some(version) = self.version {
+            root.insert(LuaKey::from("version"), LuaValue::Number(version));
+        }
+
+        render_assignment("s", &LuaValue::Table(root))
+    }
+}
+
+impl FromStr for PresetFile {
+    type Err = Error;
+
+    /// Parse the content of a `.lrtemplate` file.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::LightroomParsing`] if the content is not a Lua table or
+    /// has no `id` field, or [`Error::InvalidUuid`] if the `id` is not a UUID.
+    fn from_str(content: &str) -> Result<Self> {
+        let (_, document) = parse_document(content)?;
+        let LuaValue::Table(mut root) = document else {
+            return Err(Error::LightroomParsing(
+                "preset is not a Lua table".to_string(),
+            ));
+        };
+
+        // Values of an unexpected type are left in `root` and end up in `extra`.
+        let mut take = |key: &str, expected: fn(&LuaValue) -> bool| {
+            let key = LuaKey::from(key);
+            match root.get(&key) {
+                Some(value) if expected(value) => root.remove(&key),
+                _ => None,
+            }
+        };
+        let mut take_string = |key: &str| match take(key, |v| matches!(v, LuaValue::String(_))) {
+            Some(LuaValue::String(v)) => Some(v),
+            _ => None,
+        };
+
+        let id = take_string("id")
+            .ok_or_else(|| Error::LightroomParsing("preset has no `id` field".to_string()))
+            .and_then(|v| Uuid::parse_str(&v.to_lowercase()).map_err(Error::InvalidUuid))?;
+        let generator = take_string("generator");
+        let internal_name = take_string("internalName");
+        let title = take_string("title");
+        let kind = take_string("type");
+
+        let version = match take("version", |v| matches!(v, LuaValue::Number(_))) {
+            Some(LuaValue::Number(v)) => Some(v),
+            _ => None,
+        };
+        let values = match take("value", |v| matches!(v, LuaValue::Table(_))) {
+            Some(LuaValue::Table(v)) => v,
+            _ => BTreeMap::new(),
+        };
+
+        Ok(Self {
+            id,
+            generator,
+            internal_name,
+            title,
+            kind,
+            values,
+            version,
+            extra: root,
+        })
+    }
+}
+
+impl From<&PresetFile> for MetadataPreset {
+    /// Recover the fields written by `metadata_preset.lrtemplate`.
+    ///
+    /// The region is not stored in the preset and is left empty. Location Shown
+    /// is derived from the other location fields and therefore not read back.
+    fn from(file: &PresetFile) -> Self {
+        let value = |name: &str| file.adobe_str(name).unwrap_or_default();
+
+        Self {
+            id: file.id,
+            gps: value("GPS"),
+            altitude: value("GPSAltitude"),
+            title: file.title.clone().unwrap_or_default(),
+            internal_name: file.internal_name.clone().unwrap_or_default(),
+            file_name: String::new(),
+            location: value("location"),
+            city: value("city"),
+            region: String::new(),
+            state: value("state"),
+            country: value("country"),
+            iso_country_code: value("isoCountryCode"),
+            creator: value("creator"),
+            copyright: value("copyright"),
+            keywords: value("keywords")
+                .split(',')
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+                .collect(),
+            scene: value("scene"),
+            version: file.version.unwrap_or_default() as u64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use askama::Template as _;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_rendered_preset() {
+        let preset = MetadataPreset {
+            id: Uuid::parse_str("0f9d8c86-2c1e-4a34-9a6b-5b0d2c7f1e11").unwrap(),
+            gps: r#"12°9'0" N 68°16'48" W"#.to_string(),
+            title: r#"[Location] Bonaire: "Salt" Pier"#.to_string(),
+            location: "Salt Pier".to_string(),
+            city: "Kralendijk".to_string(),
+            country: "Bonaire".to_string(),
+            iso_country_code: "bq".to_string(),
+            keywords: vec!["Caribbean Sea".to_string(), "Diving".to_string()],
+            scene: "011300".to_string(),
+            ..Default::default()
+        };
+
+        let file: PresetFile = preset.render().unwrap().parse().unwrap();
+        let parsed = MetadataPreset::from(&file);
+        assert_eq!(preset.id, parsed.id);
+        assert_eq!(preset.title, parsed.title);
+        assert_eq!(preset.gps, parsed.gps);
+        assert_eq!(preset.keywords, parsed.keywords);
+        assert_eq!(preset.scene, parsed.scene);
+        assert!(file.adobe_value("locationShown").is_some());
+        assert_eq!("BQ", parsed.iso_country_code);
+        assert_eq!("", parsed.state);
+        assert_eq!(Some("Metadata"), file.kind.as_deref());
+        assert_eq!(Some(PRESET_GENERATOR), file.generator.as_deref());
+    }
+
+    #[test]
+    fn test_parse_hand_made_preset() {
+        let content = r#"s = {
+	id = "5D0B6A16-3A1C-4B8B-8F3B-0E7A3D4C2B1A",
+	internalName = "Copyright",
+	title = "Copyright",
+	type = "Metadata",
+	value = {
+		["com.adobe.copyright"] = "© Jane Doe",
+		["com.adobe.copyrightState"] = true,
+		["com.adobe.creator"] = "Jane Doe",
+		uuid = "5D0B6A16-3A1C-4B8B-8F3B-0E7A3D4C2B1A",
+	},
+	version = 0,
+}
+"#;
+        let file: PresetFile = content.parse().unwrap();
+        assert_eq!(Some("© Jane Doe".to_string()), file.adobe_str("copyright"));
+        assert_eq!(
+            Some(&LuaValue::Bool(true)),
+            file.adobe_value("copyrightState")
+        );
+        assert_eq!(3, file.adobe_values().count());
+        assert_eq!(None, file.generator);
+        assert_eq!(content, file.render());
+    }
+
+    #[test]
+    fn test_round_trip_unknown_keys() {
+        let content = r#"s = {
+	comment = "Exported from an old catalog",
+	id = "5D0B6A16-3A1C-4B8B-8F3B-0E7A3D4C2B1A",
+	options = {
+		autoApply = true,
+	},
+	title = {
+		en = "Copyright",
+	},
+	value = {
+		["com.adobe.creator"] = "Jane Doe",
+	},
+	version = "1",
+}
+"#;
+        let file: PresetFile = content.parse().unwrap();
+        assert_eq!(None, file.title);
+        assert_eq!(None, file.version);
+        assert_eq!(4, file.extra.len());
+        assert_eq!(content, file.render());
+    }
+
+    #[test]
+    fn test_parse_preset_without_id() {
+        assert!("s = { title = \"x\" }".parse::<PresetFile>().is_err());
+    }
+}
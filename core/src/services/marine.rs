@@ -0,0 +1,319 @@
+//! Offline body-of-water detection from marine region polygons.
+//!
+//! The regions come from a local GeoJSON file such as the IHO sea areas
+//! published by marineregions.org. Sites on land (e.g. shore dives whose
+//! coordinates were taken at the parking lot) are matched to the nearest
+//! region within a configurable distance.
+
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::domain::{BodyOfWaterConfig, DiveSite, Polygon};
+use crate::error::{Error, Result};
+use crate::parsers::geojson::parse_features;
+
+/// Meters per degree of latitude.
+const METERS_PER_DEGREE: f64 = 111_195.0;
+
+/// A named marine region with its bounding box.
+#[derive(Debug, Clone)]
+struct Region {
+    name: String,
+    polygon: Polygon,
+    /// `(min longitude, min latitude, max longitude, max latitude)`
+    bounds: (f64, f64, f64, f64),
+}
+
+/// Distance in meters from a point to the segment `a`–`b`, all as
+/// `(longitude, latitude)`, in an equirectangular projection around the point.
+fn segment_distance(point: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let scale = point.1.to_radians().cos();
+    let project = |(lon, lat): (f64, f64)| {
+        let mut delta = lon - point.0;
+        if delta > 180.0 {
+            delta -= 360.0;
+        } else if delta < -180.0 {
+            delta += 360.0;
+        }
+        (
+            delta * scale * METERS_PER_DEGREE,
+            (lat - point.1) * METERS_PER_DEGREE,
+        )
+    };
+    let (a, b) = (project(a), project(b));
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length = dx * dx + dy * dy;
+    let t = if length == 0.0 {
+        0.0
+    } else {
+        (-(a.0 * dx + a.1 * dy) / length).clamp(0.0, 1.0)
+    };
+    (a.0 + t * dx).hypot(a.1 + t * dy)
+}
+
+impl Region {
+    /// Distance in meters from a point to the region, `0` inside it.
+    fn distance(&self, latitude: f64, longitude: f64) -> f64 {
+        if self.polygon.contains(latitude, longitude) {
+            return 0.0;
+        }
+        std::iter::once(&self.polygon.exterior)
+            .chain(&self.polygon.holes)
+            .flat_map(|ring| {
+                ring.iter()
+                    .zip(ring.iter().cycle().skip(1))
+                    .map(|(a, b)| segment_distance((longitude, latitude), *a, *b))
+            })
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    /// Whether the bounding box, grown by `margin` meters, contains the point.
+    fn near(&self, latitude: f64, longitude: f64, margin: f64) -> bool {
+        let (min_lon, min_lat, max_lon, max_lat) = self.bounds;
+        let delta_latitude = margin / METERS_PER_DEGREE;
+        let delta_longitude = margin / (METERS_PER_DEGREE * latitude.to_radians().cos().max(0.01));
+        (min_lat - delta_latitude..=max_lat + delta_latitude).contains(&latitude)
+            && (min_lon - delta_longitude..=max_lon + delta_longitude).contains(&longitude)
+    }
+}
+
+/// How the detected body of water relates to the one recorded in MacDive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BodyOfWater {
+    /// MacDive has no value, the detected one is used.
+    Filled(String),
+    /// MacDive has the detected value.
+    Matches,
+    /// MacDive has another value, which is kept; the detected one is given.
+    Differs(String),
+    /// No region was found for the site.
+    Unknown,
+}
+
+/// Named marine regions loaded from GeoJSON.
+#[derive(Debug, Clone)]
+pub struct MarineRegions {
+    regions: Vec<Region>,
+    max_distance: f64,
+}
+
+impl MarineRegions {
+    /// Parse marine regions from a GeoJSON document.
+    ///
+    /// Features without a string property `name_property` are skipped.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Json`] or [`Error::Config`] if the document is not
+    /// valid GeoJSON, or [`Error::Config`] if it has no named polygons.
+    pub fn from_geojson(content: &str, name_property: &str, max_distance: f64) -> Result<Self> {
+        let mut regions = vec![];
+        for feature in parse_features(content)? {
+            let Some(Value::String(name)) = feature.properties.get(name_property) else {
+                continue;
+            };
+            for polygon in feature.polygons {
+                let bounds = polygon.exterior.iter().fold(
+                    (f64::MAX, f64::MAX, f64::MIN, f64::MIN),
+                    |(min_lon, min_lat, max_lon, max_lat), (lon, lat)| {
+                        (
+                            min_lon.min(*lon),
+                            min_lat.min(*lat),
+                            max_lon.max(*lon),
+                            max_lat.max(*lat),
+                        )
+                    },
+                );
+                regions.push(Region {
+                    name: name.trim().to_string(),
+                    polygon,
+                    bounds,
+                });
+            }
+        }
+
+        if regions.is_empty() {
+            return Err(Error::Config(format!(
+                "no marine regions with a `{name_property}` property found"
+            )));
+        }
+        Ok(Self {
+            regions,
+            max_distance,
+        })
+    }
+
+    /// Load the marine regions configured in `config`.
+    ///
+    /// Returns `None` if no file is configured.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Io`] if the file cannot be read, otherwise see
+    /// [`MarineRegions::from_geojson`].
+    pub fn from_config(config: &BodyOfWaterConfig) -> Result<Option<Self>> {
+        config
+            .marine_regions
+            .as_deref()
+            .map(|path| Self::load(path, config))
+            .transpose()
+    }
+
+    fn load(path: &Path, config: &BodyOfWaterConfig) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Self::from_geojson(&content, &config.name_property, config.max_distance)
+    }
+
+    /// The body of water at a position and the distance to it in meters.
+    ///
+    /// A region containing the position always wins. Otherwise the nearest
+    /// region within the maximum distance is returned.
+    pub fn find(&self, latitude: f64, longitude: f64) -> Option<(&str, f64)> {
+        self.regions
+            .iter()
+            .filter(|region| region.near(latitude, longitude, self.max_distance))
+            .map(|region| (region.name.as_str(), region.distance(latitude, longitude)))
+            .filter(|(_, distance)| *distance <= self.max_distance)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+    }
+
+    /// Compare the detected body of water of a site with the recorded one.
+    ///
+    /// Marine regions never name the body of water of a fresh water site,
+    /// e.g. a cenote or lake near the coast, so those are not filled.
+    pub fn check(&self, site: &DiveSite) -> BodyOfWater {
+        let Some((detected, _)) = self.find(site.latitude, site.longitude) else {
+            return BodyOfWater::Unknown;
+        };
+        match site.body_of_water.as_deref().map(str::trim) {
+            None | Some("") if site.is_fresh_water() => BodyOfWater::Unknown,
+            None | Some("") => BodyOfWater::Filled(detected.to_string()),
+            Some(recorded) if recorded.eq_ignore_ascii_case(detected) => BodyOfWater::Matches,
+            Some(_) => BodyOfWater::Differs(detected.to_string()),
+        }
+    }
+
+    /// Fill in the body of water of a site if MacDive has none.
+    pub fn fill(&self, mut site: DiveSite) -> DiveSite {
+        if let BodyOfWater::Filled(name) = self.check(&site) {
+            site.body_of_water = Some(name);
+        }
+        site
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    const REGIONS: &str = r#"{
+        "type": "FeatureCollection",
+        "features": [
+            {
+                "type": "Feature",
+                "properties": { "NAME": "Caribbean Sea" },
+                "geometry": {
+                    "type": "Polygon",
+                    "coordinates": [[[-70, 10], [-68, 10], [-68, 12], [-70, 12], [-70, 10]]]
+                }
+            },
+            {
+                "type": "Feature",
+                "properties": { "NAME": "North Atlantic Ocean" },
+                "geometry": {
+                    "type": "Polygon",
+                    "coordinates": [[[-70, 12], [-68, 12], [-68, 14], [-70, 14], [-70, 12]]]
+                }
+            },
+            {
+                "type": "Feature",
+                "properties": {},
+                "geometry": {
+                    "type": "Polygon",
+                    "coordinates": [[[0, 0], [1, 0], [1, 1], [0, 0]]]
+                }
+            }
+        ]
+    }"#;
+
+    fn site(latitude: f64, longitude: f64, body_of_water: Option<&str>) -> DiveSite {
+        DiveSite {
+            uuid: Uuid::new_v4(),
+            country: String::from("Bonaire"),
+            iso_country_code: String::from("BQ"),
+            name: String::from("Test Site"),
+            latitude,
+            longitude,
+            body_of_water: body_of_water.map(String::from),
+            site_id: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_find_region() {
+        let regions = MarineRegions::from_geojson(REGIONS, "NAME", 5_000.0).unwrap();
+        assert_eq!(Some(("Caribbean Sea", 0.0)), regions.find(11.0, -69.0));
+
+        // About 2.2 km east of the Caribbean Sea.
+        let (name, distance) = regions.find(11.0, -67.98).unwrap();
+        assert_eq!("Caribbean Sea", name);
+        assert!((2_000.0..2_400.0).contains(&distance));
+
+        assert!(regions.find(11.0, -67.9).is_none());
+        assert!(regions.find(0.2, 0.8).is_none());
+    }
+
+    #[test]
+    fn test_check_site() {
+        let regions = MarineRegions::from_geojson(REGIONS, "NAME", 5_000.0).unwrap();
+        assert_eq!(
+            BodyOfWater::Filled(String::from("Caribbean Sea")),
+            regions.check(&site(11.0, -69.0, None))
+        );
+        assert_eq!(
+            BodyOfWater::Matches,
+            regions.check(&site(11.0, -69.0, Some("caribbean sea")))
+        );
+        assert_eq!(
+            BodyOfWater::Differs(String::from("North Atlantic Ocean")),
+            regions.check(&site(13.0, -69.0, Some("Caribbean Sea")))
+        );
+        assert_eq!(BodyOfWater::Unknown, regions.check(&site(0.0, 50.0, None)));
+
+        let filled = regions.fill(site(11.0, -69.0, Some(" ")));
+        assert_eq!(Some("Caribbean Sea"), filled.body_of_water.as_deref());
+    }
+
+    #[test]
+    fn test_fresh_water_site() {
+        let regions = MarineRegions::from_geojson(REGIONS, "NAME", 5_000.0).unwrap();
+        // A lake about 2.2 km from the coast of the Caribbean Sea.
+        let lake = |body_of_water: Option<&str>| DiveSite {
+            water_type: Some(String::from("Fresh")),
+            ..site(11.0, -67.98, body_of_water)
+        };
+
+        assert_eq!(BodyOfWater::Unknown, regions.check(&lake(None)));
+        assert_eq!(None, regions.fill(lake(None)).body_of_water);
+        assert_eq!(
+            BodyOfWater::Differs(String::from("Caribbean Sea")),
+            regions.check(&lake(Some("Blue Lake")))
+        );
+        assert_eq!(
+            Some("Blue Lake"),
+            regions
+                .fill(lake(Some("Blue Lake")))
+                .body_of_water
+                .as_deref()
+        );
+    }
+
+    #[test]
+    fn test_no_named_regions() {
+        assert!(MarineRegions::from_geojson(REGIONS, "name", 5_000.0).is_err());
+    }
+}
@@ -1,6 +1,15 @@
 /// Service integrations for external APIs.
+pub mod duplicates;
+pub mod elevation;
+pub mod export;
 pub mod geocoding;
+pub mod geojson;
+pub mod geotag;
 pub mod globalnames;
 pub mod inaturalist;
 pub mod lightroom;
+pub mod marine;
 pub mod mtp;
+pub mod overrides;
+pub mod validation;
+pub mod xmp;
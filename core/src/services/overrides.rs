@@ -0,0 +1,429 @@
+//! Check location overrides against dive sites and suggest new ones.
+//!
+//! Overrides are matched in the order of
+//! [`ApplicationConfig::locations`](crate::domain::ApplicationConfig::locations),
+//! so when polygons overlap only the first one is applied to a site. The
+//! report built here makes this visible before any metadata is written.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashSet};
+
+use crate::domain::{DiveSite, LocationOverride};
+use crate::util::disjoint_set::DisjointSet;
+use crate::util::geo::{buffered_hull, haversine_distance};
+
+/// The sites that fall within a single override.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverrideCoverage {
+    /// Name of the override in the configuration.
+    pub name: String,
+    /// Indices of all sites within the override's polygons.
+    pub sites: Vec<usize>,
+    /// Indices of the sites within the polygons that are captured by an
+    /// earlier override instead.
+    pub shadowed: Vec<usize>,
+}
+
+/// A field two overlapping overrides set to different values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldConflict {
+    /// Name of the field (e.g. `state`).
+    pub field: &'static str,
+    /// Value of the override that is applied.
+    pub first: String,
+    /// Value of the override that loses.
+    pub second: String,
+}
+
+/// Two overrides whose polygons overlap.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverrideOverlap {
+    /// The override that is applied to sites in the overlapping area.
+    pub first: String,
+    /// The override that is shadowed in the overlapping area.
+    pub second: String,
+    /// Indices of the sites within both overrides.
+    pub sites: Vec<usize>,
+    /// Fields both overrides set, but to different values.
+    pub conflicts: Vec<FieldConflict>,
+}
+
+/// Result of [`check_overrides`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CoverageReport {
+    /// Coverage of every override, in the order they are matched.
+    pub overrides: Vec<OverrideCoverage>,
+    /// Indices of the sites not within any override.
+    pub uncovered: Vec<usize>,
+    /// All pairs of overlapping overrides.
+    pub overlaps: Vec<OverrideOverlap>,
+}
+
+/// The fields both overrides set to different values.
+fn conflicts(first: &LocationOverride, second: &LocationOverride) -> Vec<FieldConflict> {
+    [
+        ("country", &first.country, &second.country),
+        (
+            "iso_country_code",
+            &first.iso_country_code,
+            &second.iso_country_code,
+        ),
+        ("state", &first.state, &second.state),
+        ("region", &first.region, &second.region),
+        ("locality", &first.locality, &second.locality),
+    ]
+    .into_iter()
+    .filter_map(|(field, first, second)| match (first, second) {
+        (Some(first), Some(second)) if first != second => Some(FieldConflict {
+            field,
+            first: first.to_owned(),
+            second: second.to_owned(),
+        }),
+        _ => None,
+    })
+    .collect()
+}
+
+/// Evaluate every override against all dive sites.
+///
+/// # Arguments
+///
+/// * `overrides` - Named overrides in the order they are matched, as returned
+///   by [`ApplicationConfig::named_locations`](crate::domain::ApplicationConfig::named_locations).
+/// * `sites` - The dive sites to check; the report refers to them by index.
+pub fn check_overrides(
+    overrides: &[(String, LocationOverride)],
+    sites: &[DiveSite],
+) -> CoverageReport {
+    let contained: Vec<Vec<usize>> = overrides
+        .iter()
+        .map(|(_, location)| {
+            sites
+                .iter()
+                .enumerate()
+                .filter(|(_, site)| location.contains(site.latitude, site.longitude))
+                .map(|(index, _)| index)
+                .collect()
+        })
+        .collect();
+
+    let mut report = CoverageReport::default();
+    for (i, (name, _)) in overrides.iter().enumerate() {
+        let shadowed = contained[i]
+            .iter()
+            .filter(|site| contained[..i].iter().any(|earlier| earlier.contains(site)))
+            .copied()
+            .collect();
+        report.overrides.push(OverrideCoverage {
+            name: name.to_owned(),
+            sites: contained[i].clone(),
+            shadowed,
+        });
+    }
+
+    report.uncovered = (0..sites.len())
+        .filter(|site| !contained.iter().any(|sites| sites.contains(site)))
+        .collect();
+
+    for (i, (first_name, first)) in overrides.iter().enumerate() {
+        for (j, (second_name, second)) in overrides.iter().enumerate().skip(i + 1) {
+            if !first.overlaps(second) {
+                continue;
+            }
+            report.overlaps.push(OverrideOverlap {
+                first: first_name.to_owned(),
+                second: second_name.to_owned(),
+                sites: contained[i]
+                    .iter()
+                    .filter(|site| contained[j].contains(site))
+                    .copied()
+                    .collect(),
+                conflicts: conflicts(first, second),
+            });
+        }
+    }
+
+    report
+}
+
+/// A location override proposed for a cluster of nearby dive sites.
+#[derive(Debug, Clone)]
+pub struct OverrideSuggestion {
+    /// Proposed name of the override, unique among all suggestions.
+    pub name: String,
+    /// Indices of the sites in the cluster.
+    pub sites: Vec<usize>,
+    /// The override with a buffered convex hull around the sites.
+    pub location: LocationOverride,
+}
+
+/// Two values are compatible unless both are set and differ.
+fn compatible(a: &Option<String>, b: &Option<String>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+        _ => true,
+    }
+}
+
+/// The most common value, ties broken alphabetically.
+fn most_common<'a>(values: impl Iterator<Item = Option<&'a str>>) -> Option<String> {
+    let mut counts = BTreeMap::<&str, usize>::new();
+    for value in values.flatten().filter(|value| !value.is_empty()) {
+        *counts.entry(value).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .max_by_key(|(value, count)| (*count, Reverse(*value)))
+        .map(|(value, _)| value.to_string())
+}
+
+/// Lowercase ASCII name with dashes, e.g. `Lembeh Strait` → `lembeh-strait`.
+fn slug(value: &str) -> String {
+    value
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(str::to_ascii_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Group dive sites into clusters and propose an override for each.
+///
+/// Two sites end up in the same cluster if they are in the same country, no
+/// further apart than `max_distance` meters and do not disagree on their
+/// region or locality. Clusters grow transitively, so a chain of sites along
+/// a liveaboard route becomes a single cluster.
+///
+/// Each proposal carries the most common location values of its sites and a
+/// convex hull buffered by `buffer` meters around them.
+pub fn suggest_overrides(
+    sites: &[DiveSite],
+    max_distance: f64,
+    buffer: f64,
+) -> Vec<OverrideSuggestion> {
+    let mut sets = DisjointSet::new(sites.len());
+    // Region and locality of each cluster, kept at its root, so that sites
+    // without values cannot bridge two clusters that disagree.
+    let mut values: Vec<(Option<String>, Option<String>)> = sites
+        .iter()
+        .map(|site| (site.region.clone(), site.locality.clone()))
+        .collect();
+    for (i, a) in sites.iter().enumerate() {
+        for (j, b) in sites.iter().enumerate().skip(i + 1) {
+            if !a.iso_country_code.eq_ignore_ascii_case(&b.iso_country_code)
+                || haversine_distance(a.latitude, a.longitude, b.latitude, b.longitude)
+                    > max_distance
+            {
+                continue;
+            }
+            let (a, b) = (sets.root(i), sets.root(j));
+            if a == b
+                || !compatible(&values[a].0, &values[b].0)
+                || !compatible(&values[a].1, &values[b].1)
+            {
+                continue;
+            }
+            let (region, locality) = std::mem::take(&mut values[b]);
+            values[a].0 = values[a].0.take().or(region);
+            values[a].1 = values[a].1.take().or(locality);
+            sets.union(a, b);
+        }
+    }
+
+    let mut names = HashSet::new();
+    sets.groups()
+        .into_iter()
+        .map(|indices| {
+            let members = || indices.iter().map(|index| &sites[*index]);
+            let points: Vec<(f64, f64)> = members()
+                .map(|site| (site.longitude, site.latitude))
+                .collect();
+            let area = buffered_hull(&points, buffer)
+                .into_iter()
+                .map(|(lon, lat)| ((lon * 1e6).round() / 1e6, (lat * 1e6).round() / 1e6))
+                .collect();
+
+            let location = LocationOverride {
+                area,
+                country: most_common(members().map(|site| Some(site.country.as_str()))),
+                iso_country_code: most_common(
+                    members().map(|site| Some(site.iso_country_code.as_str())),
+                ),
+                state: most_common(members().map(|site| site.state.as_deref())),
+                region: most_common(members().map(|site| site.region.as_deref())),
+                locality: most_common(members().map(|site| site.locality.as_deref())),
+                ..Default::default()
+            };
+
+            let base = [&location.locality, &location.region, &location.state]
+                .into_iter()
+                .flatten()
+                .chain(&location.country)
+                .map(|value| slug(value))
+                .find(|value| !value.is_empty())
+                .unwrap_or_else(|| String::from("cluster"));
+            let name = (1..)
+                .map(|n| match n {
+                    1 => base.clone(),
+                    n => format!("{base}-{n}"),
+                })
+                .find(|name| names.insert(name.clone()))
+                .unwrap_or(base);
+
+            OverrideSuggestion {
+                name,
+                sites: indices,
+                location,
+            }
+        })
+        .collect()
+}
+
+/// Render suggestions as entries of the `locations` map of the
+/// configuration file, ready to paste.
+///
+/// Strings are written as JSON strings, which are valid YAML scalars.
+pub fn render_yaml(suggestions: &[OverrideSuggestion]) -> String {
+    let quote = |value: &Option<String>| match value {
+        Some(value) => serde_json::Value::from(value.as_str()).to_string(),
+        None => String::from("~"),
+    };
+
+    let mut output = String::new();
+    for suggestion in suggestions {
+        let location = &suggestion.location;
+        output.push_str(&format!(
+            "  {}:\n    area:\n      # Lon/Lat\n",
+            suggestion.name
+        ));
+        for (longitude, latitude) in &location.area {
+            output.push_str(&format!("      - [{longitude}, {latitude}]\n"));
+        }
+        output.push_str(&format!("    country: {}\n", quote(&location.country)));
+        output.push_str(&format!(
+            "    iso_country_code: {}\n",
+            quote(&location.iso_country_code)
+        ));
+        output.push_str(&format!("    state: {}\n", quote(&location.state)));
+        output.push_str(&format!("    region: {}\n", quote(&location.region)));
+        output.push_str(&format!("    locality: {}\n", quote(&location.locality)));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn site(latitude: f64, longitude: f64) -> DiveSite {
+        site_in(latitude, longitude, None)
+    }
+
+    fn site_in(latitude: f64, longitude: f64, region: Option<&str>) -> DiveSite {
+        DiveSite {
+            uuid: Uuid::new_v4(),
+            country: String::from("Bonaire"),
+            iso_country_code: String::from("BQ"),
+            region: region.map(String::from),
+            name: String::from("Test Site"),
+            latitude,
+            longitude,
+            site_id: 1,
+            ..Default::default()
+        }
+    }
+
+    fn square(x: f64, y: f64, size: f64, state: &str) -> LocationOverride {
+        LocationOverride {
+            area: vec![(x, y), (x + size, y), (x + size, y + size), (x, y + size)],
+            state: Some(String::from(state)),
+            region: Some(String::from("Leeward")),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_check_overrides() {
+        let overrides = vec![
+            (String::from("small"), square(2.0, 2.0, 4.0, "Small")),
+            (String::from("large"), square(0.0, 0.0, 10.0, "Large")),
+            (String::from("remote"), square(50.0, 50.0, 1.0, "Remote")),
+        ];
+        // Inside both, inside only the large square and outside of all.
+        let sites = vec![site(3.0, 3.0), site(8.0, 8.0), site(-5.0, -5.0)];
+
+        let report = check_overrides(&overrides, &sites);
+        assert_eq!(vec![0], report.overrides[0].sites);
+        assert_eq!(vec![0, 1], report.overrides[1].sites);
+        assert_eq!(vec![0], report.overrides[1].shadowed);
+        assert!(report.overrides[2].sites.is_empty());
+        assert_eq!(vec![2], report.uncovered);
+
+        assert_eq!(1, report.overlaps.len());
+        let overlap = &report.overlaps[0];
+        assert_eq!(("small", "large"), (&*overlap.first, &*overlap.second));
+        assert_eq!(vec![0], overlap.sites);
+        assert_eq!(
+            vec![FieldConflict {
+                field: "state",
+                first: String::from("Small"),
+                second: String::from("Large"),
+            }],
+            overlap.conflicts
+        );
+    }
+
+    #[test]
+    fn test_suggest_overrides() {
+        // A chain of sites roughly 3 km apart, a site of another region close
+        // to it and a remote site.
+        let sites = vec![
+            site_in(12.00, -68.30, Some("Leeward")),
+            site_in(12.03, -68.30, None),
+            site_in(12.06, -68.30, Some("Leeward")),
+            site_in(12.06, -68.28, Some("Washington Slagbaai")),
+            site_in(14.00, -68.00, None),
+        ];
+
+        let suggestions = suggest_overrides(&sites, 5000.0, 500.0);
+        assert_eq!(3, suggestions.len());
+        assert_eq!(vec![0, 1, 2], suggestions[0].sites);
+        assert_eq!(vec![3], suggestions[1].sites);
+        assert_eq!(vec![4], suggestions[2].sites);
+
+        let leeward = &suggestions[0];
+        assert_eq!("leeward", leeward.name);
+        assert_eq!(Some("Leeward"), leeward.location.region.as_deref());
+        assert_eq!(Some("BQ"), leeward.location.iso_country_code.as_deref());
+        assert!(
+            sites[..3]
+                .iter()
+                .all(|site| leeward.location.contains(site.latitude, site.longitude))
+        );
+        assert!(
+            !leeward
+                .location
+                .contains(sites[3].latitude, sites[3].longitude)
+        );
+
+        // Both remaining clusters fall back to the country name.
+        assert_eq!("bonaire", suggestions[2].name);
+        assert_eq!(16, suggestions[2].location.area.len());
+    }
+
+    #[test]
+    fn test_render_yaml_round_trip() {
+        let suggestions =
+            suggest_overrides(&[site_in(12.0, -68.3, Some("Lac \"Bay\""))], 1.0, 100.0);
+        let yaml = format!("locations:\n{}", render_yaml(&suggestions));
+
+        let locations: BTreeMap<String, BTreeMap<String, LocationOverride>> =
+            serde_saphyr::from_str(&yaml).unwrap();
+        let location = &locations["locations"]["lac-bay"];
+        assert_eq!(Some("Lac \"Bay\""), location.region.as_deref());
+        assert_eq!(None, location.state);
+        assert!(location.contains(12.0, -68.3));
+    }
+}
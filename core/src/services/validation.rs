@@ -0,0 +1,258 @@
+//! Data-quality checks for MacDive dive sites.
+//!
+//! The checks report every problem of a site at once, unlike the conversion
+//! into a [`DiveSite`](crate::domain::DiveSite) which stops at the first one.
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::domain::DiveSite;
+use crate::services::geocoding::Geocoder;
+
+/// Coordinates closer than this to 0°, 0° are treated as never set.
+const NULL_ISLAND_EPSILON: f64 = 1e-6;
+
+/// A problem with a dive site in MacDive.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "issue", rename_all = "snake_case")]
+pub enum SiteIssue {
+    /// The site has no UUID.
+    MissingUuid,
+    /// The site's UUID cannot be parsed.
+    InvalidUuid { uuid: String },
+    /// The site has no name.
+    MissingName,
+    /// The site has no country.
+    MissingCountry,
+    /// The site's country is not a known country name.
+    UnknownCountry { country: String },
+    /// The latitude or longitude is missing.
+    MissingCoordinates,
+    /// The latitude or longitude is outside of the valid range.
+    InvalidCoordinates,
+    /// The latitude and longitude look like they have been entered the other
+    /// way around.
+    SwappedCoordinates,
+    /// The site is at 0°, 0°, which usually means the position was never set.
+    NullIsland,
+    /// The stored country disagrees with the country at the site's position.
+    CountryMismatch { stored: String, detected: String },
+}
+
+impl fmt::Display for SiteIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SiteIssue::MissingUuid => write!(f, "missing UUID"),
+            SiteIssue::InvalidUuid { uuid } => write!(f, "invalid UUID `{uuid}`"),
+            SiteIssue::MissingName => write!(f, "missing name"),
+            SiteIssue::MissingCountry => write!(f, "missing country"),
+            SiteIssue::UnknownCountry { country } => write!(f, "unknown country `{country}`"),
+            SiteIssue::MissingCoordinates => write!(f, "missing coordinates"),
+            SiteIssue::InvalidCoordinates => write!(f, "coordinates out of range"),
+            SiteIssue::SwappedCoordinates => write!(f, "latitude and longitude look swapped"),
+            SiteIssue::NullIsland => write!(f, "coordinates at 0°, 0°"),
+            SiteIssue::CountryMismatch { stored, detected } => {
+                write!(
+                    f,
+                    "country is {stored} but the coordinates are in {detected}"
+                )
+            }
+        }
+    }
+}
+
+/// All problems found with a single dive site.
+#[derive(Debug, Clone, Serialize)]
+pub struct SiteReport {
+    /// Primary key of the site in the MacDive database.
+    pub site_id: i64,
+    pub name: Option<String>,
+    pub country: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub issues: Vec<SiteIssue>,
+}
+
+/// Check the GPS coordinates of a site without looking them up.
+///
+/// Coordinates whose latitude is out of range but would be valid the other
+/// way around are reported as swapped rather than invalid.
+///
+/// # Examples
+///
+/// ```
+/// use macdive_toolbox_core::services::validation::{SiteIssue, check_coordinates};
+///
+/// assert!(check_coordinates(Some(-8.27), Some(115.31)).is_empty());
+/// assert_eq!(
+///     vec![SiteIssue::SwappedCoordinates],
+///     check_coordinates(Some(115.31), Some(-8.27))
+/// );
+/// ```
+pub fn check_coordinates(latitude: Option<f64>, longitude: Option<f64>) -> Vec<SiteIssue> {
+    let (Some(latitude), Some(longitude)) = (latitude, longitude) else {
+        return vec![SiteIssue::MissingCoordinates];
+    };
+
+    let valid = |latitude: f64, longitude: f64| {
+        latitude.is_finite()
+            && longitude.is_finite()
+            && latitude.abs() <= 90.0
+            && longitude.abs() <= 180.0
+    };
+    if valid(latitude, longitude) {
+        if latitude.abs() < NULL_ISLAND_EPSILON && longitude.abs() < NULL_ISLAND_EPSILON {
+            return vec![SiteIssue::NullIsland];
+        }
+        vec![]
+    } else if valid(longitude, latitude) {
+        vec![SiteIssue::SwappedCoordinates]
+    } else {
+        vec![SiteIssue::InvalidCoordinates]
+    }
+}
+
+/// Compare the stored country of a site with the country at its position.
+///
+/// # Arguments
+///
+/// * `stored` - ISO code of the country stored in MacDive.
+/// * `detected` - ISO code of the country at the site's position.
+/// * `swapped` - ISO code of the country at the position with latitude and
+///   longitude swapped, if it has been looked up. A match there means the
+///   coordinates were entered the wrong way around.
+pub fn check_country(stored: &str, detected: &str, swapped: Option<&str>) -> Option<SiteIssue> {
+    if stored.eq_ignore_ascii_case(detected) {
+        None
+    } else if swapped.is_some_and(|swapped| stored.eq_ignore_ascii_case(swapped)) {
+        Some(SiteIssue::SwappedCoordinates)
+    } else {
+        Some(SiteIssue::CountryMismatch {
+            stored: stored.to_uppercase(),
+            detected: detected.to_uppercase(),
+        })
+    }
+}
+
+/// Look up the country at the position of `site` and compare it with the
+/// stored one, see [`check_country`].
+///
+/// If they differ, the position with latitude and longitude swapped is looked
+/// up as well, without caching the result. The stored country is cleared for
+/// both lookups, since some geocoders (e.g. GeoNames) only search the country
+/// a site claims to be in. Sites that cannot be geocoded or whose position
+/// has no country are not reported.
+pub async fn geocode_country(geocoder: &dyn Geocoder, site: DiveSite) -> Option<SiteIssue> {
+    let stored = site.iso_country_code.clone();
+    let (latitude, longitude) = (site.latitude, site.longitude);
+    let site = DiveSite {
+        country: String::new(),
+        iso_country_code: String::new(),
+        ..site
+    };
+    let detected = match geocoder.reverse_geocode(site.clone()).await {
+        Ok(geocoded) => geocoded.iso_country_code,
+        Err(e) => {
+            tracing::warn!("Could not geocode dive site {}: {e}", site.site_id);
+            return None;
+        }
+    };
+    if detected.is_empty() || stored.eq_ignore_ascii_case(&detected) {
+        return None;
+    }
+
+    let swapped = if longitude.abs() <= 90.0 {
+        let site = DiveSite {
+            latitude: longitude,
+            longitude: latitude,
+            ..site
+        };
+        geocoder
+            .lookup(site)
+            .await
+            .ok()
+            .map(|geocoded| geocoded.iso_country_code)
+    } else {
+        None
+    };
+    check_country(&stored, &detected, swapped.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::geocoding::GeoNamesGeocoder;
+
+    use super::*;
+
+    #[test]
+    fn test_check_coordinates() {
+        assert_eq!(
+            vec![SiteIssue::MissingCoordinates],
+            check_coordinates(Some(12.15), None)
+        );
+        assert_eq!(
+            vec![SiteIssue::NullIsland],
+            check_coordinates(Some(0.0), Some(0.0))
+        );
+        assert_eq!(
+            vec![SiteIssue::InvalidCoordinates],
+            check_coordinates(Some(95.0), Some(190.0))
+        );
+        assert_eq!(
+            vec![SiteIssue::InvalidCoordinates],
+            check_coordinates(Some(f64::NAN), Some(10.0))
+        );
+        // Valid both ways around, so only the country check can tell.
+        assert!(check_coordinates(Some(-8.27), Some(39.68)).is_empty());
+    }
+
+    #[test]
+    fn test_check_country() {
+        assert_eq!(None, check_country("BQ", "bq", None));
+        assert_eq!(
+            Some(SiteIssue::SwappedCoordinates),
+            check_country("ID", "SO", Some("ID"))
+        );
+        assert_eq!(
+            Some(SiteIssue::CountryMismatch {
+                stored: String::from("CW"),
+                detected: String::from("BQ"),
+            }),
+            check_country("CW", "BQ", Some("XX"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_geocode_country() {
+        let geocoder = GeoNamesGeocoder::from_dumps(
+            "\
+3513563\tKralendijk\tKralendijk\t\t12.15\t-68.26667\tP\tPPLC\tBQ\t\t00\t\t\t\t3081\t\t1\tAmerica/Kralendijk\t2019-09-05
+3513090\tWillemstad\tWillemstad\t\t12.1084\t-68.93354\tP\tPPLC\tCW\t\t00\t\t\t\t125000\t\t1\tAmerica/Curacao\t2020-03-06
+",
+            "",
+            "",
+            "",
+            10_000.0,
+        )
+        .unwrap();
+        let site = |iso_country_code: &str| DiveSite {
+            iso_country_code: iso_country_code.to_string(),
+            name: String::from("Salt Pier"),
+            latitude: 12.083,
+            longitude: -68.283,
+            site_id: 1,
+            ..Default::default()
+        };
+
+        assert_eq!(None, geocode_country(&geocoder, site("BQ")).await);
+        // GeoNames would find Willemstad if it only searched Curaçao.
+        assert_eq!(
+            Some(SiteIssue::CountryMismatch {
+                stored: String::from("CW"),
+                detected: String::from("BQ"),
+            }),
+            geocode_country(&geocoder, site("CW")).await
+        );
+    }
+}
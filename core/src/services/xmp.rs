@@ -0,0 +1,918 @@
+//! XMP sidecar files carrying dive site location metadata.
+//!
+//! Sidecars are updated by streaming the existing document and only replacing
+//! the properties this module manages inside `rdf:Description`. Everything
+//! else — other namespaces, comments, padding and unknown properties — is
+//! copied through unchanged, so sidecars written by darktable, digiKam,
+//! Capture One or Lightroom keep their own data.
+
+use std::path::{Path, PathBuf};
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::name::ResolveResult;
+use quick_xml::{NsReader, Writer};
+use walkdir::WalkDir;
+
+use crate::domain::{DiveSite, LightroomConfig};
+use crate::error::{Error, Result};
+use crate::services::lightroom::MetadataPreset;
+
+/// File extensions (lower case) of photos that get a sidecar.
+pub const PHOTO_EXTENSIONS: &[&str] = &[
+    "arw", "cr2", "cr3", "dng", "heic", "heif", "jpeg", "jpg", "nef", "orf", "pef", "png", "raf",
+    "rw2", "srw", "tif", "tiff",
+];
+
+/// Skeleton used when a photo has no sidecar yet.
+const EMPTY_SIDECAR: &str = r#"<?xpacket begin="﻿" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/" x:xmptk="MacDive Toolbox">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""/>
+ </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>
+"#;
+
+/// An XML namespace with the prefix used when writing properties.
+#[derive(Debug, PartialEq, Eq)]
+struct Namespace {
+    prefix: &'static str,
+    uri: &'static str,
+}
+
+const RDF: Namespace = Namespace {
+    prefix: "rdf",
+    uri: "http://www.w3.org/1999/02/22-rdf-syntax-ns#",
+};
+const DC: Namespace = Namespace {
+    prefix: "dc",
+    uri: "http://purl.org/dc/elements/1.1/",
+};
+const EXIF: Namespace = Namespace {
+    prefix: "exif",
+    uri: "http://ns.adobe.com/exif/1.0/",
+};
+const PHOTOSHOP: Namespace = Namespace {
+    prefix: "photoshop",
+    uri: "http://ns.adobe.com/photoshop/1.0/",
+};
+const IPTC_CORE: Namespace = Namespace {
+    prefix: "Iptc4xmpCore",
+    uri: "http://iptc.org/std/Iptc4xmpCore/1.0/xmlns/",
+};
+const IPTC_EXT: Namespace = Namespace {
+    prefix: "Iptc4xmpExt",
+    uri: "http://iptc.org/std/Iptc4xmpExt/2008-02-29/",
+};
+const LIGHTROOM: Namespace = Namespace {
+    prefix: "lr",
+    uri: "http://ns.adobe.com/lightroom/1.0/",
+};
+
+/// Namespaces declared on the `rdf:Description` that receives the properties.
+const NAMESPACES: [&Namespace; 7] = [
+    &RDF, &DC, &EXIF, &PHOTOSHOP, &IPTC_CORE, &IPTC_EXT, &LIGHTROOM,
+];
+
+/// Separator between the levels of a hierarchical keyword.
+pub const KEYWORD_SEPARATOR: char = '|';
+
+/// The value of a single XMP property.
+#[derive(Debug)]
+enum Value {
+    Text(String),
+    Bag(Vec<String>),
+    Seq(Vec<String>),
+    /// A language alternative with only the `x-default` entry.
+    Alt(String),
+    /// An unordered array of structures with fields in `IPTC_EXT`.
+    Structs(Vec<Vec<(&'static str, String)>>),
+}
+
+/// A property written into the sidecar.
+#[derive(Debug)]
+struct Property {
+    namespace: &'static Namespace,
+    name: &'static str,
+    value: Value,
+}
+
+/// How an XMP sidecar was affected by [`write_sidecar`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SidecarStatus {
+    /// The photo had no sidecar yet.
+    Created,
+    /// An existing sidecar was updated.
+    Updated,
+    /// The sidecar already carried identical values.
+    Unchanged,
+}
+
+/// The location metadata written into XMP sidecars.
+///
+/// Carries the same GPS and IPTC fields as a Lightroom [`MetadataPreset`], but
+/// keeps the decimal coordinates since XMP stores GPS positions in its own
+/// notation.
+#[derive(Debug, Clone, Default)]
+pub struct XmpLocation {
+    /// WGS84 latitude in decimal degrees.
+    pub latitude: f64,
+    /// WGS84 longitude in decimal degrees.
+    pub longitude: f64,
+    /// Altitude in meters, zero if unknown.
+    pub altitude: f32,
+    /// IPTC sublocation — the name of the dive site.
+    pub location: String,
+    /// IPTC city.
+    pub city: String,
+    /// IPTC state/province.
+    pub state: String,
+    /// IPTC country name.
+    pub country: String,
+    /// ISO 3166-1 alpha-2 country code.
+    pub iso_country_code: String,
+    /// IPTC creator, empty to leave the field untouched.
+    pub creator: String,
+    /// IPTC copyright notice, empty to leave the field untouched.
+    pub copyright: String,
+    /// Keywords merged into the existing keywords of the photo.
+    pub keywords: Vec<String>,
+    /// Six digit IPTC scene code, empty to leave the field untouched.
+    pub scene: String,
+    /// Hierarchical keywords (levels separated by [`KEYWORD_SEPARATOR`])
+    /// merged into the existing `lr:hierarchicalSubject` entries of the photo.
+    pub hierarchical_keywords: Vec<String>,
+}
+
+impl XmpLocation {
+    /// Build the sidecar metadata for `site`.
+    ///
+    /// Uses the same field mapping as [`MetadataPreset::from_site`], so sidecars
+    /// and Lightroom presets always agree.
+    ///
+    /// # Errors
+    ///
+    /// See [`MetadataPreset::from_site`].
+    pub fn from_site(site: DiveSite, config: &LightroomConfig) -> Result<Self> {
+        let (latitude, longitude, altitude) = (site.latitude, site.longitude, site.altitude);
+        let preset = MetadataPreset::from_site(site, config)?;
+
+        Ok(Self {
+            latitude,
+            longitude,
+            altitude,
+            location: preset.location,
+            city: preset.city,
+            state: preset.state,
+            country: preset.country,
+            iso_country_code: preset.iso_country_code.to_uppercase(),
+            creator: preset.creator,
+            copyright: preset.copyright,
+            keywords: preset.keywords,
+            scene: preset.scene,
+            hierarchical_keywords: vec![],
+        })
+    }
+
+    /// The properties to write, given the flat and hierarchical keywords
+    /// already present in the sidecar.
+    fn properties(
+        &self,
+        existing_keywords: &[String],
+        existing_hierarchical: &[String],
+    ) -> Vec<Property> {
+        let mut properties = vec![
+            Property {
+                namespace: &EXIF,
+                name: "GPSVersionID",
+                value: Value::Text(String::from("2.2.0.0")),
+            },
+            Property {
+                namespace: &EXIF,
+                name: "GPSLatitude",
+                value: Value::Text(gps_coordinate(self.latitude, 'N', 'S')),
+            },
+            Property {
+                namespace: &EXIF,
+                name: "GPSLongitude",
+                value: Value::Text(gps_coordinate(self.longitude, 'E', 'W')),
+            },
+        ];
+        // MacDive stores an unknown altitude as zero; keep whatever altitude
+        // the camera recorded in that case.
+        if self.altitude != 0.0 {
+            properties.push(Property {
+                namespace: &EXIF,
+                name: "GPSAltitude",
+                value: Value::Text(format!(
+                    "{}/100",
+                    (self.altitude.abs() * 100.0).round() as u64
+                )),
+            });
+            properties.push(Property {
+                namespace: &EXIF,
+                name: "GPSAltitudeRef",
+                value: Value::Text(String::from(if self.altitude < 0.0 { "1" } else { "0" })),
+            });
+        }
+
+        let mut text = |namespace, name, value: &str| {
+            if !value.is_empty() {
+                properties.push(Property {
+                    namespace,
+                    name,
+                    value: Value::Text(value.to_string()),
+                });
+            }
+        };
+        text(&IPTC_CORE, "Location", &self.location);
+        text(&PHOTOSHOP, "City", &self.city);
+        text(&PHOTOSHOP, "State", &self.state);
+        text(&PHOTOSHOP, "Country", &self.country);
+        text(&IPTC_CORE, "CountryCode", &self.iso_country_code);
+
+        let shown: Vec<(&'static str, String)> = [
+            ("Sublocation", &self.location),
+            ("City", &self.city),
+            ("ProvinceState", &self.state),
+            ("CountryName", &self.country),
+            ("CountryCode", &self.iso_country_code),
+        ]
+        .into_iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(name, value)| (name, value.to_owned()))
+        .collect();
+        if !shown.is_empty() {
+            properties.push(Property {
+                namespace: &IPTC_EXT,
+                name: "LocationShown",
+                value: Value::Structs(vec![shown]),
+            });
+        }
+
+        if !self.scene.is_empty() {
+            properties.push(Property {
+                namespace: &IPTC_CORE,
+                name: "Scene",
+                value: Value::Bag(vec![self.scene.to_owned()]),
+            });
+        }
+        if !self.creator.is_empty() {
+            properties.push(Property {
+                namespace: &DC,
+                name: "creator",
+                value: Value::Seq(vec![self.creator.to_owned()]),
+            });
+        }
+        if !self.copyright.is_empty() {
+            properties.push(Property {
+                namespace: &DC,
+                name: "rights",
+                value: Value::Alt(self.copyright.to_owned()),
+            });
+        }
+
+        let mut keywords = existing_keywords.to_vec();
+        for keyword in &self.keywords {
+            if !keywords
+                .iter()
+                .any(|k| k.to_lowercase() == keyword.to_lowercase())
+            {
+                keywords.push(keyword.to_owned());
+            }
+        }
+        if !keywords.is_empty() {
+            properties.push(Property {
+                namespace: &DC,
+                name: "subject",
+                value: Value::Bag(keywords),
+            });
+        }
+
+        // A keyword whose leaf already exists anywhere in the hierarchy is not
+        // added again, e.g. after a candidate was moved to its final place.
+        let leaf = |keyword: &str| {
+            keyword
+                .rsplit(KEYWORD_SEPARATOR)
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_lowercase()
+        };
+        let mut hierarchical = existing_hierarchical.to_vec();
+        for keyword in &self.hierarchical_keywords {
+            if !hierarchical.iter().any(|k| leaf(k) == leaf(keyword)) {
+                hierarchical.push(keyword.to_owned());
+            }
+        }
+        if !hierarchical.is_empty() {
+            properties.push(Property {
+                namespace: &LIGHTROOM,
+                name: "hierarchicalSubject",
+                value: Value::Bag(hierarchical),
+            });
+        }
+
+        properties
+    }
+}
+
+/// Format a decimal coordinate in the XMP `GPSCoordinate` notation `DDD,MM.mmmmmmK`.
+fn gps_coordinate(value: f64, positive: char, negative: char) -> String {
+    let absolute = value.abs();
+    let degrees = absolute.trunc();
+    let minutes = (absolute - degrees) * 60.0;
+    let direction = if value < 0.0 { negative } else { positive };
+    format!("{},{:.6}{}", degrees as u32, minutes, direction)
+}
+
+fn xml_error(e: impl std::fmt::Display) -> Error {
+    Error::Xmp(e.to_string())
+}
+
+/// Returns the managed namespace an element or attribute name resolves to.
+fn resolve(result: &ResolveResult) -> Option<&'static Namespace> {
+    match result {
+        ResolveResult::Bound(ns) => NAMESPACES.into_iter().find(|n| n.uri.as_bytes() == ns.0),
+        _ => None,
+    }
+}
+
+fn is_rdf(namespace: Option<&Namespace>, local_name: &[u8], name: &[u8]) -> bool {
+    namespace == Some(&RDF) && local_name == name
+}
+
+fn is_managed(properties: &[Property], namespace: Option<&Namespace>, local_name: &[u8]) -> bool {
+    properties
+        .iter()
+        .any(|p| Some(p.namespace) == namespace && p.name.as_bytes() == local_name)
+}
+
+/// Collect the items of the array property `property:name` of an existing
+/// sidecar, e.g. the `dc:subject` keywords.
+fn read_array(content: &str, property: &Namespace, name: &str) -> Result<Vec<String>> {
+    let mut reader = NsReader::from_str(content);
+    let mut items = Vec::new();
+    let mut in_property = false;
+    let mut in_item = false;
+
+    loop {
+        let (namespace, event) = reader.read_resolved_event().map_err(xml_error)?;
+        let namespace = resolve(&namespace);
+        match event {
+            Event::Start(e) => {
+                let local_name = e.local_name();
+                if namespace == Some(property) && local_name.as_ref() == name.as_bytes() {
+                    in_property = true;
+                } else if in_property && namespace == Some(&RDF) && local_name.as_ref() == b"li" {
+                    in_item = true;
+                }
+            }
+            Event::End(e) => {
+                let local_name = e.local_name();
+                if namespace == Some(property) && local_name.as_ref() == name.as_bytes() {
+                    in_property = false;
+                } else if namespace == Some(&RDF) && local_name.as_ref() == b"li" {
+                    in_item = false;
+                }
+            }
+            Event::Text(e) if in_item => {
+                let item = e.unescape().map_err(xml_error)?;
+                if !item.trim().is_empty() {
+                    items.push(item.trim().to_string());
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(items)
+}
+
+/// Copy the attributes of an `rdf:Description`, dropping managed properties
+/// and declaring the namespaces of the properties written into it.
+fn description_start(
+    reader: &NsReader<&[u8]>,
+    start: &BytesStart,
+    properties: &[Property],
+    declare: bool,
+) -> Result<BytesStart<'static>> {
+    let mut output = BytesStart::new(String::from_utf8_lossy(start.name().as_ref()).into_owned());
+
+    for attribute in start.attributes() {
+        let attribute = attribute.map_err(xml_error)?;
+        let (namespace, local_name) = reader.resolve_attribute(attribute.key);
+        if is_managed(properties, resolve(&namespace), local_name.as_ref()) {
+            continue;
+        }
+        let key = attribute.key.as_ref();
+        if declare
+            && NAMESPACES
+                .iter()
+                .any(|ns| key == format!("xmlns:{}", ns.prefix).as_bytes())
+        {
+            continue;
+        }
+        output.push_attribute(attribute);
+    }
+
+    if declare {
+        // The `rdf` prefix is necessarily bound already.
+        for ns in NAMESPACES.into_iter().filter(|ns| **ns != RDF) {
+            output.push_attribute((format!("xmlns:{}", ns.prefix).as_str(), ns.uri));
+        }
+    }
+
+    Ok(output)
+}
+
+/// Render `properties` as child elements of an `rdf:Description`.
+fn render_properties(properties: &[Property], indent: &str) -> String {
+    use quick_xml::escape::escape;
+
+    let mut output = String::new();
+    for property in properties {
+        let name = format!("{}:{}", property.namespace.prefix, property.name);
+        let array = |kind: &str, items: &[String]| {
+            let mut output = format!("{indent} <{name}>\n{indent}  <rdf:{kind}>\n");
+            for item in items {
+                output += &format!("{indent}   <rdf:li>{}</rdf:li>\n", escape(item.as_str()));
+            }
+            output + &format!("{indent}  </rdf:{kind}>\n{indent} </{name}>\n")
+        };
+
+        output += &match &property.value {
+            Value::Text(value) => format!("{indent} <{name}>{}</{name}>\n", escape(value.as_str())),
+            Value::Bag(items) => array("Bag", items),
+            Value::Seq(items) => array("Seq", items),
+            Value::Alt(value) => format!(
+                "{indent} <{name}>\n{indent}  <rdf:Alt>\n{indent}   <rdf:li xml:lang=\"x-default\">{}</rdf:li>\n{indent}  </rdf:Alt>\n{indent} </{name}>\n",
+                escape(value.as_str())
+            ),
+            Value::Structs(items) => {
+                let mut output = format!("{indent} <{name}>\n{indent}  <rdf:Bag>\n");
+                for fields in items {
+                    output += &format!("{indent}   <rdf:li rdf:parseType=\"Resource\">\n");
+                    for (field, value) in fields {
+                        output += &format!(
+                            "{indent}    <{prefix}:{field}>{}</{prefix}:{field}>\n",
+                            escape(value.as_str()),
+                            prefix = IPTC_EXT.prefix,
+                        );
+                    }
+                    output += &format!("{indent}   </rdf:li>\n");
+                }
+                output + &format!("{indent}  </rdf:Bag>\n{indent} </{name}>\n")
+            }
+        };
+    }
+    output
+}
+
+/// Drop the whitespace-only tail of the output, including its line break.
+///
+/// Used before skipping a property so that removed elements do not leave
+/// blank lines behind.
+fn trim_trailing_line(buffer: &mut Vec<u8>) {
+    if let Some(position) = buffer.iter().rposition(|b| *b != b' ' && *b != b'\t')
+        && buffer[position] == b'\n'
+    {
+        buffer.truncate(position);
+    }
+}
+
+/// Insert the rendered properties before the closing `rdf:Description` tag.
+fn insert_properties(buffer: &mut Vec<u8>, properties: &[Property], indent: &str) {
+    let rendered = render_properties(properties, indent);
+    let line_start = buffer.len().saturating_sub(indent.len());
+    if buffer.ends_with(indent.as_bytes()) && buffer[..line_start].ends_with(b"\n") {
+        // The closing tag is already on its own, indented line.
+        buffer.extend(&rendered.as_bytes()[indent.len()..]);
+    } else {
+        buffer.push(b'\n');
+        buffer.extend(rendered.as_bytes());
+    }
+    buffer.extend(indent.as_bytes());
+}
+
+/// Merge the location metadata into the content of an XMP sidecar.
+///
+/// Managed properties are removed from every top-level `rdf:Description` —
+/// whether they are written as attributes or as elements — and written again
+/// into the first one. Descriptions nested inside properties, such as the
+/// history entries Lightroom and darktable write, are copied unchanged.
+/// Keywords are merged with the existing `dc:subject` and
+/// `lr:hierarchicalSubject` entries instead of replacing them. Passing `None`
+/// creates a new sidecar.
+///
+/// # Errors
+///
+/// Returns [`Error::Xmp`] if the existing content is not well-formed XML or
+/// contains no `rdf:Description`.
+pub fn update_sidecar(existing: Option<&str>, location: &XmpLocation) -> Result<String> {
+    let content = existing.unwrap_or(EMPTY_SIDECAR);
+    let properties = location.properties(
+        &read_array(content, &DC, "subject")?,
+        &read_array(content, &LIGHTROOM, "hierarchicalSubject")?,
+    );
+
+    let mut reader = NsReader::from_str(content);
+    let mut writer = Writer::new(Vec::with_capacity(content.len() + 2048));
+    let mut depth = 0usize;
+    // Depth of the children of `rdf:RDF`, i.e. of top-level descriptions.
+    let mut rdf: Option<usize> = None;
+    // Depth of the children of the top-level `rdf:Description` being copied.
+    let mut description: Option<usize> = None;
+    // Nesting level inside a managed property that is being dropped.
+    let mut skipping = 0usize;
+    let mut inserted = false;
+    let mut indent = String::new();
+
+    loop {
+        let (namespace, event) = reader.read_resolved_event().map_err(xml_error)?;
+        let namespace = resolve(&namespace);
+
+        match event {
+            Event::Start(e) => {
+                if skipping > 0 {
+                    skipping += 1;
+                } else if description == Some(depth)
+                    && is_managed(&properties, namespace, e.local_name().as_ref())
+                {
+                    trim_trailing_line(writer.get_mut());
+                    skipping = 1;
+                } else if rdf == Some(depth)
+                    && is_rdf(namespace, e.local_name().as_ref(), b"Description")
+                {
+                    description = Some(depth + 1);
+                    let start = description_start(&reader, &e, &properties, !inserted)?;
+                    writer.write_event(Event::Start(start)).map_err(xml_error)?;
+                } else {
+                    if rdf.is_none() && is_rdf(namespace, e.local_name().as_ref(), b"RDF") {
+                        rdf = Some(depth + 1);
+                    }
+                    writer.write_event(Event::Start(e)).map_err(xml_error)?;
+                }
+                depth += 1;
+            }
+            Event::End(e) => {
+                depth -= 1;
+                if skipping > 0 {
+                    skipping -= 1;
+                    continue;
+                }
+                if description == Some(depth + 1) {
+                    if !inserted {
+                        insert_properties(writer.get_mut(), &properties, &indent);
+                        inserted = true;
+                    }
+                    description = None;
+                }
+                writer.write_event(Event::End(e)).map_err(xml_error)?;
+            }
+            Event::Empty(e) => {
+                if skipping > 0 {
+                    continue;
+                }
+                if description == Some(depth)
+                    && is_managed(&properties, namespace, e.local_name().as_ref())
+                {
+                    trim_trailing_line(writer.get_mut());
+                    continue;
+                }
+                if rdf == Some(depth) && is_rdf(namespace, e.local_name().as_ref(), b"Description")
+                {
+                    let start = description_start(&reader, &e, &properties, !inserted)?;
+                    if inserted {
+                        writer.write_event(Event::Empty(start)).map_err(xml_error)?;
+                    } else {
+                        let end = start.to_end().into_owned();
+                        writer.write_event(Event::Start(start)).map_err(xml_error)?;
+                        insert_properties(writer.get_mut(), &properties, &indent);
+                        writer.write_event(Event::End(end)).map_err(xml_error)?;
+                        inserted = true;
+                    }
+                } else {
+                    writer.write_event(Event::Empty(e)).map_err(xml_error)?;
+                }
+            }
+            Event::Text(e) => {
+                if skipping > 0 {
+                    continue;
+                }
+                // Remember the indentation of the current line so that new
+                // properties line up with the surrounding document.
+                if let Some(line) = e.rsplit(|b| *b == b'\n').next()
+                    && e.contains(&b'\n')
+                    && line.iter().all(|b| *b == b' ' || *b == b'\t')
+                {
+                    indent = String::from_utf8_lossy(line).into_owned();
+                }
+                writer.write_event(Event::Text(e)).map_err(xml_error)?;
+            }
+            Event::Eof => break,
+            event => {
+                if skipping == 0 {
+                    writer.write_event(event).map_err(xml_error)?;
+                }
+            }
+        }
+    }
+
+    if !inserted {
+        return Err(Error::Xmp(String::from("no rdf:Description element found")));
+    }
+
+    String::from_utf8(writer.into_inner()).map_err(xml_error)
+}
+
+/// Path of the sidecar for `photo`.
+///
+/// By default the extension is replaced (`IMG_0001.xmp`), as Lightroom and
+/// Capture One expect. darktable and (optionally) digiKam append the sidecar
+/// extension instead (`IMG_0001.CR2.xmp`).
+pub fn sidecar_path(photo: &Path, append_extension: bool) -> PathBuf {
+    if append_extension {
+        let mut path = photo.as_os_str().to_owned();
+        path.push(".xmp");
+        PathBuf::from(path)
+    } else {
+        photo.with_extension("xmp")
+    }
+}
+
+/// Group the photos that would share a sidecar, e.g. the RAW and JPEG files
+/// of the same shot when the extension is replaced.
+///
+/// Only sidecars used by more than one photo are returned, sorted by path.
+pub fn shared_sidecars(photos: &[PathBuf], append_extension: bool) -> Vec<(PathBuf, Vec<&Path>)> {
+    let mut sidecars = std::collections::BTreeMap::<PathBuf, Vec<&Path>>::new();
+    for photo in photos {
+        sidecars
+            .entry(sidecar_path(photo, append_extension))
+            .or_default()
+            .push(photo);
+    }
+    sidecars
+        .into_iter()
+        .filter(|(_, photos)| photos.len() > 1)
+        .collect()
+}
+
+/// Find all photos in `directory`, sorted by path.
+///
+/// # Errors
+///
+/// Returns [`Error::Io`] if the directory cannot be read.
+pub fn find_photos(directory: &Path, recursive: bool) -> Result<Vec<PathBuf>> {
+    let mut walker = WalkDir::new(directory).follow_links(true);
+    if !recursive {
+        walker = walker.max_depth(1);
+    }
+
+    let mut photos = Vec::new();
+    for entry in walker {
+        let entry = entry.map_err(|e| Error::Io(e.into()))?;
+        let is_photo = entry
+            .path()
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| PHOTO_EXTENSIONS.contains(&e.to_lowercase().as_str()));
+        if entry.file_type().is_file() && is_photo {
+            photos.push(entry.into_path());
+        }
+    }
+    photos.sort();
+
+    Ok(photos)
+}
+
+/// Create or update the sidecar of `photo` with the location metadata.
+///
+/// Nothing is written if `dry_run` is set or the sidecar would not change.
+///
+/// # Errors
+///
+/// Returns [`Error::Io`] if the sidecar cannot be read or written, or
+/// [`Error::Xmp`] if the existing sidecar cannot be parsed.
+pub fn write_sidecar(
+    photo: &Path,
+    location: &XmpLocation,
+    append_extension: bool,
+    dry_run: bool,
+) -> Result<(PathBuf, SidecarStatus)> {
+    let path = sidecar_path(photo, append_extension);
+    let existing = match std::fs::read_to_string(&path) {
+        Ok(content) => Some(content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+
+    let content = update_sidecar(existing.as_deref(), location)?;
+    let status = match &existing {
+        None => SidecarStatus::Created,
+        Some(existing) if *existing == content => SidecarStatus::Unchanged,
+        Some(_) => SidecarStatus::Updated,
+    };
+
+    if !dry_run && status != SidecarStatus::Unchanged {
+        std::fs::write(&path, content)?;
+    }
+
+    Ok((path, status))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn location() -> XmpLocation {
+        XmpLocation {
+            latitude: 12.083,
+            longitude: -68.283,
+            altitude: 0.0,
+            location: String::from("Salt Pier"),
+            city: String::from("Kralendijk"),
+            country: String::from("Bonaire"),
+            iso_country_code: String::from("BQ"),
+            keywords: vec![String::from("Caribbean Sea")],
+            scene: String::from("011300"),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_gps_coordinate() {
+        assert_eq!("12,4.980000N", gps_coordinate(12.083, 'N', 'S'));
+        assert_eq!("68,16.980000W", gps_coordinate(-68.283, 'E', 'W'));
+    }
+
+    #[test]
+    fn test_new_sidecar() {
+        let content = update_sidecar(None, &location()).unwrap();
+        assert!(content.contains("<exif:GPSLatitude>12,4.980000N</exif:GPSLatitude>"));
+        assert!(content.contains("<Iptc4xmpExt:Sublocation>Salt Pier</Iptc4xmpExt:Sublocation>"));
+        assert!(content.contains("<rdf:li>011300</rdf:li>"));
+        assert!(!content.contains("photoshop:State"));
+        assert!(!content.contains("dc:creator"));
+        // Updating again must not change anything.
+        assert_eq!(
+            content,
+            update_sidecar(Some(&content), &location()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_merge_existing_sidecar() {
+        let existing = r#"<?xml version="1.0" encoding="UTF-8"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+  <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+    <rdf:Description rdf:about=""
+        xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+        xmlns:photoshop="http://ns.adobe.com/photoshop/1.0/"
+        xmlns:dc="http://purl.org/dc/elements/1.1/"
+        xmp:Rating="4"
+        photoshop:City="Somewhere">
+      <!-- keep me -->
+      <dc:subject>
+        <rdf:Bag>
+          <rdf:li>caribbean sea</rdf:li>
+          <rdf:li>Turtle</rdf:li>
+        </rdf:Bag>
+      </dc:subject>
+      <xmp:Label>Red</xmp:Label>
+    </rdf:Description>
+  </rdf:RDF>
+</x:xmpmeta>
+"#;
+        let content = update_sidecar(Some(existing), &location()).unwrap();
+        assert!(content.contains(r#"xmp:Rating="4""#));
+        assert!(content.contains("<xmp:Label>Red</xmp:Label>"));
+        assert!(content.contains("<!-- keep me -->"));
+        assert!(!content.contains("Somewhere"));
+        assert!(content.contains("<photoshop:City>Kralendijk</photoshop:City>"));
+        assert!(content.contains("<rdf:li>Turtle</rdf:li>"));
+        assert!(!content.contains("<rdf:li>Caribbean Sea</rdf:li>"));
+        assert_eq!(1, content.matches("<dc:subject>").count());
+        assert_eq!(
+            content,
+            update_sidecar(Some(&content), &location()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_nested_descriptions() {
+        let existing = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:xmpMM="http://ns.adobe.com/xap/1.0/mm/"
+    xmlns:stEvt="http://ns.adobe.com/xap/1.0/sType/ResourceEvent#"
+    xmlns:photoshop="http://ns.adobe.com/photoshop/1.0/">
+   <xmpMM:History>
+    <rdf:Seq>
+     <rdf:li>
+      <rdf:Description stEvt:action="saved" photoshop:City="Nested">
+       <photoshop:Country>Nested</photoshop:Country>
+      </rdf:Description>
+     </rdf:li>
+    </rdf:Seq>
+   </xmpMM:History>
+   <photoshop:Country>Curaçao</photoshop:Country>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>
+"#;
+        let content = update_sidecar(Some(existing), &location()).unwrap();
+        assert!(content.contains(
+            r#"<rdf:Description stEvt:action="saved" photoshop:City="Nested">
+       <photoshop:Country>Nested</photoshop:Country>
+      </rdf:Description>
+     </rdf:li>"#
+        ));
+        assert!(!content.contains("Curaçao"));
+        assert!(
+            content
+                .contains("   </xmpMM:History>\n   <exif:GPSVersionID>2.2.0.0</exif:GPSVersionID>")
+        );
+        assert!(content.ends_with("  </rdf:Description>\n </rdf:RDF>\n</x:xmpmeta>\n"));
+        assert_eq!(
+            content,
+            update_sidecar(Some(&content), &location()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_unknown_altitude() {
+        let content = update_sidecar(None, &location()).unwrap();
+        assert!(!content.contains("GPSAltitude"));
+        let content = update_sidecar(
+            None,
+            &XmpLocation {
+                altitude: -12.5,
+                ..location()
+            },
+        )
+        .unwrap();
+        assert!(content.contains("<exif:GPSAltitude>1250/100</exif:GPSAltitude>"));
+        assert!(content.contains("<exif:GPSAltitudeRef>1</exif:GPSAltitudeRef>"));
+    }
+
+    #[test]
+    fn test_merge_hierarchical_keywords() {
+        let existing = update_sidecar(
+            None,
+            &XmpLocation {
+                hierarchical_keywords: vec![String::from("Critters|Chordata|Chelonia mydas")],
+                ..location()
+            },
+        )
+        .unwrap();
+        let location = XmpLocation {
+            hierarchical_keywords: vec![
+                String::from("MacDive Candidates|Chordata|Chelonia mydas"),
+                String::from("MacDive Candidates|Chordata|Aetobatus narinari"),
+            ],
+            ..location()
+        };
+
+        let content = update_sidecar(Some(&existing), &location).unwrap();
+        assert!(content.contains(r#"xmlns:lr="http://ns.adobe.com/lightroom/1.0/""#));
+        assert!(content.contains("<rdf:li>Critters|Chordata|Chelonia mydas</rdf:li>"));
+        assert!(
+            content.contains("<rdf:li>MacDive Candidates|Chordata|Aetobatus narinari</rdf:li>")
+        );
+        assert!(!content.contains("MacDive Candidates|Chordata|Chelonia mydas"));
+        assert_eq!(content, update_sidecar(Some(&content), &location).unwrap());
+    }
+
+    #[test]
+    fn test_sidecar_path() {
+        let photo = Path::new("/photos/IMG_0001.CR2");
+        assert_eq!(
+            PathBuf::from("/photos/IMG_0001.xmp"),
+            sidecar_path(photo, false)
+        );
+        assert_eq!(
+            PathBuf::from("/photos/IMG_0001.CR2.xmp"),
+            sidecar_path(photo, true)
+        );
+    }
+
+    #[test]
+    fn test_shared_sidecars() {
+        let photos = [
+            PathBuf::from("/photos/IMG_0001.CR2"),
+            PathBuf::from("/photos/IMG_0001.JPG"),
+            PathBuf::from("/photos/IMG_0002.JPG"),
+        ];
+        assert_eq!(
+            vec![(
+                PathBuf::from("/photos/IMG_0001.xmp"),
+                vec![photos[0].as_path(), photos[1].as_path()]
+            )],
+            shared_sidecars(&photos, false)
+        );
+        assert!(shared_sidecars(&photos, true).is_empty());
+    }
+}
@@ -0,0 +1,753 @@
+//! Render and parse GPS coordinates in the notations divers come across.
+//!
+//! Boat captains and dive guides hand out positions as degrees and decimal
+//! minutes, maps use UTM or MGRS grid references, and Google Maps shares
+//! Plus Codes. [`format_coordinates`] renders a WGS84 position in any of the
+//! [`CoordinateFormat`]s and [`parse_coordinates`] reads all of them back.
+//!
+//! # Examples
+//!
+//! ```
+//! use macdive_toolbox_core::domain::CoordinateFormat;
+//! use macdive_toolbox_core::util::coordinates::{format_coordinates, parse_coordinates};
+//!
+//! let ddm = format_coordinates(12.15, -68.28, CoordinateFormat::Ddm).unwrap();
+//! assert_eq!("12°9.000' N 68°16.800' W", ddm);
+//!
+//! let (latitude, longitude) = parse_coordinates("N 12° 09.000' W 068° 16.800'").unwrap();
+//! assert!((latitude - 12.15).abs() < 1e-9 && (longitude + 68.28).abs() < 1e-9);
+//! ```
+
+use google_maps::LatLng;
+use rust_decimal::{Decimal, prelude::FromPrimitive};
+
+use crate::domain::{CoordinateFormat, DecimalToDms};
+use crate::error::{Error, Result};
+
+/// WGS84 semi-major axis in meters.
+const SEMI_MAJOR_AXIS: f64 = 6_378_137.0;
+/// WGS84 flattening.
+const FLATTENING: f64 = 1.0 / 298.257_223_563;
+/// UTM scale factor on the central meridian.
+const SCALE: f64 = 0.9996;
+const FALSE_EASTING: f64 = 500_000.0;
+/// False northing of the southern hemisphere.
+const FALSE_NORTHING: f64 = 10_000_000.0;
+
+/// UTM latitude bands from 80° S to 84° N, 8° each (`X` spans 12°).
+const BANDS: &[u8] = b"CDEFGHJKLMNPQRSTUVWX";
+/// MGRS 100 km square column letters, by zone set.
+const MGRS_COLUMNS: [&[u8]; 3] = [b"ABCDEFGH", b"JKLMNPQR", b"STUVWXYZ"];
+/// MGRS 100 km square row letters, shifted by five in even zones.
+const MGRS_ROWS: &[u8] = b"ABCDEFGHJKLMNPQRSTUV";
+
+/// Open Location Code digits.
+const OLC_ALPHABET: &[u8] = b"23456789CFGHJMPQRVWX";
+/// Number of Plus Code digits rendered, giving a resolution of about 14 m.
+const OLC_LENGTH: usize = 10;
+/// Resolution of the last digit pair in degrees.
+const OLC_RESOLUTION: f64 = 0.000_125;
+
+fn invalid(value: &str, reason: &str) -> Error {
+    Error::InvalidCoordinates(format!("`{}`: {reason}", value.trim()))
+}
+
+fn check_range(latitude: f64, longitude: f64) -> Result<()> {
+    if !(-90.0..=90.0).contains(&latitude) {
+        return Err(Error::InvalidLatitude);
+    }
+    if !(-180.0..=180.0).contains(&longitude) {
+        return Err(Error::InvalidLongitude);
+    }
+    Ok(())
+}
+
+/// Render a WGS84 position in the given notation.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidLatitude`] or [`Error::InvalidLongitude`] for
+/// positions out of range, or [`Error::InvalidCoordinates`] for UTM and MGRS
+/// positions in the polar regions, which these grids do not cover.
+pub fn format_coordinates(
+    latitude: f64,
+    longitude: f64,
+    format: CoordinateFormat,
+) -> Result<String> {
+    check_range(latitude, longitude)?;
+
+    match format {
+        CoordinateFormat::Decimal => Ok(format!("{latitude:.6}, {longitude:.6}")),
+        CoordinateFormat::Dms => LatLng {
+            lat: Decimal::from_f64(latitude).ok_or(Error::InvalidLatitude)?,
+            lng: Decimal::from_f64(longitude).ok_or(Error::InvalidLongitude)?,
+        }
+        .to_dms(),
+        CoordinateFormat::Ddm => Ok(format!(
+            "{} {}",
+            format_ddm(latitude, 'N', 'S'),
+            format_ddm(longitude, 'E', 'W')
+        )),
+        CoordinateFormat::Utm => {
+            let utm = Utm::from_wgs84(latitude, longitude)?;
+            Ok(format!(
+                "{}{} {:.0} {:.0}",
+                utm.zone,
+                utm.band,
+                utm.easting.floor(),
+                utm.northing.floor()
+            ))
+        }
+        CoordinateFormat::Mgrs => Utm::from_wgs84(latitude, longitude)?.to_mgrs(),
+        CoordinateFormat::PlusCode => Ok(encode_plus_code(latitude, longitude)),
+    }
+}
+
+fn format_ddm(value: f64, positive: char, negative: char) -> String {
+    // Round to the rendered precision first so that 59.9996' becomes the next degree.
+    let thousandths = (value.abs() * 60_000.0).round() as u64;
+    format!(
+        "{}°{}.{:03}' {}",
+        thousandths / 60_000,
+        thousandths % 60_000 / 1000,
+        thousandths % 1000,
+        if value < 0.0 { negative } else { positive }
+    )
+}
+
+/// Parse a WGS84 position in any of the supported notations.
+///
+/// Degree based notations accept hemisphere letters before or after each
+/// value, or signed values, with the two values separated by whitespace or a
+/// comma: `12.15, -68.28`, `12°9'0" N 68°16'48" W`, `N12 09.000 W068 16.800`.
+/// Plus Codes have to be full codes; short codes like `4PH9+2X Kralendijk`
+/// need a reference location and are rejected.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidCoordinates`] if the value cannot be parsed, or
+/// [`Error::InvalidLatitude`] or [`Error::InvalidLongitude`] if it is out of
+/// range.
+pub fn parse_coordinates(value: &str) -> Result<(f64, f64)> {
+    let compact: String = value
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase();
+
+    let (latitude, longitude) = if is_plus_code(value) {
+        decode_plus_code(&compact).ok_or_else(|| invalid(value, "not a full Plus Code"))?
+    } else if let Some(utm) = parse_utm(value) {
+        utm?.to_wgs84()
+    } else if let Some(mgrs) = parse_mgrs(&compact) {
+        mgrs.ok_or_else(|| invalid(value, "not a valid MGRS reference"))?
+            .to_wgs84()
+    } else {
+        parse_degrees(value)?
+    };
+
+    check_range(latitude, longitude)?;
+    Ok((latitude, longitude))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Hemisphere(char),
+}
+
+/// Parse degree based notations: decimal degrees, DMS and DDM.
+fn parse_degrees(value: &str) -> Result<(f64, f64)> {
+    let mut tokens = vec![];
+    let mut number = String::new();
+    let mut has_separator = false;
+    let flush = |number: &mut String, tokens: &mut Vec<Token>| -> Result<()> {
+        if !number.is_empty() {
+            let parsed = number
+                .parse()
+                .map_err(|_| invalid(value, "malformed number"))?;
+            tokens.push(Token::Number(parsed));
+            number.clear();
+        }
+        Ok(())
+    };
+
+    for c in value.chars() {
+        match c {
+            '0'..='9' | '.' => number.push(c),
+            '-' | '+' if number.is_empty() => number.push(c),
+            'N' | 'S' | 'E' | 'W' | 'n' | 's' | 'e' | 'w' => {
+                flush(&mut number, &mut tokens)?;
+                tokens.push(Token::Hemisphere(c.to_ascii_uppercase()));
+            }
+            ',' | ';' => {
+                flush(&mut number, &mut tokens)?;
+                has_separator = true;
+                tokens.push(Token::Hemisphere(','));
+            }
+            '°' | '\'' | '"' | '′' | '″' | '’' | '”' | 'º' => {
+                flush(&mut number, &mut tokens)?
+            }
+            c if c.is_whitespace() => flush(&mut number, &mut tokens)?,
+            _ => return Err(invalid(value, &format!("unexpected character `{c}`"))),
+        }
+    }
+    flush(&mut number, &mut tokens)?;
+
+    // Split into the latitude and longitude parts, each a list of numbers
+    // and an optional hemisphere.
+    let mut parts: Vec<(Vec<f64>, Option<char>)> = vec![];
+    let prefixed = matches!(tokens.first(), Some(Token::Hemisphere(c)) if *c != ',');
+    let mut current: (Vec<f64>, Option<char>) = (vec![], None);
+    for token in tokens {
+        match token {
+            Token::Number(number) => current.0.push(number),
+            Token::Hemisphere(',') => {
+                if !current.0.is_empty() {
+                    parts.push(std::mem::take(&mut current));
+                }
+            }
+            Token::Hemisphere(hemisphere) if prefixed => {
+                if !current.0.is_empty() {
+                    parts.push(std::mem::take(&mut current));
+                }
+                current.1 = Some(hemisphere);
+            }
+            Token::Hemisphere(hemisphere) => {
+                current.1 = Some(hemisphere);
+                parts.push(std::mem::take(&mut current));
+            }
+        }
+    }
+    if !current.0.is_empty() {
+        parts.push(current);
+    }
+
+    // Without separators or hemispheres, split the numbers in half.
+    if parts.len() == 1
+        && !has_separator
+        && parts[0].1.is_none()
+        && parts[0].0.len().is_multiple_of(2)
+    {
+        let mut numbers = parts.remove(0).0;
+        let longitude = numbers.split_off(numbers.len() / 2);
+        parts = vec![(numbers, None), (longitude, None)];
+    }
+
+    let [latitude, longitude] = <[_; 2]>::try_from(parts)
+        .map_err(|_| invalid(value, "expected a latitude and a longitude"))?;
+    // Hemispheres may also be given in the order longitude, latitude.
+    let (latitude, longitude) = match (latitude.1, longitude.1) {
+        (Some('E' | 'W'), Some('N' | 'S')) => (longitude, latitude),
+        _ => (latitude, longitude),
+    };
+
+    Ok((
+        degrees(value, latitude, ['N', 'S'])?,
+        degrees(value, longitude, ['E', 'W'])?,
+    ))
+}
+
+/// Combine degrees, minutes and seconds into signed decimal degrees.
+fn degrees(
+    value: &str,
+    (numbers, hemisphere): (Vec<f64>, Option<char>),
+    axis: [char; 2],
+) -> Result<f64> {
+    let (degrees, minutes, seconds) = match numbers[..] {
+        [degrees] => (degrees, 0.0, 0.0),
+        [degrees, minutes] => (degrees, minutes, 0.0),
+        [degrees, minutes, seconds] => (degrees, minutes, seconds),
+        _ => return Err(invalid(value, "expected degrees, minutes and seconds")),
+    };
+    if !(0.0..60.0).contains(&minutes) || !(0.0..60.0).contains(&seconds) {
+        return Err(invalid(value, "minutes and seconds must be below 60"));
+    }
+    let magnitude = degrees.abs() + minutes / 60.0 + seconds / 3600.0;
+
+    match hemisphere {
+        None if degrees.is_sign_negative() => Ok(-magnitude),
+        None => Ok(magnitude),
+        Some(_) if degrees.is_sign_negative() => {
+            Err(invalid(value, "negative value with a hemisphere"))
+        }
+        Some(h) if h == axis[0] => Ok(magnitude),
+        Some(h) if h == axis[1] => Ok(-magnitude),
+        Some(_) => Err(invalid(value, "hemispheres do not match")),
+    }
+}
+
+/// A position on the UTM grid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Utm {
+    zone: u8,
+    band: char,
+    easting: f64,
+    northing: f64,
+}
+
+/// Derived ellipsoid parameters: `e²` and `e'²`.
+fn eccentricities() -> (f64, f64) {
+    let e2 = FLATTENING * (2.0 - FLATTENING);
+    (e2, e2 / (1.0 - e2))
+}
+
+/// Central meridian of a UTM zone in degrees.
+fn central_meridian(zone: u8) -> f64 {
+    f64::from(zone) * 6.0 - 183.0
+}
+
+/// Meridional arc from the equator to latitude `phi` (radians) in meters.
+fn meridional_arc(phi: f64) -> f64 {
+    let (e2, _) = eccentricities();
+    let (e4, e6) = (e2 * e2, e2 * e2 * e2);
+    SEMI_MAJOR_AXIS
+        * ((1.0 - e2 / 4.0 - 3.0 * e4 / 64.0 - 5.0 * e6 / 256.0) * phi
+            - (3.0 * e2 / 8.0 + 3.0 * e4 / 32.0 + 45.0 * e6 / 1024.0) * (2.0 * phi).sin()
+            + (15.0 * e4 / 256.0 + 45.0 * e6 / 1024.0) * (4.0 * phi).sin()
+            - (35.0 * e6 / 3072.0) * (6.0 * phi).sin())
+}
+
+impl Utm {
+    /// Project a WGS84 position, including the Norway and Svalbard zone
+    /// exceptions.
+    fn from_wgs84(latitude: f64, longitude: f64) -> Result<Self> {
+        if !(-80.0..=84.0).contains(&latitude) {
+            return Err(Error::InvalidCoordinates(String::from(
+                "UTM does not cover the polar regions",
+            )));
+        }
+
+        let mut zone = (((longitude + 180.0) / 6.0).floor() as u8 % 60) + 1;
+        if (56.0..64.0).contains(&latitude) && (3.0..12.0).contains(&longitude) {
+            zone = 32;
+        }
+        if (72.0..=84.0).contains(&latitude) && longitude >= 0.0 {
+            zone = match longitude {
+                l if l < 9.0 => 31,
+                l if l < 21.0 => 33,
+                l if l < 33.0 => 35,
+                l if l < 42.0 => 37,
+                _ => zone,
+            };
+        }
+        let band = BANDS[(((latitude + 80.0) / 8.0).floor() as usize).min(BANDS.len() - 1)] as char;
+
+        let (e2, ep2) = eccentricities();
+        let phi = latitude.to_radians();
+        let n = SEMI_MAJOR_AXIS / (1.0 - e2 * phi.sin().powi(2)).sqrt();
+        let t = phi.tan().powi(2);
+        let c = ep2 * phi.cos().powi(2);
+        let mut delta = longitude - central_meridian(zone);
+        if delta > 180.0 {
+            delta -= 360.0;
+        } else if delta < -180.0 {
+            delta += 360.0;
+        }
+        let a = phi.cos() * delta.to_radians();
+
+        let easting = SCALE
+            * n
+            * (a + (1.0 - t + c) * a.powi(3) / 6.0
+                + (5.0 - 18.0 * t + t * t + 72.0 * c - 58.0 * ep2) * a.powi(5) / 120.0)
+            + FALSE_EASTING;
+        let mut northing = SCALE
+            * (meridional_arc(phi)
+                + n * phi.tan()
+                    * (a * a / 2.0
+                        + (5.0 - t + 9.0 * c + 4.0 * c * c) * a.powi(4) / 24.0
+                        + (61.0 - 58.0 * t + t * t + 600.0 * c - 330.0 * ep2) * a.powi(6) / 720.0));
+        if latitude < 0.0 {
+            northing += FALSE_NORTHING;
+        }
+
+        Ok(Self {
+            zone,
+            band,
+            easting,
+            northing,
+        })
+    }
+
+    fn southern(&self) -> bool {
+        self.band < 'N'
+    }
+
+    /// Convert back to a WGS84 position.
+    fn to_wgs84(self) -> (f64, f64) {
+        let (e2, ep2) = eccentricities();
+        let x = self.easting - FALSE_EASTING;
+        let y = if self.southern() {
+            self.northing - FALSE_NORTHING
+        } else {
+            self.northing
+        };
+
+        let e1 = (1.0 - (1.0 - e2).sqrt()) / (1.0 + (1.0 - e2).sqrt());
+        let mu = y
+            / SCALE
+            / (SEMI_MAJOR_AXIS
+                * (1.0 - e2 / 4.0 - 3.0 * e2 * e2 / 64.0 - 5.0 * e2 * e2 * e2 / 256.0));
+        let phi1 = mu
+            + (3.0 * e1 / 2.0 - 27.0 * e1.powi(3) / 32.0) * (2.0 * mu).sin()
+            + (21.0 * e1 * e1 / 16.0 - 55.0 * e1.powi(4) / 32.0) * (4.0 * mu).sin()
+            + (151.0 * e1.powi(3) / 96.0) * (6.0 * mu).sin()
+            + (1097.0 * e1.powi(4) / 512.0) * (8.0 * mu).sin();
+
+        let sin2 = phi1.sin().powi(2);
+        let n1 = SEMI_MAJOR_AXIS / (1.0 - e2 * sin2).sqrt();
+        let t1 = phi1.tan().powi(2);
+        let c1 = ep2 * phi1.cos().powi(2);
+        let r1 = SEMI_MAJOR_AXIS * (1.0 - e2) / (1.0 - e2 * sin2).powf(1.5);
+        let d = x / (n1 * SCALE);
+
+        let latitude = phi1
+            - (n1 * phi1.tan() / r1)
+                * (d * d / 2.0
+                    - (5.0 + 3.0 * t1 + 10.0 * c1 - 4.0 * c1 * c1 - 9.0 * ep2) * d.powi(4) / 24.0
+                    + (61.0 + 90.0 * t1 + 298.0 * c1 + 45.0 * t1 * t1
+                        - 252.0 * ep2
+                        - 3.0 * c1 * c1)
+                        * d.powi(6)
+                        / 720.0);
+        let longitude = (d - (1.0 + 2.0 * t1 + c1) * d.powi(3) / 6.0
+            + (5.0 - 2.0 * c1 + 28.0 * t1 - 3.0 * c1 * c1 + 8.0 * ep2 + 24.0 * t1 * t1)
+                * d.powi(5)
+                / 120.0)
+            / phi1.cos();
+
+        (
+            latitude.to_degrees(),
+            central_meridian(self.zone) + longitude.to_degrees(),
+        )
+    }
+
+    /// Render as an MGRS reference with 1 m precision.
+    fn to_mgrs(self) -> Result<String> {
+        let set = usize::from((self.zone - 1) % 3);
+        let column = (self.easting / 100_000.0).floor() as usize;
+        let column = MGRS_COLUMNS[set]
+            .get(column.wrapping_sub(1))
+            .ok_or_else(|| {
+                Error::InvalidCoordinates(String::from("easting outside of the zone"))
+            })?;
+        let offset = if self.zone.is_multiple_of(2) { 5 } else { 0 };
+        let row =
+            MGRS_ROWS[((self.northing / 100_000.0).floor() as usize + offset) % MGRS_ROWS.len()];
+
+        Ok(format!(
+            "{}{} {}{} {:05} {:05}",
+            self.zone,
+            self.band,
+            *column as char,
+            row as char,
+            (self.easting.floor() as u64) % 100_000,
+            (self.northing.floor() as u64) % 100_000
+        ))
+    }
+}
+
+/// Split a leading UTM zone and latitude band, e.g. `19P` from `19PDP…`.
+fn zone_and_band(value: &str) -> Option<(u8, char, &str)> {
+    let digits = value.chars().take_while(char::is_ascii_digit).count();
+    if !(1..=2).contains(&digits) {
+        return None;
+    }
+    let zone: u8 = value[..digits]
+        .parse()
+        .ok()
+        .filter(|z| (1..=60).contains(z))?;
+    let band = value[digits..].chars().next()?.to_ascii_uppercase();
+    (band.is_ascii() && BANDS.contains(&(band as u8))).then(|| (zone, band, &value[digits + 1..]))
+}
+
+/// Parse `19P 578336 1343244` (easting and northing in meters).
+///
+/// Returns `None` if the value does not look like a UTM position at all.
+fn parse_utm(value: &str) -> Option<Result<Utm>> {
+    let parts: Vec<&str> = value.split_whitespace().collect();
+    let (zone, band, easting, northing) = match parts[..] {
+        [zone_band, easting, northing] => {
+            let (zone, band, rest) = zone_and_band(zone_band)?;
+            if !rest.is_empty() {
+                return None;
+            }
+            (zone, band, easting, northing)
+        }
+        [zone, band, easting, northing] if band.len() == 1 => {
+            let (zone, band, _) = zone_and_band(&format!("{zone}{band}"))?;
+            (zone, band, easting, northing)
+        }
+        _ => return None,
+    };
+    let easting: f64 = easting.parse().ok()?;
+    let northing: f64 = northing.parse().ok()?;
+
+    Some(
+        if (100_000.0..1_000_000.0).contains(&easting) && (0.0..10_000_000.0).contains(&northing) {
+            Ok(Utm {
+                zone,
+                band,
+                easting,
+                northing,
+            })
+        } else {
+            Err(invalid(value, "easting or northing out of range"))
+        },
+    )
+}
+
+/// Parse a whitespace-free MGRS reference, e.g. `19PEP7833643244`.
+///
+/// Returns `None` if the value does not look like MGRS, and `Some(None)` if
+/// it does but is invalid.
+fn parse_mgrs(value: &str) -> Option<Option<Utm>> {
+    let (zone, band, rest) = zone_and_band(value)?;
+    let mut chars = rest.chars();
+    let (column, row) = (chars.next()?, chars.next()?);
+    let digits = chars.as_str();
+    if !column.is_ascii_alphabetic()
+        || !row.is_ascii_alphabetic()
+        || !digits.chars().all(|c| c.is_ascii_digit())
+        || digits.len() % 2 != 0
+        || digits.len() > 10
+    {
+        return None;
+    }
+
+    Some((|| {
+        let set = usize::from((zone - 1) % 3);
+        let column = MGRS_COLUMNS[set]
+            .iter()
+            .position(|c| *c as char == column)?
+            + 1;
+        let offset = if zone % 2 == 0 { 5 } else { 0 };
+        let row = (MGRS_ROWS.iter().position(|c| *c as char == row)? + MGRS_ROWS.len() - offset)
+            % MGRS_ROWS.len();
+
+        // Scale the digits to meters and take the center of the square.
+        let precision = digits.len() / 2;
+        let scale = 10f64.powi(5 - precision as i32);
+        let (easting, northing) = digits.split_at(precision);
+        let parse = |digits: &str| digits.parse::<f64>().unwrap_or(0.0) * scale + scale / 2.0;
+        let easting = column as f64 * 100_000.0 + parse(easting);
+        let mut northing = row as f64 * 100_000.0 + parse(northing);
+
+        // Row letters repeat every 2000 km; use the band to pick the cycle.
+        let band_index = BANDS.iter().position(|b| *b as char == band)?;
+        let band_latitude = band_index as f64 * 8.0 - 80.0;
+        let band_northing = Utm::from_wgs84(band_latitude, central_meridian(zone))
+            .ok()?
+            .northing;
+        // The southern edge of the band on the central meridian bounds the
+        // northing from below, with a margin for positions at the zone edges.
+        let minimum = band_northing - 100_000.0;
+        while northing < minimum.max(0.0) {
+            northing += 2_000_000.0;
+        }
+
+        Some(Utm {
+            zone,
+            band,
+            easting,
+            northing,
+        })
+    })())
+}
+
+/// Encode a position as a 10 digit Open Location Code.
+fn encode_plus_code(latitude: f64, longitude: f64) -> String {
+    // Latitude 90 has no cell of its own.
+    let latitude = latitude.min(90.0 - OLC_RESOLUTION / 2.0) + 90.0;
+    let longitude = (longitude + 180.0).rem_euclid(360.0);
+    let mut latitude = (latitude / OLC_RESOLUTION).floor() as usize;
+    let mut longitude = (longitude / OLC_RESOLUTION).floor() as usize;
+
+    let mut pairs = vec![];
+    for _ in 0..OLC_LENGTH / 2 {
+        pairs.push([OLC_ALPHABET[latitude % 20], OLC_ALPHABET[longitude % 20]]);
+        latitude /= 20;
+        longitude /= 20;
+    }
+
+    let mut code: String = pairs.iter().rev().flatten().map(|c| *c as char).collect();
+    code.insert(8, '+');
+    code
+}
+
+/// Whether a value starts with something shaped like a full or short Plus
+/// Code, i.e. up to eight code digits, a `+` and up to seven more digits.
+///
+/// Signed decimal degrees like `12.15, +68.28` also contain a `+`, but no
+/// run of code digits right before it.
+fn is_plus_code(value: &str) -> bool {
+    let Some(code) = value.split_whitespace().next() else {
+        return false;
+    };
+    let code = code.to_uppercase();
+    let Some((head, tail)) = code.split_once('+') else {
+        return false;
+    };
+    let digit = |c: u8| OLC_ALPHABET.contains(&c);
+    (2..=8).contains(&head.len())
+        && head.bytes().all(|c| digit(c) || c == b'0')
+        && tail.len() <= 7
+        && tail.bytes().all(digit)
+}
+
+/// Decode the center of a full Open Location Code with at least 10 digits.
+fn decode_plus_code(code: &str) -> Option<(f64, f64)> {
+    let (head, tail) = code.split_once('+')?;
+    if head.len() != 8 || tail.len() < 2 {
+        return None;
+    }
+    let digits = head
+        .chars()
+        .chain(tail.chars().take(2))
+        .map(|c| OLC_ALPHABET.iter().position(|d| *d as char == c))
+        .collect::<Option<Vec<usize>>>()?;
+
+    let (mut latitude, mut longitude, mut resolution) = (0.0, 0.0, 20.0);
+    for pair in digits.chunks(2) {
+        latitude += pair[0] as f64 * resolution;
+        longitude += pair[1] as f64 * resolution;
+        resolution /= 20.0;
+    }
+    if latitude >= 180.0 || longitude >= 360.0 {
+        return None;
+    }
+    Some((
+        latitude - 90.0 + OLC_RESOLUTION / 2.0,
+        longitude - 180.0 + OLC_RESOLUTION / 2.0,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(expected: (f64, f64), actual: (f64, f64), tolerance: f64) {
+        assert!(
+            (expected.0 - actual.0).abs() < tolerance && (expected.1 - actual.1).abs() < tolerance,
+            "expected {expected:?}, got {actual:?}"
+        );
+    }
+
+    #[test]
+    fn test_format_coordinates() {
+        let format = |format| format_coordinates(12.15, -68.28, format).unwrap();
+        assert_eq!("12.150000, -68.280000", format(CoordinateFormat::Decimal));
+        assert_eq!(r#"12°9'0" N 68°16'48" W"#, format(CoordinateFormat::Dms));
+        assert_eq!("12°9.000' N 68°16.800' W", format(CoordinateFormat::Ddm));
+        assert_eq!(
+            "0°0.000' N 0°59.999' W",
+            format_coordinates(0.0, -0.99999, CoordinateFormat::Ddm).unwrap()
+        );
+        assert_eq!(
+            "1°0.000' N 0°0.000' E",
+            format_coordinates(0.9999999, 0.0, CoordinateFormat::Ddm).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_degrees() {
+        let expected = (12.15, -68.28);
+        for value in [
+            "12.15, -68.28",
+            "12.15 -68.28",
+            "12.15N 68.28W",
+            r#"12°9'0" N 68°16'48" W"#,
+            "N 12° 09.000' W 068° 16.800'",
+            "N12 09.000 W068 16.800",
+            "12 9 0 N, 68 16 48 W",
+            "68°16.8' W 12°9' N",
+        ] {
+            assert_close(expected, parse_coordinates(value).unwrap(), 1e-9);
+        }
+
+        assert!(parse_coordinates("12.15").is_err());
+        assert!(parse_coordinates("12°75' N 68° W").is_err());
+        assert!(parse_coordinates("-12.15 N 68.28 W").is_err());
+        assert!(parse_coordinates("12.15 N 68.28 S").is_err());
+        assert!(parse_coordinates("95, 10").is_err());
+    }
+
+    #[test]
+    fn test_utm() {
+        // Washington Monument, given as 18S UJ 23487 06483 in the MGRS
+        // documentation from coordinates rounded to about 10 m.
+        let utm = Utm::from_wgs84(38.8895, -77.0352).unwrap();
+        assert_eq!((18, 'S'), (utm.zone, utm.band));
+        assert_eq!(
+            "18S UJ 23486 06483",
+            format_coordinates(38.8895, -77.0352, CoordinateFormat::Mgrs).unwrap()
+        );
+
+        for (latitude, longitude) in [
+            (12.15, -68.28),
+            (-8.2, 124.5),
+            (60.5, 5.5),
+            (-33.86, 151.21),
+        ] {
+            let utm = Utm::from_wgs84(latitude, longitude).unwrap();
+            assert_close((latitude, longitude), utm.to_wgs84(), 1e-7);
+
+            let rendered = format_coordinates(latitude, longitude, CoordinateFormat::Utm).unwrap();
+            assert_close(
+                (latitude, longitude),
+                parse_coordinates(&rendered).unwrap(),
+                1e-4,
+            );
+            let rendered = format_coordinates(latitude, longitude, CoordinateFormat::Mgrs).unwrap();
+            assert_close(
+                (latitude, longitude),
+                parse_coordinates(&rendered).unwrap(),
+                1e-4,
+            );
+        }
+
+        // Norway exception.
+        assert_eq!(32, Utm::from_wgs84(60.5, 5.5).unwrap().zone);
+        assert!(format_coordinates(85.0, 0.0, CoordinateFormat::Utm).is_err());
+        assert!(parse_coordinates("19P 1469696 1343183").is_err());
+        // `Ő` would be a `P` if truncated to a byte.
+        assert!(parse_coordinates("19Ő 578336 1343244").is_err());
+        assert!(parse_coordinates("19ŐDP 78336 43244").is_err());
+    }
+
+    #[test]
+    fn test_plus_codes() {
+        assert_eq!("8FVC2222+22", encode_plus_code(47.0000625, 8.0000625));
+        assert_close(
+            (47.0000625, 8.0000625),
+            parse_coordinates("8fvc2222+22").unwrap(),
+            1e-9,
+        );
+
+        let code = format_coordinates(-8.2, 124.5, CoordinateFormat::PlusCode).unwrap();
+        assert_close(
+            (-8.2, 124.5),
+            parse_coordinates(&code).unwrap(),
+            OLC_RESOLUTION,
+        );
+        assert!(parse_coordinates("4PH9+2X Kralendijk").is_err());
+    }
+
+    #[test]
+    fn test_signed_degrees_are_not_plus_codes() {
+        assert!(is_plus_code("8FVC2222+22"));
+        assert!(is_plus_code("4ph9+2x Kralendijk"));
+        assert!(!is_plus_code("12.15, +68.28"));
+        assert!(!is_plus_code("+12.15 +68.28"));
+        assert_close(
+            (12.15, 68.28),
+            parse_coordinates("12.15, +68.28").unwrap(),
+            1e-9,
+        );
+        assert_close(
+            (12.15, 68.28),
+            parse_coordinates("12.15 +68.28").unwrap(),
+            1e-9,
+        );
+    }
+}
@@ -0,0 +1,56 @@
+//! Disjoint-set (union-find) forest for grouping items transitively.
+
+use std::collections::BTreeMap;
+
+/// Disjoint sets of the indices `0..len`, initially each in a set of its own.
+///
+/// # Examples
+///
+/// ```
+/// use macdive_toolbox_core::util::disjoint_set::DisjointSet;
+///
+/// let mut sets = DisjointSet::new(4);
+/// sets.union(0, 2);
+/// sets.union(2, 3);
+/// assert_eq!(sets.root(3), sets.root(0));
+/// assert_eq!(vec![vec![0, 2, 3], vec![1]], sets.groups());
+/// ```
+#[derive(Debug, Clone)]
+pub struct DisjointSet {
+    parents: Vec<usize>,
+}
+
+impl DisjointSet {
+    /// Create `len` sets of a single index each.
+    pub fn new(len: usize) -> Self {
+        Self {
+            parents: (0..len).collect(),
+        }
+    }
+
+    /// The index representing the set that contains `index`.
+    pub fn root(&mut self, mut index: usize) -> usize {
+        while self.parents[index] != index {
+            self.parents[index] = self.parents[self.parents[index]];
+            index = self.parents[index];
+        }
+        index
+    }
+
+    /// Merge the set containing `b` into the set containing `a`, whose root
+    /// stays the root of the merged set.
+    pub fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.root(a), self.root(b));
+        self.parents[b] = a;
+    }
+
+    /// All sets with their indices in ascending order, ordered by their root.
+    pub fn groups(&mut self) -> Vec<Vec<usize>> {
+        let mut groups = BTreeMap::<usize, Vec<usize>>::new();
+        for index in 0..self.parents.len() {
+            let root = self.root(index);
+            groups.entry(root).or_default().push(index);
+        }
+        groups.into_values().collect()
+    }
+}
@@ -0,0 +1,88 @@
+use crate::error::{Error, Result};
+
+/// Substitute `{placeholder}` occurrences in `template` with values from `lookup`.
+///
+/// Literal braces can be written as `{{` and `}}`. The `lookup` closure
+/// returns `None` for unknown placeholder names, which is reported as a
+/// configuration error so that typos in format strings do not go unnoticed.
+///
+/// # Errors
+///
+/// Returns [`Error::Config`] for unknown placeholders or unbalanced braces.
+///
+/// # Examples
+///
+/// ```
+/// use macdive_toolbox_core::util::format::format_placeholders;
+///
+/// let result = format_placeholders("{{{name}}}", |key| match key {
+///     "name" => Some("Salt Pier".to_string()),
+///     _ => None,
+/// });
+/// assert_eq!("{Salt Pier}", result.unwrap());
+/// ```
+pub fn format_placeholders(
+    template: &str,
+    mut lookup: impl FnMut(&str) -> Option<String>,
+) -> Result<String> {
+    let mut output = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                output.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                output.push('}');
+            }
+            '{' => {
+                let mut name = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    name.push(c);
+                }
+                if !closed {
+                    return Err(Error::Config(format!(
+                        "unbalanced `{{` in format string `{template}`"
+                    )));
+                }
+                let value = lookup(name.trim()).ok_or_else(|| {
+                    Error::Config(format!("unknown placeholder `{{{name}}}` in `{template}`"))
+                })?;
+                output.push_str(&value);
+            }
+            '}' => {
+                return Err(Error::Config(format!(
+                    "unbalanced `}}` in format string `{template}`"
+                )));
+            }
+            c => output.push(c),
+        }
+    }
+
+    Ok(output)
+}
+
+/// Make `value` safe for use as a single file or folder name.
+///
+/// Replaces path separators and characters that are invalid on common file
+/// systems with `-`, and strips leading dots and surrounding whitespace.
+pub fn sanitize_file_name(value: &str) -> String {
+    let sanitized: String = value
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '-',
+            c if c.is_control() => '-',
+            c => c,
+        })
+        .collect();
+
+    sanitized.trim().trim_start_matches('.').trim().to_string()
+}
@@ -0,0 +1,82 @@
+/// Mean earth radius in meters, as used by the haversine formula.
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// Great-circle distance in meters between two WGS84 positions.
+///
+/// # Examples
+///
+/// ```
+/// use macdive_toolbox_core::util::geo::haversine_distance;
+///
+/// // One degree of latitude is roughly 111 km.
+/// let distance = haversine_distance(0.0, 0.0, 1.0, 0.0);
+/// assert!((distance - 111_195.0).abs() < 1.0);
+/// ```
+pub fn haversine_distance(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let delta_phi = (lat2 - lat1).to_radians();
+    let delta_lambda = (lon2 - lon1).to_radians();
+
+    let a = (delta_phi / 2.0).sin().powi(2)
+        + phi1.cos() * phi2.cos() * (delta_lambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().asin()
+}
+
+/// Convex hull of a set of `(longitude, latitude)` points, counter-clockwise
+/// and without repeating the first vertex.
+///
+/// Uses Andrew's monotone chain on plain coordinates, which is fine for areas
+/// that do not cross the antimeridian.
+pub fn convex_hull(points: &[(f64, f64)]) -> Vec<(f64, f64)> {
+    let mut points = points.to_vec();
+    points.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+
+    let cross = |o: (f64, f64), a: (f64, f64), b: (f64, f64)| {
+        (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+    };
+    let mut hull: Vec<(f64, f64)> = Vec::with_capacity(points.len() * 2);
+    for pass in [points.clone(), points.into_iter().rev().collect()] {
+        let start = hull.len();
+        for point in pass {
+            while hull.len() >= start + 2
+                && cross(hull[hull.len() - 2], hull[hull.len() - 1], point) <= 0.0
+            {
+                hull.pop();
+            }
+            hull.push(point);
+        }
+        // The last point of each chain is the first point of the other one.
+        hull.pop();
+    }
+    hull
+}
+
+/// Convex hull around circles of `buffer` meters drawn around each point.
+///
+/// Each circle is approximated with 16 vertices, so a single point yields a
+/// 16-sided polygon instead of an empty one.
+pub fn buffered_hull(points: &[(f64, f64)], buffer: f64) -> Vec<(f64, f64)> {
+    const SEGMENTS: usize = 16;
+    let meters_per_degree = EARTH_RADIUS.to_radians();
+
+    let circles: Vec<(f64, f64)> = points
+        .iter()
+        .flat_map(|&(longitude, latitude)| {
+            let delta_latitude = buffer / meters_per_degree;
+            let delta_longitude =
+                buffer / (meters_per_degree * latitude.to_radians().cos().max(0.01));
+            (0..SEGMENTS).map(move |i| {
+                let angle = std::f64::consts::TAU * i as f64 / SEGMENTS as f64;
+                (
+                    longitude + delta_longitude * angle.cos(),
+                    latitude + delta_latitude * angle.sin(),
+                )
+            })
+        })
+        .collect();
+    convex_hull(&circles)
+}
@@ -1,2 +1,6 @@
+pub mod coordinates;
+pub mod disjoint_set;
+pub mod format;
 pub mod fs;
+pub mod geo;
 pub mod rate_limit;
@@ -0,0 +1,29 @@
+//! Core Data entity registry of the MacDive database (read-only).
+//!
+//! Core Data numbers the entities of a model and uses those numbers in the
+//! names of many-to-many join tables (e.g. `Z_3RELATIONSHIPDIVETOCRITTER`).
+//! The numbers depend on the model version, so they are looked up here by
+//! entity name instead of being hardcoded.
+
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "Z_PRIMARYKEY")]
+pub struct Model {
+    /// Entity number, as stored in `Z_ENT` of the entity's rows.
+    #[sea_orm(primary_key, auto_increment = false, column_name = "Z_ENT")]
+    pub ent: i64,
+    /// Entity name, e.g. `Dive` or `Critter`.
+    #[sea_orm(column_name = "Z_NAME")]
+    pub name: Option<String>,
+    #[sea_orm(column_name = "Z_SUPER")]
+    pub super_ent: Option<i64>,
+    /// Highest primary key handed out for the entity.
+    #[sea_orm(column_name = "Z_MAX")]
+    pub max: Option<i64>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,41 @@
+//! MacDive dive entity (read-only).
+
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "ZDIVE")]
+pub struct Model {
+    #[sea_orm(primary_key, column_name = "Z_PK")]
+    pub id: i64,
+    #[sea_orm(column_name = "Z_ENT")]
+    pub ent: Option<i64>,
+    #[sea_orm(column_name = "Z_OPT")]
+    pub opt: Option<i64>,
+    #[sea_orm(column_name = "ZDIVENUMBER")]
+    pub number: Option<i64>,
+    #[sea_orm(column_name = "ZRELATIONSHIPDIVETODIVESITE")]
+    pub site: Option<i64>,
+    /// Local wall-clock start time, stored like an NSDate (seconds since 2001-01-01)
+    /// but without time zone adjustment.
+    #[sea_orm(column_name = "ZRAWDATE")]
+    pub raw_date: Option<f64>,
+    /// Seconds since 2001-01-01 (Apple NSDate epoch). Convert to `chrono::DateTime` in the domain layer.
+    #[sea_orm(column_name = "ZDATE")]
+    pub date: Option<f64>,
+    /// Dive time in seconds.
+    #[sea_orm(column_name = "ZDURATION")]
+    pub duration: Option<f64>,
+    #[sea_orm(column_name = "ZMAXDEPTH")]
+    pub max_depth: Option<f64>,
+    #[sea_orm(column_name = "ZTIMEZONE")]
+    pub timezone: Option<String>,
+    #[sea_orm(column_name = "ZNOTES")]
+    pub notes: Option<String>,
+    #[sea_orm(column_name = "ZUUID")]
+    pub uuid: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,29 @@
+//! MacDive dive/critter sightings (read-only).
+//!
+//! Core Data stores the many-to-many relationship between dives and critters
+//! in a join table without a primary key of its own. The table and column
+//! names contain the entity numbers of `Dive` and `Critter` (see
+//! [`crate::core_data_entity`]), so rows are read with [`Model::select`]
+//! rather than through a fixed entity.
+
+use sea_orm::FromQueryResult;
+
+#[derive(Clone, Debug, PartialEq, FromQueryResult)]
+pub struct Model {
+    /// Primary key of the dive (`ZDIVE.Z_PK`).
+    pub dive: i64,
+    /// Primary key of the critter (`ZCRITTER.Z_PK`).
+    pub critter: i64,
+}
+
+impl Model {
+    /// SQL selecting all sightings, given the Core Data entity numbers of
+    /// `Dive` and `Critter`.
+    pub fn select(dive_ent: i64, critter_ent: i64) -> String {
+        format!(
+            "SELECT Z_{dive_ent}RELATIONSHIPCRITTERTODIVE AS dive, \
+             Z_{critter_ent}RELATIONSHIPDIVETOCRITTER AS critter \
+             FROM Z_{dive_ent}RELATIONSHIPDIVETOCRITTER"
+        )
+    }
+}
@@ -0,0 +1,25 @@
+//! `SeaORM` Entity for cached reverse geocoding results.
+
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel)]
+#[sea_orm(table_name = "geocode_cache")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub site_uuid: String,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub provider: String,
+    pub country: String,
+    pub iso_country_code: String,
+    pub state: Option<String>,
+    pub region: Option<String>,
+    pub locality: Option<String>,
+    pub geocoded_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {}
+
+impl ActiveModelBehavior for ActiveModel {}
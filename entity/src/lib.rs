@@ -2,8 +2,12 @@
 
 pub mod prelude;
 
+pub mod core_data_entity;
 pub mod critter;
 pub mod critter_category;
+pub mod dive;
+pub mod dive_critter;
 pub mod dive_site;
+pub mod geocode_cache;
 pub mod taxon_cache;
 pub mod verified_name;
@@ -1,7 +1,10 @@
 //! Re-exports of all SeaORM entity types for convenient use by consumers.
 
+pub use super::core_data_entity::Entity as CoreDataEntity;
 pub use super::critter::Entity as Critter;
 pub use super::critter_category::Entity as CritterCategory;
+pub use super::dive::Entity as Dive;
 pub use super::dive_site::Entity as DiveSite;
+pub use super::geocode_cache::Entity as GeocodeCache;
 pub use super::taxon_cache::Entity as TaxonCache;
 pub use super::verified_name::Entity as VerifiedName;
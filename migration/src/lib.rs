@@ -2,6 +2,7 @@ pub use sea_orm_migration::prelude::*;
 
 mod m20220101_000001_create_table;
 mod m20230320_162727_inaturalist_cache;
+mod m20261016_090000_geocode_cache;
 
 pub struct Migrator;
 
@@ -11,6 +12,7 @@ impl MigratorTrait for Migrator {
         vec![
             Box::new(m20220101_000001_create_table::Migration),
             Box::new(m20230320_162727_inaturalist_cache::Migration),
+            Box::new(m20261016_090000_geocode_cache::Migration),
         ]
     }
 }
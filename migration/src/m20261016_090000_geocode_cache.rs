@@ -0,0 +1,73 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(GeocodeCache::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(GeocodeCache::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(GeocodeCache::SiteUuid).string().not_null())
+                    .col(ColumnDef::new(GeocodeCache::Latitude).double().not_null())
+                    .col(ColumnDef::new(GeocodeCache::Longitude).double().not_null())
+                    .col(ColumnDef::new(GeocodeCache::Provider).string().not_null())
+                    .col(ColumnDef::new(GeocodeCache::Country).string().not_null())
+                    .col(
+                        ColumnDef::new(GeocodeCache::IsoCountryCode)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(GeocodeCache::State).string())
+                    .col(ColumnDef::new(GeocodeCache::Region).string())
+                    .col(ColumnDef::new(GeocodeCache::Locality).string())
+                    .col(
+                        ColumnDef::new(GeocodeCache::GeocodedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .index(
+                        Index::create()
+                            .unique()
+                            .col(GeocodeCache::SiteUuid)
+                            .col(GeocodeCache::Latitude)
+                            .col(GeocodeCache::Longitude),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(GeocodeCache::Table).to_owned())
+            .await
+    }
+}
+
+/// Learn more at https://docs.rs/sea-query#iden
+#[derive(Iden)]
+enum GeocodeCache {
+    Table,
+    Id,
+    SiteUuid,
+    Latitude,
+    Longitude,
+    Provider,
+    Country,
+    IsoCountryCode,
+    State,
+    Region,
+    Locality,
+    GeocodedAt,
+}
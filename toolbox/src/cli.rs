@@ -1,9 +1,11 @@
-use std::collections::HashMap;
 use std::path::PathBuf;
 
 use clap::{ArgAction, ColorChoice, ValueHint};
-use macdive_toolbox_core::domain::{ApplicationConfig, CritterConfig};
+use macdive_toolbox_core::domain::{ApplicationConfig, GeocodingConfig, GeocodingProvider};
+use macdive_toolbox_core::parsers::sites::SiteFileFormat;
+use macdive_toolbox_core::services::geocoding::CachedGeocoder;
 use macdive_toolbox_core::services::mtp::DeviceSelector;
+use sea_orm::DbConn;
 
 use crate::errors::PathError;
 
@@ -55,10 +57,7 @@ impl Cli {
                 use macdive_toolbox_core::config::load_config;
                 Ok(load_config(path)?)
             }
-            None => Ok(ApplicationConfig {
-                locations: HashMap::new(),
-                critters: CritterConfig::default(),
-            }),
+            None => Ok(ApplicationConfig::default()),
         }
     }
 }
@@ -81,25 +80,281 @@ pub(crate) enum Commands {
         #[clap(flatten)]
         options: MtpOptions,
     },
+    Xmp {
+        #[clap(subcommand)]
+        command: XmpCommands,
+    },
+    Sites {
+        #[clap(subcommand)]
+        command: SiteCommands,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+pub(crate) enum SiteCommands {
+    /// Show which dive sites each location override captures and where overrides overlap
+    CheckOverrides {
+        /// Write the override polygons and dive sites to this GeoJSON file
+        #[clap(long, value_hint=ValueHint::FilePath)]
+        geojson: Option<PathBuf>,
+    },
+    /// Propose location overrides for clusters of nearby dive sites
+    SuggestOverrides(SuggestOverridesOptions),
+    /// Detect the body of water of dive sites from marine region polygons
+    BodyOfWater {
+        /// GeoJSON file with named marine regions (overrides the configuration file)
+        #[clap(long, value_hint=ValueHint::FilePath)]
+        marine_regions: Option<PathBuf>,
+        /// Also list sites whose body of water matches the detected one
+        #[clap(long)]
+        all: bool,
+    },
+    /// Export dive sites as GPX, KML, GeoJSON or Subsurface dive sites
+    Export(SiteExportOptions),
+    /// Convert a GPX, KML or CSV site list into a MacDive import file, skipping known sites
+    PrepareImport(SiteImportOptions),
+    /// List near-duplicate dive sites that are candidates for merging
+    Duplicates {
+        /// Maximum distance in meters between duplicate sites
+        #[clap(short, long, default_value_t = 100.0)]
+        distance: f64,
+        /// Minimum name similarity of duplicate sites, between 0 and 1
+        #[clap(short, long, default_value_t = 0.8)]
+        similarity: f64,
+        /// Output format of the report
+        #[clap(long, default_value = "table")]
+        #[arg(value_enum)]
+        format: SummaryFormat,
+    },
+    /// Report data-quality problems of all dive sites
+    Validate {
+        /// Output format of the report
+        #[clap(long, default_value = "table")]
+        #[arg(value_enum)]
+        format: SummaryFormat,
+        /// Compare the stored country with the reverse geocoded one
+        #[clap(long)]
+        check_country: bool,
+        #[clap(flatten)]
+        geocoder: GeocoderOptions,
+    },
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub(crate) enum SiteExportFormat {
+    Gpx,
+    Kml,
+    Geojson,
+    /// Subsurface logbook with only the dive sites
+    Subsurface,
+}
+
+#[derive(Debug, clap::Args)]
+pub(crate) struct SiteExportOptions {
+    /// Format of the exported file
+    #[clap(short, long)]
+    #[arg(value_enum)]
+    pub(crate) format: SiteExportFormat,
+    /// Write the sites to this file instead of printing them
+    #[clap(short, long, value_hint=ValueHint::FilePath)]
+    pub(crate) output: Option<PathBuf>,
+    #[clap(flatten)]
+    pub(crate) geocoder: GeocoderOptions,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub(crate) enum SiteImportFormat {
+    Gpx,
+    Kml,
+    Csv,
+}
+
+impl From<SiteImportFormat> for SiteFileFormat {
+    fn from(format: SiteImportFormat) -> Self {
+        match format {
+            SiteImportFormat::Gpx => SiteFileFormat::Gpx,
+            SiteImportFormat::Kml => SiteFileFormat::Kml,
+            SiteImportFormat::Csv => SiteFileFormat::Csv,
+        }
+    }
+}
+
+#[derive(Debug, clap::Args)]
+pub(crate) struct SiteImportOptions {
+    /// Format of the site list, detected from the file extension if omitted
+    #[clap(long)]
+    #[arg(value_enum)]
+    pub(crate) input_format: Option<SiteImportFormat>,
+    /// Maximum distance in meters to an existing site with a similar name
+    #[clap(long, default_value_t = 100.0)]
+    pub(crate) max_distance: f64,
+    /// Minimum name similarity to an existing site nearby, between 0 and 1
+    #[clap(long, default_value_t = 0.8)]
+    pub(crate) similarity: f64,
+    /// Path to the GPX, KML or CSV site list
+    #[clap(short, long, value_hint=ValueHint::FilePath)]
+    pub(crate) source: PathBuf,
+    /// Path of the MacDive import file to write
+    #[clap(short, long, value_hint=ValueHint::FilePath)]
+    pub(crate) dest: PathBuf,
+    #[clap(flatten)]
+    pub(crate) geocoder: GeocoderOptions,
+}
+
+#[derive(Debug, clap::Args)]
+pub(crate) struct SuggestOverridesOptions {
+    /// Maximum distance in meters between neighbouring sites of a cluster
+    #[clap(short, long, default_value_t = 5000.0)]
+    pub(crate) distance: f64,
+    /// Distance in meters the polygons extend beyond the outermost sites
+    #[clap(short, long, default_value_t = 1000.0)]
+    pub(crate) buffer: f64,
+    /// Include sites already covered by a location override
+    #[clap(long)]
+    pub(crate) all: bool,
+    /// Write the proposals to this file instead of printing them
+    #[clap(short, long, value_hint=ValueHint::FilePath)]
+    pub(crate) output: Option<PathBuf>,
+    /// Also write the proposed polygons and their sites to this GeoJSON file
+    #[clap(long, value_hint=ValueHint::FilePath)]
+    pub(crate) geojson: Option<PathBuf>,
+    #[clap(flatten)]
+    pub(crate) geocoder: GeocoderOptions,
 }
 
 #[derive(clap::Subcommand, Debug)]
 pub(crate) enum LightroomCommands {
-    ExportSites {
-        /// Force export and overwrite all existing files
-        #[clap(short, long)]
-        force: bool,
+    ExportSites(ExportSitesOptions),
+    /// Remove presets written by this tool whose dive site no longer exists in MacDive
+    PruneSites {
+        /// Remove the orphaned presets instead of only listing them
+        #[clap(long)]
+        apply: bool,
+        /// Move orphaned presets into this folder instead of deleting them
+        #[clap(short, long, value_hint=ValueHint::DirPath)]
+        quarantine: Option<PathBuf>,
+    },
+    /// List catalog photos taken on a dive whose location is missing or wrong
+    AuditCatalog {
+        /// Path to the Lightroom Classic catalog (.lrcat)
+        #[clap(value_hint=ValueHint::FilePath)]
+        catalog: PathBuf,
+        /// How far the camera clock is ahead of the dive computer ([+|-]HH:MM[:SS])
+        #[clap(long, default_value = "00:00", allow_hyphen_values = true)]
+        clock_offset: String,
+        /// Match photos taken up to this many minutes before or after a dive
+        #[clap(short, long, default_value_t = 60)]
+        tolerance: u32,
+        /// Maximum distance in meters between a photo's GPS position and the dive site
+        #[clap(long, default_value_t = 2000.0)]
+        max_distance: f64,
+        /// Output format of the worklist
+        #[clap(long, default_value = "table")]
+        #[arg(value_enum)]
+        format: SummaryFormat,
     },
 }
 
+#[derive(Debug, clap::Args)]
+pub(crate) struct ExportSitesOptions {
+    /// Force export and overwrite all existing files
+    #[clap(short, long)]
+    pub(crate) force: bool,
+    /// Only show which presets would be created or changed
+    #[clap(short = 'n', long)]
+    pub(crate) dry_run: bool,
+    /// Skip dive sites that cannot be converted or geocoded instead of aborting,
+    /// and keep sites whose elevation cannot be looked up
+    #[clap(long)]
+    pub(crate) lenient: bool,
+    /// Output format of the summary
+    #[clap(long, default_value = "table")]
+    #[arg(value_enum)]
+    pub(crate) format: SummaryFormat,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub(crate) enum SummaryFormat {
+    Table,
+    Json,
+}
+
 #[derive(Clone, Debug, clap::Args)]
 pub(crate) struct LightroomOptions {
     /// Path to the Lightroom Settings directory
     #[clap(short, long, value_hint=ValueHint::DirPath)]
     lightroom: Option<PathBuf>,
-    /// Google Maps API key for reverse geocoding
+    #[clap(flatten)]
+    pub(crate) geocoder: GeocoderOptions,
+}
+
+/// Reverse geocoding service, see [`GeocodingProvider`].
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub(crate) enum GeocoderProvider {
+    Google,
+    Nominatim,
+    #[value(name = "opencage")]
+    OpenCage,
+    #[value(name = "geonames")]
+    GeoNames,
+}
+
+impl From<GeocoderProvider> for GeocodingProvider {
+    fn from(provider: GeocoderProvider) -> Self {
+        match provider {
+            GeocoderProvider::Google => GeocodingProvider::Google,
+            GeocoderProvider::Nominatim => GeocodingProvider::Nominatim,
+            GeocoderProvider::OpenCage => GeocodingProvider::OpenCage,
+            GeocoderProvider::GeoNames => GeocodingProvider::GeoNames,
+        }
+    }
+}
+
+#[derive(Clone, Debug, clap::Args)]
+pub(crate) struct GeocoderOptions {
+    /// API key for reverse geocoding with Google Maps or OpenCage
     #[clap(short, long, value_hint=ValueHint::Other)]
     pub(crate) api_key: Option<String>,
+    /// Reverse geocoding service (overrides the configuration file)
+    #[clap(long)]
+    #[arg(value_enum)]
+    pub(crate) geocoder: Option<GeocoderProvider>,
+    /// Base URL of the reverse geocoding service, e.g. a local Nominatim instance
+    #[clap(long, value_hint=ValueHint::Url)]
+    pub(crate) geocoder_url: Option<String>,
+    /// Directory with the GeoNames dumps for offline reverse geocoding
+    #[clap(long, value_hint=ValueHint::DirPath)]
+    pub(crate) geonames: Option<PathBuf>,
+}
+
+impl GeocoderOptions {
+    /// Create the caching geocoder from the configuration file and the
+    /// command line.
+    ///
+    /// In offline mode only cached addresses are applied. Returns `None` if
+    /// the selected service needs an API key and none is given.
+    pub fn geocoder<'a>(
+        &self,
+        cache: &'a DbConn,
+        config: &GeocodingConfig,
+        offline: bool,
+    ) -> anyhow::Result<Option<CachedGeocoder<'a>>> {
+        let mut config = config.clone();
+        if let Some(provider) = self.geocoder {
+            config.provider = provider.into();
+        }
+        if let Some(url) = &self.geocoder_url {
+            config.base_url = Some(url.to_owned());
+        }
+        if let Some(key) = &self.api_key {
+            config.api_key = Some(key.to_owned());
+        }
+        if let Some(path) = &self.geonames {
+            config.geonames_path = Some(path.to_owned());
+        }
+
+        Ok(CachedGeocoder::new(cache, &config, offline)?)
+    }
 }
 
 impl LightroomOptions {
@@ -108,11 +363,73 @@ impl LightroomOptions {
     }
 }
 
+#[derive(clap::Subcommand, Debug)]
+pub(crate) enum XmpCommands {
+    /// Write the location of a dive site into the XMP sidecars of photos
+    WriteSite(XmpWriteSiteOptions),
+    /// Match photos to dives by capture time and write the dive site into their sidecars
+    Geotag(XmpGeotagOptions),
+}
+
+#[derive(Debug, clap::Args)]
+pub(crate) struct XmpGeotagOptions {
+    /// Directory containing the photos
+    #[clap(value_hint=ValueHint::DirPath)]
+    pub(crate) directory: PathBuf,
+    /// How far the camera clock is ahead of the dive computer ([+|-]HH:MM[:SS])
+    #[clap(long, default_value = "00:00", allow_hyphen_values = true)]
+    pub(crate) clock_offset: String,
+    /// Match photos taken up to this many minutes before or after a dive
+    #[clap(short, long, default_value_t = 60)]
+    pub(crate) tolerance: u32,
+    /// Include photos in subdirectories
+    #[clap(short, long)]
+    pub(crate) recursive: bool,
+    /// Name sidecars `IMG_0001.CR2.xmp` (darktable) instead of `IMG_0001.xmp`
+    #[clap(long)]
+    pub(crate) append_extension: bool,
+    /// Only show which photos would be tagged
+    #[clap(short = 'n', long)]
+    pub(crate) dry_run: bool,
+    /// Add critters logged on the dive as candidate keywords
+    #[clap(long)]
+    pub(crate) critters: bool,
+    #[clap(flatten)]
+    pub(crate) geocoder: GeocoderOptions,
+}
+
+#[derive(Debug, clap::Args)]
+pub(crate) struct XmpWriteSiteOptions {
+    /// UUID or name of the MacDive dive site
+    #[clap(short, long)]
+    pub(crate) site: String,
+    /// Directory containing the photos
+    #[clap(value_hint=ValueHint::DirPath)]
+    pub(crate) directory: PathBuf,
+    /// Include photos in subdirectories
+    #[clap(short, long)]
+    pub(crate) recursive: bool,
+    /// Name sidecars `IMG_0001.CR2.xmp` (darktable) instead of `IMG_0001.xmp`
+    #[clap(long)]
+    pub(crate) append_extension: bool,
+    /// Only show which sidecars would be created or changed
+    #[clap(short = 'n', long)]
+    pub(crate) dry_run: bool,
+    #[clap(flatten)]
+    pub(crate) geocoder: GeocoderOptions,
+}
+
 #[derive(clap::Subcommand, Debug)]
 pub(crate) enum CritterCommands {
     Validate,
     ValidateCategories,
     PrepareImport(PrepareImportOptions),
+    /// Export the critter taxonomy as a Lightroom keyword list
+    ExportKeywords {
+        /// Path of the keyword file, prints to stdout if omitted
+        #[clap(short, long, value_hint=ValueHint::FilePath)]
+        output: Option<PathBuf>,
+    },
 }
 
 #[derive(Debug, clap::Args)]
@@ -9,11 +9,13 @@ use macdive_toolbox_core::services::globalnames;
 use macdive_toolbox_core::services::inaturalist::{
     self, Taxon, get_taxon_by_id, get_taxon_by_name,
 };
+use macdive_toolbox_core::services::lightroom::{Keyword, KeywordTree};
 use sea_orm::DbConn;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::path::Path;
 use tracing::instrument;
 
 /// Trait for resolving a taxon's category group name from its ancestry.
@@ -611,3 +613,107 @@ pub(crate) async fn critter_import(
     };
     Ok(())
 }
+
+/// Taxonomic ranks included in the keyword hierarchy, from the top down.
+const KEYWORD_RANKS: [&str; 5] = ["phylum", "class", "order", "family", "genus"];
+
+/// Build a keyword from a taxon's scientific name, with its common names as synonyms.
+fn taxon_keyword(taxon: &Taxon, common_names: &[Option<&str>]) -> Option<Keyword> {
+    Some(Keyword {
+        name: taxon.name.as_deref()?.trim().to_string(),
+        synonyms: std::iter::once(taxon.preferred_common_name.as_deref())
+            .chain(common_names.iter().copied())
+            .flatten()
+            .map(|name| change_case::title_case(name.trim()))
+            .collect(),
+    })
+}
+
+/// Resolve the keyword path (top level down to the species) of a critter.
+pub(crate) async fn keyword_path(
+    cache: &DbConn,
+    scientific_name: &str,
+    common_name: Option<&str>,
+    config: &CritterConfig,
+    offline: bool,
+) -> anyhow::Result<Vec<Keyword>> {
+    let taxon = get_taxon_by_name(cache, scientific_name, offline).await?;
+    let group_by_category = config.keywords.group_by_category;
+
+    let mut path = vec![];
+    if group_by_category {
+        let group = taxon.group_name(cache, &config.categories, offline).await?;
+        path.push(Keyword::new(group.to_string()));
+    }
+    for ancestor_id in taxon.ancestor_ids.iter().flatten() {
+        if *ancestor_id == taxon.id {
+            continue;
+        }
+        let ancestor = get_taxon_by_id(cache, *ancestor_id, offline).await?;
+        let include = match ancestor.rank.as_deref() {
+            Some("phylum") => !group_by_category,
+            Some(rank) => KEYWORD_RANKS.contains(&rank),
+            None => false,
+        };
+        if include {
+            path.extend(taxon_keyword(&ancestor, &[]));
+        }
+    }
+    path.extend(taxon_keyword(&taxon, &[common_name]));
+
+    Ok(path)
+}
+
+/// Export the MacDive critter list as a Lightroom keyword import file.
+///
+/// Critters are placed in a Phylum > Class > Order > Family > Genus > Species
+/// hierarchy resolved through iNaturalist, with common names as synonyms.
+pub(crate) async fn export_keywords(
+    db: &DatabaseManager,
+    config: &CritterConfig,
+    output: Option<&Path>,
+    offline: bool,
+) -> anyhow::Result<()> {
+    let _header_span = header("critters export-keywords");
+
+    let cache = db.cache();
+    let critters = queries::critters(db.macdive()).await?;
+    let paths = futures::stream::iter(critters.iter().filter(|c| c.species.is_some()))
+        .map(|critter| async move {
+            let scientific_name = critter.species.as_deref().unwrap_or_default();
+            let result = keyword_path(
+                cache,
+                scientific_name,
+                critter.name.as_deref(),
+                config,
+                offline,
+            )
+            .await;
+            if let Err(e) = &result {
+                tracing::error!(
+                    scientific_name,
+                    reason = e.to_string(),
+                    "Taxon lookup failed"
+                );
+            }
+            result.ok()
+        })
+        .buffer_unordered(20)
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut tree = KeywordTree::default();
+    for path in paths.into_iter().flatten() {
+        tree.insert(&path);
+    }
+    if tree.is_empty() {
+        anyhow::bail!("No critters could be resolved to a taxon");
+    }
+
+    match output {
+        Some(path) => std::fs::write(path, tree.render())?,
+        None => print!("{}", tree.render()),
+    }
+
+    Ok(())
+}
@@ -1,45 +1,73 @@
-use crate::cli::LightroomOptions;
+use crate::cli::{ExportSitesOptions, LightroomOptions, SummaryFormat};
+use crate::commands::new_table;
+use crate::commands::xmp::fetch_valid_dives;
 use crate::errors::ConversionError;
 use crate::types::dive_site_from_entity;
+use chrono::TimeDelta;
 use comfy_table::*;
 use console::{Emoji, style};
 use futures::StreamExt;
 use indicatif::ProgressBar;
 use macdive_toolbox_core::db::DatabaseManager;
-use macdive_toolbox_core::domain::{DiveSite, LocationOverride};
+use macdive_toolbox_core::domain::{ApplicationConfig, DiveSite};
+use macdive_toolbox_core::lrcat;
 use macdive_toolbox_core::macdive::queries;
-use macdive_toolbox_core::services::geocoding;
+use macdive_toolbox_core::services::elevation::SrtmTiles;
+use macdive_toolbox_core::services::geocoding::{self, Geocoder};
+use macdive_toolbox_core::services::geotag::{match_dive, parse_clock_offset};
 use macdive_toolbox_core::services::lightroom::{
-    MetadataPreset, read_existing_presets, write_presets,
+    AuditEntry, LocationIssue, MetadataPreset, PresetDiff, PresetStatus, audit_photo,
+    check_quarantine, diff_preset, orphaned_presets, read_existing_presets, remove_preset,
+    resolve_file_names, write_presets,
 };
+use macdive_toolbox_core::services::marine::MarineRegions;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use uuid::Uuid;
 
 static LOOKING_GLASS: Emoji<'_, '_> = Emoji("🔍  ", "");
 static DIVING_MASK: Emoji<'_, '_> = Emoji("🤿️  ", "");
 static SATELLITE: Emoji<'_, '_> = Emoji("🛰️   ", "");
 static FILE_FOLDER: Emoji<'_, '_> = Emoji("📂  ", "");
+static WASTEBASKET: Emoji<'_, '_> = Emoji("🗑️   ", "");
+static CAMERA: Emoji<'_, '_> = Emoji("📷  ", "");
 
-fn print_summary(presets: &[MetadataPreset]) {
-    let mut table = Table::new();
-    table
-        .load_preset("││──╞═╪╡┆    ┬┴┌┐└┘")
-        .set_content_arrangement(ContentArrangement::Dynamic)
-        .set_header(vec![
-            Cell::new("Site").add_attribute(Attribute::Bold),
-            Cell::new("City").add_attribute(Attribute::Bold),
-            Cell::new("Region").add_attribute(Attribute::Bold),
-            Cell::new("State").add_attribute(Attribute::Bold),
-            Cell::new("Country").add_attribute(Attribute::Bold),
-            Cell::new("GPS").add_attribute(Attribute::Bold),
-        ]);
+fn print_summary(presets: &[MetadataPreset], diffs: &[PresetDiff], altitude_threshold: f32) {
+    let mut table = new_table(&[
+        "Status", "Site", "City", "Region", "State", "Country", "GPS", "Altitude", "Changes",
+    ]);
+
+    for (site, diff) in presets.iter().zip(diffs) {
+        let status = match diff.status {
+            PresetStatus::New => Cell::new("new").fg(Color::Green),
+            PresetStatus::Changed => Cell::new("changed").fg(Color::Yellow),
+            PresetStatus::Unchanged => Cell::new("unchanged"),
+        };
+        let changes = diff
+            .changes
+            .iter()
+            .map(|change| format!("{}: {:?} → {:?}", change.field, change.old, change.new))
+            .collect::<Vec<_>>()
+            .join("\n");
+        // Flag altitude dives, which need different decompression planning.
+        let altitude = match site.altitude.parse::<f32>() {
+            Ok(altitude) if altitude > altitude_threshold => {
+                Cell::new(format!("{altitude} m")).fg(Color::Yellow)
+            }
+            Ok(altitude) if altitude != 0.0 => Cell::new(format!("{altitude} m")),
+            _ => Cell::new(""),
+        };
 
-    for site in presets {
         table.add_row(vec![
+            status,
             Cell::new(&site.location),
             Cell::new(&site.city),
             Cell::new(&site.region),
             Cell::new(&site.state),
             Cell::new(&site.country),
             Cell::new(&site.gps),
+            altitude,
+            Cell::new(changes),
         ]);
     }
 
@@ -49,70 +77,330 @@ fn print_summary(presets: &[MetadataPreset]) {
 pub(crate) async fn export_lightroom_metadata_presets(
     db: &DatabaseManager,
     options: &LightroomOptions,
-    overrides: &[LocationOverride],
-    force: bool,
+    config: &ApplicationConfig,
+    export: &ExportSitesOptions,
+    geocoder: Option<&dyn Geocoder>,
 ) -> anyhow::Result<()> {
-    println!(
+    // In lenient mode sites that fail a step are logged and dropped.
+    let mut skipped = 0;
+    let mut keep = |result: Result<DiveSite, ConversionError>, id: i64| match result {
+        Ok(site) => Ok(Some(site)),
+        Err(e) if export.lenient => {
+            tracing::warn!("Skipping dive site {id}: {e}");
+            skipped += 1;
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    };
+
+    eprintln!(
         "{} {}Locating existing metadata presets...",
         style("[1/4]").bold().dim(),
         LOOKING_GLASS
     );
     let existing = read_existing_presets(&options.lightroom_metadata()?)?;
 
-    println!(
+    eprintln!(
         "{} {}Fetching dive sites from MacDive...",
         style("[2/4]").bold().dim(),
         DIVING_MASK
     );
-    let sites = queries::sites(db.macdive())
-        .await?
-        .into_iter()
-        .map(dive_site_from_entity)
-        .collect::<Result<Vec<DiveSite>, ConversionError>>()?;
+    let mut sites = vec![];
+    for model in queries::sites(db.macdive()).await? {
+        let id = model.id;
+        sites.extend(keep(dive_site_from_entity(model), id)?);
+    }
 
-    println!(
+    eprintln!(
         "{} {}Looking up addresses for dive sites...",
         style("[3/4]").bold().dim(),
         SATELLITE
     );
     let mut sites: Vec<DiveSite> = sites
         .into_iter()
-        .filter(|site| force || !existing.contains_key(&site.uuid))
+        .filter(|site| export.force || !existing.contains_key(&site.uuid))
         .collect();
     let pb = ProgressBar::new(sites.len() as u64);
 
-    if let Some(key) = &options.api_key {
-        sites = futures::stream::iter(sites)
+    if let Some(geocoder) = geocoder {
+        let geocoded = futures::stream::iter(sites)
             .map(|site| {
                 pb.inc(1);
-                geocoding::geocode_site(site, key)
+                let id = site.site_id;
+                async move { (id, geocoder.reverse_geocode(site).await) }
             })
             .buffer_unordered(10usize)
             .collect::<Vec<_>>()
-            .await
-            .into_iter()
-            .map(|item| {
-                item.map_err(ConversionError::from).and_then(|site| {
-                    geocoding::apply_overrides(site, overrides).map_err(ConversionError::from)
-                })
-            })
-            .collect::<anyhow::Result<Vec<_>, ConversionError>>()?;
+            .await;
+        sites = vec![];
+        for (id, item) in geocoded {
+            sites.extend(keep(item.map_err(ConversionError::from), id)?);
+        }
+    }
+    let overrides = config.locations();
+    let mut located = vec![];
+    for site in sites {
+        let id = site.site_id;
+        let site = geocoding::apply_overrides(site, &overrides).map_err(ConversionError::from);
+        located.extend(keep(site, id)?);
+    }
+    sites = located;
+    if let Some(regions) = MarineRegions::from_config(&config.body_of_water)? {
+        sites = sites.into_iter().map(|site| regions.fill(site)).collect();
+    }
+    if let Some(tiles) = SrtmTiles::from_config(&config.elevation) {
+        // A missing altitude does not warrant dropping a site, so in lenient
+        // mode sites whose tile cannot be read keep their altitude.
+        let mut filled = vec![];
+        for site in sites {
+            match tiles.fill(site.clone()) {
+                Ok(site) => filled.push(site),
+                Err(e) if export.lenient => {
+                    tracing::warn!("No elevation for dive site {}: {e}", site.site_id);
+                    filled.push(site);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        sites = filled;
+    }
+    let mut presets = vec![];
+    for site in sites {
+        let id = site.site_id;
+        match MetadataPreset::from_site(site, &config.lightroom) {
+            Ok(preset) => presets.push(preset),
+            Err(e) if export.lenient => {
+                tracing::warn!("Skipping dive site {id}: {e}");
+                skipped += 1;
+            }
+            Err(e) => return Err(ConversionError::from(e).into()),
+        }
     }
-    let presets = sites
-        .into_iter()
-        .map(|site| MetadataPreset::try_from(site).map_err(ConversionError::from))
-        .collect::<Result<Vec<MetadataPreset>, ConversionError>>()?;
     pb.finish_and_clear();
+    if skipped > 0 {
+        eprintln!("Skipped {skipped} dive sites with errors, run `sites validate` for details.");
+    }
+    resolve_file_names(&options.lightroom_metadata()?, &mut presets, &existing);
+
+    let diffs = presets
+        .iter()
+        .map(|preset| {
+            diff_preset(
+                existing.get(&preset.id).map(|e| e.preset()).as_ref(),
+                preset,
+            )
+        })
+        .collect::<Vec<PresetDiff>>();
+
+    if !export.dry_run {
+        eprintln!(
+            "{} {}Writing Lightroom Metadata Presets...",
+            style("[4/4]").bold().dim(),
+            FILE_FOLDER
+        );
+        // Only rewrite presets whose content actually changes.
+        let changed = presets
+            .iter()
+            .zip(&diffs)
+            .filter(|(_, diff)| diff.needs_write())
+            .map(|(preset, _)| preset.clone())
+            .collect::<Vec<MetadataPreset>>();
+        write_presets(&options.lightroom_metadata()?, &changed, &existing)?;
+    }
 
-    println!(
-        "{} {}Writing Lightroom Metadata Presets...",
-        style("[4/4]").bold().dim(),
-        FILE_FOLDER
+    match export.format {
+        SummaryFormat::Json => println!("{}", serde_json::to_string_pretty(&diffs)?),
+        SummaryFormat::Table if !presets.is_empty() => {
+            print_summary(&presets, &diffs, config.elevation.altitude_threshold)
+        }
+        SummaryFormat::Table => {}
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn prune_lightroom_metadata_presets(
+    db: &DatabaseManager,
+    options: &LightroomOptions,
+    apply: bool,
+    quarantine: Option<&Path>,
+) -> anyhow::Result<()> {
+    eprintln!(
+        "{} {}Locating existing metadata presets...",
+        style("[1/3]").bold().dim(),
+        LOOKING_GLASS
     );
-    write_presets(&options.lightroom_metadata()?, &presets, &existing)?;
+    let presets = options.lightroom_metadata()?;
+    if let Some(quarantine) = quarantine {
+        check_quarantine(&presets, quarantine)?;
+    }
+    let existing = read_existing_presets(&presets)?;
+
+    eprintln!(
+        "{} {}Fetching dive sites from MacDive...",
+        style("[2/3]").bold().dim(),
+        DIVING_MASK
+    );
+    let sites = queries::sites(db.macdive())
+        .await?
+        .into_iter()
+        .filter_map(|site| site.uuid)
+        .filter_map(|uuid| Uuid::parse_str(&uuid.to_lowercase()).ok())
+        .collect::<HashSet<Uuid>>();
+
+    // An empty site list almost certainly means the wrong database was opened;
+    // refuse to treat every single preset as orphaned.
+    if sites.is_empty() {
+        anyhow::bail!("No dive sites found in MacDive, refusing to prune presets");
+    }
+
+    let orphans = orphaned_presets(&existing, &sites);
+    if orphans.is_empty() {
+        println!("No orphaned metadata presets found.");
+        return Ok(());
+    }
+
+    let mut table = new_table(&["Preset", "Title", "UUID"]);
+    for (uuid, existing) in &orphans {
+        table.add_row(vec![
+            Cell::new(existing.entry.file_name().to_string_lossy()),
+            Cell::new(existing.file.title.as_deref().unwrap_or_default()),
+            Cell::new(uuid),
+        ]);
+    }
+    println!("{table}");
+
+    if !apply {
+        println!(
+            "Found {} orphaned metadata presets, re-run with `--apply` to remove them.",
+            orphans.len()
+        );
+        return Ok(());
+    }
+
+    eprintln!(
+        "{} {}Removing orphaned metadata presets...",
+        style("[3/3]").bold().dim(),
+        WASTEBASKET
+    );
+    for (_, existing) in &orphans {
+        remove_preset(existing, quarantine)?;
+    }
+    println!("Removed {} orphaned metadata presets.", orphans.len());
+
+    Ok(())
+}
+
+pub(crate) async fn audit_lightroom_catalog(
+    db: &DatabaseManager,
+    options: &LightroomOptions,
+    catalog: &Path,
+    clock_offset: &str,
+    tolerance: u32,
+    max_distance: f64,
+    format: SummaryFormat,
+) -> anyhow::Result<()> {
+    let offset = parse_clock_offset(clock_offset)?;
+    let tolerance = TimeDelta::minutes(tolerance.into());
+
+    eprintln!(
+        "{} {}Locating existing metadata presets...",
+        style("[1/3]").bold().dim(),
+        LOOKING_GLASS
+    );
+    let existing = read_existing_presets(&options.lightroom_metadata()?)?;
+
+    eprintln!(
+        "{} {}Fetching dives and dive sites from MacDive...",
+        style("[2/3]").bold().dim(),
+        DIVING_MASK
+    );
+    let dives = fetch_valid_dives(db).await?;
+    let sites = queries::sites(db.macdive())
+        .await?
+        .into_iter()
+        .filter_map(|model| {
+            let id = model.id;
+            match dive_site_from_entity(model) {
+                Ok(site) => Some((id, site)),
+                Err(e) => {
+                    tracing::warn!("Skipping dive site {id}: {e}");
+                    None
+                }
+            }
+        })
+        .collect::<HashMap<i64, DiveSite>>();
+
+    eprintln!(
+        "{} {}Auditing photos in the Lightroom catalog...",
+        style("[3/3]").bold().dim(),
+        CAMERA
+    );
+    let catalog = DatabaseManager::read_only(catalog).await?;
+    let mut entries = vec![];
+    for photo in lrcat::queries::photos(&catalog).await? {
+        let Some(taken) = photo.taken().map(|taken| taken - offset) else {
+            continue;
+        };
+        let Some((dive, _)) = match_dive(&dives, taken, tolerance) else {
+            continue;
+        };
+        let Some(site) = dive.site_id.and_then(|id| sites.get(&id)) else {
+            continue;
+        };
+        let preset = existing.get(&site.uuid);
+        if let Some(issue) = audit_photo(
+            &photo,
+            site,
+            preset.map(|p| p.preset()).as_ref(),
+            max_distance,
+        ) {
+            entries.push(AuditEntry {
+                photo: photo.path,
+                taken: taken.to_string(),
+                dive: dive.number,
+                site: site.uuid,
+                preset: preset.and_then(|p| p.file.title.clone()),
+                issue,
+            });
+        }
+    }
+    // Group the worklist by the preset to apply.
+    entries.sort_by(|a, b| (&a.preset, &a.photo).cmp(&(&b.preset, &b.photo)));
 
-    if !presets.is_empty() {
-        print_summary(&presets);
+    match format {
+        SummaryFormat::Json => println!("{}", serde_json::to_string_pretty(&entries)?),
+        SummaryFormat::Table if entries.is_empty() => {
+            println!("All photos taken on a dive have a matching location.")
+        }
+        SummaryFormat::Table => {
+            let mut table = new_table(&["Preset", "Photo", "Taken", "Dive", "Issue"]);
+            for entry in &entries {
+                let preset = match &entry.preset {
+                    Some(title) => Cell::new(title),
+                    None => Cell::new(format!("not exported ({})", entry.site)).fg(Color::Red),
+                };
+                let issue = match &entry.issue {
+                    LocationIssue::MissingLocation => Cell::new("no location").fg(Color::Yellow),
+                    LocationIssue::Mismatch { changes } => Cell::new(
+                        changes
+                            .iter()
+                            .map(|c| format!("{}: {:?} ≠ {:?}", c.field, c.old, c.new))
+                            .collect::<Vec<_>>()
+                            .join("\n"),
+                    )
+                    .fg(Color::Red),
+                };
+                table.add_row(vec![
+                    preset,
+                    Cell::new(&entry.photo),
+                    Cell::new(&entry.taken),
+                    Cell::new(entry.dive.map(|n| format!("#{n}")).unwrap_or_default()),
+                    issue,
+                ]);
+            }
+            println!("{table}");
+        }
     }
 
     Ok(())
@@ -1,3 +1,21 @@
+use comfy_table::{Attribute, Cell, ContentArrangement, Table};
+
 pub(crate) mod critters;
 pub(crate) mod lightroom;
 pub(crate) mod mtp;
+pub(crate) mod sites;
+pub(crate) mod xmp;
+
+/// Create a table in the style shared by all commands, with a bold header.
+pub(crate) fn new_table(header: &[&str]) -> Table {
+    let mut table = Table::new();
+    table
+        .load_preset("││──╞═╪╡┆    ┬┴┌┐└┘")
+        .set_content_arrangement(ContentArrangement::Dynamic)
+        .set_header(
+            header
+                .iter()
+                .map(|title| Cell::new(title).add_attribute(Attribute::Bold)),
+        );
+    table
+}
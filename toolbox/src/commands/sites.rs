@@ -0,0 +1,778 @@
+use crate::cli::{
+    SiteExportFormat, SiteExportOptions, SiteImportOptions, SuggestOverridesOptions, SummaryFormat,
+};
+use crate::commands::new_table;
+use crate::errors::ConversionError;
+use crate::types::{dive_site_from_entity, dive_site_report};
+use anyhow::anyhow;
+use comfy_table::*;
+use console::{Emoji, style};
+use futures::StreamExt;
+use indicatif::ProgressBar;
+use macdive_toolbox_core::db::DatabaseManager;
+use macdive_toolbox_core::domain::{
+    ApplicationConfig, BodyOfWaterConfig, CoordinateFormat, DiveSite,
+};
+use macdive_toolbox_core::macdive::queries;
+use macdive_toolbox_core::parsers::sites::{SiteFileFormat, parse_sites};
+use macdive_toolbox_core::services::duplicates::{
+    DuplicateGroup, find_duplicates as duplicates, find_existing,
+};
+use macdive_toolbox_core::services::elevation::SrtmTiles;
+use macdive_toolbox_core::services::export::{
+    render_geojson, render_gpx, render_kml, render_subsurface,
+};
+use macdive_toolbox_core::services::geocoding::{self, Geocoder};
+use macdive_toolbox_core::services::geojson::{feature_collection, override_feature, site_feature};
+use macdive_toolbox_core::services::marine::{BodyOfWater, MarineRegions};
+use macdive_toolbox_core::services::overrides::{
+    CoverageReport, OverrideSuggestion, check_overrides as coverage, render_yaml,
+    suggest_overrides as suggest,
+};
+use macdive_toolbox_core::services::validation::{SiteReport, geocode_country};
+use macdive_toolbox_core::util::coordinates::format_coordinates;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+static DIVING_MASK: Emoji<'_, '_> = Emoji("🤿️  ", "");
+static SATELLITE: Emoji<'_, '_> = Emoji("🛰️   ", "");
+static LINK: Emoji<'_, '_> = Emoji("🔗  ", "");
+static WATER_WAVE: Emoji<'_, '_> = Emoji("🌊  ", "");
+static WORLD_MAP: Emoji<'_, '_> = Emoji("🗺️   ", "");
+static FLOPPY_DISK: Emoji<'_, '_> = Emoji("💾  ", "");
+static INBOX_TRAY: Emoji<'_, '_> = Emoji("📥  ", "");
+static MAGNIFYING_GLASS: Emoji<'_, '_> = Emoji("🔎  ", "");
+static STETHOSCOPE: Emoji<'_, '_> = Emoji("🩺  ", "");
+
+/// Names of the given sites, one per line.
+fn site_names(sites: &[DiveSite], indices: &[usize]) -> String {
+    indices
+        .iter()
+        .map(|index| sites[*index].name.as_str())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn print_report(
+    sites: &[DiveSite],
+    report: &CoverageReport,
+    format: CoordinateFormat,
+) -> anyhow::Result<()> {
+    let mut table = new_table(&["Override", "Sites", "Shadowed"]);
+    for coverage in &report.overrides {
+        let count = Cell::new(coverage.sites.len());
+        table.add_row(vec![
+            Cell::new(&coverage.name),
+            if coverage.sites.is_empty() {
+                count.fg(Color::Yellow)
+            } else {
+                count
+            },
+            Cell::new(site_names(sites, &coverage.shadowed)),
+        ]);
+    }
+    println!("{table}");
+
+    if !report.uncovered.is_empty() {
+        let mut table = new_table(&["Uncovered site", "Country", "GPS"]);
+        for site in report.uncovered.iter().map(|index| &sites[*index]) {
+            table.add_row(vec![
+                Cell::new(&site.name),
+                Cell::new(&site.country),
+                Cell::new(format_coordinates(site.latitude, site.longitude, format)?),
+            ]);
+        }
+        println!("{table}");
+    }
+
+    if !report.overlaps.is_empty() {
+        let mut table = new_table(&["Applied", "Shadowed", "Shared sites", "Conflicts"]);
+        for overlap in &report.overlaps {
+            let conflicts = overlap
+                .conflicts
+                .iter()
+                .map(|c| format!("{}: {:?} ≠ {:?}", c.field, c.first, c.second))
+                .collect::<Vec<_>>()
+                .join("\n");
+            table.add_row(vec![
+                Cell::new(&overlap.first),
+                Cell::new(&overlap.second),
+                Cell::new(site_names(sites, &overlap.sites)),
+                if overlap.conflicts.is_empty() {
+                    Cell::new(conflicts)
+                } else {
+                    Cell::new(conflicts).fg(Color::Red)
+                },
+            ]);
+        }
+        println!("{table}");
+    }
+
+    Ok(())
+}
+
+/// Fetch all dive sites, skipping those that cannot be converted.
+///
+/// Returns the converted sites and the number of skipped sites.
+async fn fetch_valid_sites(db: &DatabaseManager) -> anyhow::Result<(Vec<DiveSite>, usize)> {
+    let mut skipped = 0;
+    let sites = queries::sites(db.macdive())
+        .await?
+        .into_iter()
+        .filter_map(|model| {
+            let id = model.id;
+            dive_site_from_entity(model)
+                .inspect_err(|e| {
+                    tracing::warn!("Skipping dive site {id}: {e}");
+                    skipped += 1;
+                })
+                .ok()
+        })
+        .collect();
+    Ok((sites, skipped))
+}
+
+/// Geocode `sites` and apply the location overrides.
+///
+/// Without `cache` the addresses are looked up without storing them, for
+/// sites that are not in MacDive yet.
+async fn locate_sites(
+    sites: Vec<DiveSite>,
+    geocoder: Option<&dyn Geocoder>,
+    config: &ApplicationConfig,
+    cache: bool,
+) -> anyhow::Result<Vec<DiveSite>> {
+    let sites = match geocoder {
+        Some(geocoder) => {
+            let pb = ProgressBar::new(sites.len() as u64);
+            let sites = futures::stream::iter(sites)
+                .map(|site| {
+                    pb.inc(1);
+                    if cache {
+                        geocoder.reverse_geocode(site)
+                    } else {
+                        geocoder.lookup(site)
+                    }
+                })
+                .buffered(10usize)
+                .collect::<Vec<_>>()
+                .await
+                .into_iter()
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(ConversionError::from)?;
+            pb.finish_and_clear();
+            sites
+        }
+        None => sites,
+    };
+
+    let overrides = config.locations();
+    Ok(sites
+        .into_iter()
+        .map(|site| geocoding::apply_overrides(site, &overrides))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(ConversionError::from)?)
+}
+
+pub(crate) async fn check_overrides(
+    db: &DatabaseManager,
+    config: &ApplicationConfig,
+    geojson: Option<&Path>,
+) -> anyhow::Result<()> {
+    let steps = if geojson.is_some() { 3 } else { 2 };
+
+    eprintln!(
+        "{} {}Fetching dive sites from MacDive...",
+        style(format!("[1/{steps}]")).bold().dim(),
+        DIVING_MASK
+    );
+    let (sites, _) = fetch_valid_sites(db).await?;
+
+    eprintln!(
+        "{} {}Checking location overrides...",
+        style(format!("[2/{steps}]")).bold().dim(),
+        WORLD_MAP
+    );
+    let overrides = config.named_locations();
+    let report = coverage(&overrides, &sites);
+    print_report(&sites, &report, config.export.coordinate_format)?;
+
+    if let Some(path) = geojson {
+        eprintln!(
+            "{} {}Writing {}...",
+            style(format!("[3/{steps}]")).bold().dim(),
+            FLOPPY_DISK,
+            path.display()
+        );
+        let mut features: Vec<_> = overrides
+            .iter()
+            .map(|(name, location)| override_feature(name, location))
+            .collect();
+        for (index, site) in sites.iter().enumerate() {
+            let mut feature = site_feature(site);
+            let applied = report
+                .overrides
+                .iter()
+                .find(|coverage| coverage.sites.contains(&index))
+                .map(|coverage| coverage.name.as_str());
+            feature["properties"]["override"] = applied.into();
+            features.push(feature);
+        }
+        std::fs::write(
+            path,
+            serde_json::to_string_pretty(&feature_collection(features))?,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn print_suggestions(sites: &[DiveSite], suggestions: &[OverrideSuggestion]) {
+    let mut table = new_table(&["Override", "Region", "State", "Country", "Sites"]);
+    for suggestion in suggestions {
+        let location = &suggestion.location;
+        table.add_row(vec![
+            Cell::new(&suggestion.name),
+            Cell::new(location.region.as_deref().unwrap_or_default()),
+            Cell::new(location.state.as_deref().unwrap_or_default()),
+            Cell::new(location.country.as_deref().unwrap_or_default()),
+            Cell::new(site_names(sites, &suggestion.sites)),
+        ]);
+    }
+    println!("{table}");
+}
+
+pub(crate) async fn suggest_overrides(
+    db: &DatabaseManager,
+    options: &SuggestOverridesOptions,
+    config: &ApplicationConfig,
+    geocoder: Option<&dyn Geocoder>,
+) -> anyhow::Result<()> {
+    eprintln!(
+        "{} {}Fetching dive sites from MacDive...",
+        style("[1/3]").bold().dim(),
+        DIVING_MASK
+    );
+    let overrides = config.locations();
+    let sites: Vec<DiveSite> = fetch_valid_sites(db)
+        .await?
+        .0
+        .into_iter()
+        .filter(|site| {
+            options.all
+                || !overrides
+                    .iter()
+                    .any(|location| location.contains(site.latitude, site.longitude))
+        })
+        .collect();
+
+    eprintln!(
+        "{} {}Looking up addresses for dive sites...",
+        style("[2/3]").bold().dim(),
+        SATELLITE
+    );
+    let sites = match geocoder {
+        Some(geocoder) => {
+            let pb = ProgressBar::new(sites.len() as u64);
+            // Keep the order of the sites so that the proposals are stable.
+            let sites = futures::stream::iter(sites)
+                .map(|site| {
+                    pb.inc(1);
+                    geocoder.reverse_geocode(site)
+                })
+                .buffered(10usize)
+                .collect::<Vec<_>>()
+                .await
+                .into_iter()
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(ConversionError::from)?;
+            pb.finish_and_clear();
+            sites
+        }
+        None => sites,
+    };
+
+    eprintln!(
+        "{} {}Clustering dive sites...",
+        style("[3/3]").bold().dim(),
+        LINK
+    );
+    let suggestions = suggest(&sites, options.distance, options.buffer);
+    let yaml = format!("locations:\n{}", render_yaml(&suggestions));
+    match &options.output {
+        Some(path) => {
+            std::fs::write(path, yaml)?;
+            print_suggestions(&sites, &suggestions);
+        }
+        None => print!("{yaml}"),
+    }
+
+    if let Some(path) = &options.geojson {
+        let mut features = vec![];
+        for suggestion in &suggestions {
+            features.push(override_feature(&suggestion.name, &suggestion.location));
+            for index in &suggestion.sites {
+                let mut feature = site_feature(&sites[*index]);
+                feature["properties"]["override"] = suggestion.name.as_str().into();
+                features.push(feature);
+            }
+        }
+        std::fs::write(
+            path,
+            serde_json::to_string_pretty(&feature_collection(features))?,
+        )?;
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn check_body_of_water(
+    db: &DatabaseManager,
+    config: &BodyOfWaterConfig,
+    all: bool,
+) -> anyhow::Result<()> {
+    eprintln!(
+        "{} {}Loading marine regions...",
+        style("[1/3]").bold().dim(),
+        WORLD_MAP
+    );
+    let regions = MarineRegions::from_config(config)?.ok_or_else(|| {
+        anyhow!("No marine regions configured, set `body_of_water.marine_regions` or use --marine-regions")
+    })?;
+
+    eprintln!(
+        "{} {}Fetching dive sites from MacDive...",
+        style("[2/3]").bold().dim(),
+        DIVING_MASK
+    );
+    let (sites, skipped) = fetch_valid_sites(db).await?;
+
+    eprintln!(
+        "{} {}Detecting bodies of water...",
+        style("[3/3]").bold().dim(),
+        WATER_WAVE
+    );
+    let mut table = new_table(&["Status", "Site", "MacDive", "Detected"]);
+    let (mut filled, mut matches, mut differs, mut unknown) = (0, 0, 0, 0);
+    for site in &sites {
+        let recorded = site.body_of_water.as_deref().unwrap_or_default();
+        let (status, detected) = match regions.check(site) {
+            BodyOfWater::Filled(detected) => {
+                filled += 1;
+                (Cell::new("filled").fg(Color::Green), detected)
+            }
+            BodyOfWater::Differs(detected) => {
+                differs += 1;
+                (Cell::new("differs").fg(Color::Red), detected)
+            }
+            BodyOfWater::Unknown => {
+                unknown += 1;
+                (Cell::new("unknown").fg(Color::Yellow), String::new())
+            }
+            BodyOfWater::Matches => {
+                matches += 1;
+                if !all {
+                    continue;
+                }
+                (Cell::new("matches"), recorded.to_string())
+            }
+        };
+        table.add_row(vec![
+            status,
+            Cell::new(&site.name),
+            Cell::new(recorded),
+            Cell::new(detected),
+        ]);
+    }
+
+    println!("{table}");
+    println!(
+        "{filled} filled, {matches} matching, {differs} differing, {unknown} without a marine region."
+    );
+    if skipped > 0 {
+        eprintln!("Skipped {skipped} dive sites with errors, run `sites validate` for details.");
+    }
+
+    Ok(())
+}
+
+fn print_validation(reports: &[SiteReport], total: usize) {
+    if reports.is_empty() {
+        println!("All {total} dive sites look fine.");
+        return;
+    }
+
+    let mut table = new_table(&["ID", "Site", "Country", "GPS", "Issues"]);
+    for report in reports {
+        let gps = match (report.latitude, report.longitude) {
+            (Some(latitude), Some(longitude)) => format!("{latitude:.5}, {longitude:.5}"),
+            _ => String::new(),
+        };
+        let issues = report
+            .issues
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n");
+        table.add_row(vec![
+            Cell::new(report.site_id),
+            Cell::new(report.name.as_deref().unwrap_or_default()),
+            Cell::new(report.country.as_deref().unwrap_or_default()),
+            Cell::new(gps),
+            Cell::new(issues).fg(Color::Red),
+        ]);
+    }
+    println!("{table}");
+    println!("{} of {total} dive sites have problems.", reports.len());
+}
+
+pub(crate) async fn validate_sites(
+    db: &DatabaseManager,
+    geocoder: Option<&dyn Geocoder>,
+    format: SummaryFormat,
+) -> anyhow::Result<()> {
+    eprintln!(
+        "{} {}Fetching dive sites from MacDive...",
+        style("[1/3]").bold().dim(),
+        DIVING_MASK
+    );
+    let models = queries::all_sites(db.macdive()).await?;
+    let total = models.len();
+
+    eprintln!(
+        "{} {}Checking dive sites...",
+        style("[2/3]").bold().dim(),
+        STETHOSCOPE
+    );
+    let mut reports = vec![];
+    let mut convertible = vec![];
+    for model in models {
+        let report = dive_site_report(&model);
+        if report.issues.is_empty()
+            && let Ok(site) = dive_site_from_entity(model)
+        {
+            convertible.push((reports.len(), site));
+        }
+        reports.push(report);
+    }
+
+    eprintln!(
+        "{} {}Comparing countries with the coordinates...",
+        style("[3/3]").bold().dim(),
+        SATELLITE
+    );
+    match geocoder {
+        Some(geocoder) => {
+            let pb = ProgressBar::new(convertible.len() as u64);
+            let issues = futures::stream::iter(convertible)
+                .map(|(index, site)| {
+                    pb.inc(1);
+                    async move { (index, geocode_country(geocoder, site).await) }
+                })
+                .buffer_unordered(10usize)
+                .collect::<Vec<_>>()
+                .await;
+            pb.finish_and_clear();
+            for (index, issue) in issues {
+                reports[index].issues.extend(issue);
+            }
+        }
+        None => eprintln!("Skipped, use --check-country with a configured geocoder."),
+    }
+
+    let reports: Vec<SiteReport> = reports
+        .into_iter()
+        .filter(|report| !report.issues.is_empty())
+        .collect();
+    match format {
+        SummaryFormat::Json => println!("{}", serde_json::to_string_pretty(&reports)?),
+        SummaryFormat::Table => print_validation(&reports, total),
+    }
+
+    Ok(())
+}
+
+fn print_duplicates(groups: &[DuplicateGroup]) {
+    if groups.is_empty() {
+        println!("No duplicate dive sites found.");
+        return;
+    }
+
+    let mut table = new_table(&[
+        "",
+        "ID",
+        "Site",
+        "Country",
+        "GPS",
+        "Dives",
+        "Distance",
+        "Similarity",
+    ]);
+    for group in groups {
+        let keep = &group.keep;
+        table.add_row(vec![
+            Cell::new("keep").fg(Color::Green),
+            Cell::new(keep.site_id),
+            Cell::new(&keep.name).add_attribute(Attribute::Bold),
+            Cell::new(&keep.country),
+            Cell::new(format!("{:.5}, {:.5}", keep.latitude, keep.longitude)),
+            Cell::new(keep.dives),
+            Cell::new(""),
+            Cell::new(""),
+        ]);
+        for site in &group.merge {
+            table.add_row(vec![
+                Cell::new("merge").fg(Color::Yellow),
+                Cell::new(site.site_id),
+                Cell::new(&site.name),
+                Cell::new(&site.country),
+                Cell::new(format!("{:.5}, {:.5}", site.latitude, site.longitude)),
+                Cell::new(site.dives),
+                Cell::new(format!("{} m", site.distance)),
+                Cell::new(format!("{:.0}%", site.similarity * 100.0)),
+            ]);
+        }
+    }
+    println!("{table}");
+
+    let merge = groups.iter().map(|group| group.merge.len()).sum::<usize>();
+    println!(
+        "{} groups of duplicates, {merge} sites to merge.",
+        groups.len()
+    );
+}
+
+pub(crate) async fn find_duplicates(
+    db: &DatabaseManager,
+    max_distance: f64,
+    min_similarity: f64,
+    format: SummaryFormat,
+) -> anyhow::Result<()> {
+    eprintln!(
+        "{} {}Fetching dives and dive sites from MacDive...",
+        style("[1/2]").bold().dim(),
+        DIVING_MASK
+    );
+    let (sites, _) = fetch_valid_sites(db).await?;
+    let mut dive_counts = HashMap::new();
+    for site_id in queries::all_dives(db.macdive())
+        .await?
+        .into_iter()
+        .filter_map(|dive| dive.site)
+    {
+        *dive_counts.entry(site_id).or_default() += 1;
+    }
+
+    eprintln!(
+        "{} {}Looking for duplicate dive sites...",
+        style("[2/2]").bold().dim(),
+        MAGNIFYING_GLASS
+    );
+    let groups = duplicates(&sites, &dive_counts, max_distance, min_similarity);
+    match format {
+        SummaryFormat::Json => println!("{}", serde_json::to_string_pretty(&groups)?),
+        SummaryFormat::Table => print_duplicates(&groups),
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn export_sites(
+    db: &DatabaseManager,
+    options: &SiteExportOptions,
+    config: &ApplicationConfig,
+    geocoder: Option<&dyn Geocoder>,
+) -> anyhow::Result<()> {
+    eprintln!(
+        "{} {}Fetching dive sites from MacDive...",
+        style("[1/3]").bold().dim(),
+        DIVING_MASK
+    );
+    let (mut sites, _) = fetch_valid_sites(db).await?;
+
+    eprintln!(
+        "{} {}Looking up addresses for dive sites...",
+        style("[2/3]").bold().dim(),
+        SATELLITE
+    );
+    sites = locate_sites(sites, geocoder, config, true).await?;
+    if let Some(regions) = MarineRegions::from_config(&config.body_of_water)? {
+        sites = sites.into_iter().map(|site| regions.fill(site)).collect();
+    }
+    if let Some(tiles) = SrtmTiles::from_config(&config.elevation) {
+        sites = sites
+            .into_iter()
+            .map(|site| tiles.fill(site))
+            .collect::<Result<Vec<_>, _>>()?;
+    }
+
+    eprintln!(
+        "{} {}Exporting {} dive sites...",
+        style("[3/3]").bold().dim(),
+        FLOPPY_DISK,
+        sites.len()
+    );
+    let format = config.export.coordinate_format;
+    let content = match options.format {
+        SiteExportFormat::Gpx => render_gpx(&sites, format)?,
+        SiteExportFormat::Kml => render_kml(&sites, format)?,
+        SiteExportFormat::Geojson => render_geojson(&sites, format)?,
+        SiteExportFormat::Subsurface => render_subsurface(&sites)?,
+    };
+    match &options.output {
+        Some(path) => std::fs::write(path, content)?,
+        None => print!("{content}"),
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SiteItem {
+    uuid: String,
+    name: String,
+    country: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    location: Option<String>,
+    lat: f64,
+    lon: f64,
+    altitude: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body_of_water: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    water_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    difficulty: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notes: Option<String>,
+}
+
+impl From<&DiveSite> for SiteItem {
+    fn from(site: &DiveSite) -> Self {
+        // MacDive keeps a single free-form location next to the country.
+        let location = [&site.locality, &site.region, &site.state]
+            .into_iter()
+            .flatten()
+            .filter(|value| !value.is_empty())
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(", ");
+        Self {
+            uuid: site.uuid.to_string().to_uppercase(),
+            name: site.name.clone(),
+            country: site.country.clone(),
+            location: Some(location).filter(|location| !location.is_empty()),
+            lat: site.latitude,
+            lon: site.longitude,
+            altitude: site.altitude,
+            body_of_water: site.body_of_water.clone(),
+            water_type: site.water_type.clone(),
+            difficulty: site.difficulty.clone(),
+            notes: site.notes.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename = "sites")]
+struct Sites {
+    #[serde(rename = "@schema")]
+    schema: String,
+    site: Vec<SiteItem>,
+}
+
+pub(crate) async fn prepare_import(
+    db: &DatabaseManager,
+    options: &SiteImportOptions,
+    config: &ApplicationConfig,
+    geocoder: Option<&dyn Geocoder>,
+) -> anyhow::Result<()> {
+    eprintln!(
+        "{} {}Reading {}...",
+        style("[1/4]").bold().dim(),
+        INBOX_TRAY,
+        options.source.display()
+    );
+    let format = options
+        .input_format
+        .map(SiteFileFormat::from)
+        .or_else(|| SiteFileFormat::from_path(&options.source))
+        .ok_or_else(|| anyhow!("Unknown site list format, use --input-format"))?;
+    let imported = parse_sites(&std::fs::read_to_string(&options.source)?, format)?;
+    for reason in &imported.skipped {
+        tracing::warn!("Skipping {reason}");
+    }
+
+    eprintln!(
+        "{} {}Comparing with dive sites in MacDive...",
+        style("[2/4]").bold().dim(),
+        MAGNIFYING_GLASS
+    );
+    let (mut known, _) = fetch_valid_sites(db).await?;
+    let mut table = new_table(&["Status", "Site", "GPS", "Existing site"]);
+    let mut sites = vec![];
+    for site in imported.sites {
+        let gps = format!("{:.5}, {:.5}", site.latitude, site.longitude);
+        // Earlier rows of the same file count as known, too.
+        match find_existing(&site, &known, options.max_distance, options.similarity) {
+            Some(existing) => {
+                table.add_row(vec![
+                    Cell::new("duplicate").fg(Color::Yellow),
+                    Cell::new(&site.name),
+                    Cell::new(gps),
+                    Cell::new(&existing.name),
+                ]);
+            }
+            None => {
+                table.add_row(vec![
+                    Cell::new("new").fg(Color::Green),
+                    Cell::new(&site.name),
+                    Cell::new(gps),
+                    Cell::new(""),
+                ]);
+                known.push(site.clone());
+                sites.push(site);
+            }
+        }
+    }
+
+    eprintln!(
+        "{} {}Looking up addresses for dive sites...",
+        style("[3/4]").bold().dim(),
+        SATELLITE
+    );
+    // Imported sites get a new UUID on every run, so their addresses are not
+    // cached.
+    sites = locate_sites(sites, geocoder, config, false).await?;
+    for site in sites.iter().filter(|site| site.country.is_empty()) {
+        tracing::warn!("No country for dive site {}, set it in MacDive", site.name);
+    }
+
+    eprintln!(
+        "{} {}Writing {}...",
+        style("[4/4]").bold().dim(),
+        FLOPPY_DISK,
+        options.dest.display()
+    );
+    let import = Sites {
+        schema: String::from("1.0.0"),
+        site: sites.iter().map(SiteItem::from).collect(),
+    };
+    let xml = quick_xml::se::to_string(&import)?;
+    std::fs::write(
+        &options.dest,
+        format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{xml}"),
+    )?;
+
+    println!("{table}");
+    println!(
+        "{} new dive sites, {} already in MacDive, {} skipped.",
+        import.site.len(),
+        table.row_count() - import.site.len(),
+        imported.skipped.len()
+    );
+
+    Ok(())
+}
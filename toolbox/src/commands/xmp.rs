@@ -0,0 +1,478 @@
+use crate::cli::{XmpGeotagOptions, XmpWriteSiteOptions};
+use crate::commands::critters::keyword_path;
+use crate::commands::new_table;
+use crate::errors::ConversionError;
+use crate::types::{dive_from_entity, dive_site_from_entity};
+use chrono::TimeDelta;
+use comfy_table::*;
+use console::{Emoji, style};
+use macdive_toolbox_core::db::DatabaseManager;
+use macdive_toolbox_core::domain::{
+    ApplicationConfig, CritterConfig, Dive, DiveSite, LightroomConfig, LocationOverride,
+};
+use macdive_toolbox_core::macdive::queries;
+use macdive_toolbox_core::services::elevation::SrtmTiles;
+use macdive_toolbox_core::services::geocoding::{self, Geocoder};
+use macdive_toolbox_core::services::geotag::{
+    DiveMatch, capture_time, match_dive, parse_clock_offset,
+};
+use macdive_toolbox_core::services::marine::MarineRegions;
+use macdive_toolbox_core::services::xmp::{
+    KEYWORD_SEPARATOR, SidecarStatus, XmpLocation, find_photos, shared_sidecars, write_sidecar,
+};
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+static DIVING_MASK: Emoji<'_, '_> = Emoji("🤿️  ", "");
+static SATELLITE: Emoji<'_, '_> = Emoji("🛰️   ", "");
+static CAMERA: Emoji<'_, '_> = Emoji("📷  ", "");
+static STOPWATCH: Emoji<'_, '_> = Emoji("⏱️   ", "");
+
+fn status_cell(status: SidecarStatus) -> Cell {
+    match status {
+        SidecarStatus::Created => Cell::new("new").fg(Color::Green),
+        SidecarStatus::Updated => Cell::new("changed").fg(Color::Yellow),
+        SidecarStatus::Unchanged => Cell::new("unchanged"),
+    }
+}
+
+fn relative<'a>(path: &'a Path, root: &Path) -> std::path::Display<'a> {
+    path.strip_prefix(root).unwrap_or(path).display()
+}
+
+/// Find a dive site by UUID or by its (case-insensitive) name.
+///
+/// Only the matching site is converted, so problems with other sites in
+/// MacDive do not get in the way.
+fn find_site(sites: Vec<entity::dive_site::Model>, query: &str) -> anyhow::Result<DiveSite> {
+    let uuid = |site: &entity::dive_site::Model| {
+        site.uuid
+            .as_deref()
+            .and_then(|uuid| Uuid::parse_str(&uuid.to_lowercase()).ok())
+    };
+
+    if let Ok(query_uuid) = Uuid::parse_str(&query.to_lowercase()) {
+        let site = sites
+            .into_iter()
+            .find(|site| uuid(site) == Some(query_uuid))
+            .ok_or_else(|| anyhow::anyhow!("No dive site with UUID `{query}` found in MacDive"))?;
+        return Ok(dive_site_from_entity(site)?);
+    }
+
+    let mut matches: Vec<entity::dive_site::Model> = sites
+        .into_iter()
+        .filter(|site| {
+            site.name
+                .as_deref()
+                .is_some_and(|name| name.trim().eq_ignore_ascii_case(query.trim()))
+        })
+        .collect();
+    match matches.len() {
+        0 => anyhow::bail!("No dive site named `{query}` found in MacDive"),
+        1 => Ok(dive_site_from_entity(matches.remove(0))?),
+        _ => anyhow::bail!(
+            "Multiple dive sites named `{query}` found, use the UUID instead: {}",
+            matches
+                .iter()
+                .map(|site| site.uuid.clone().unwrap_or_default())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// Warn about photos that share a sidecar, since the last one written wins.
+fn warn_shared_sidecars(photos: &[PathBuf], root: &Path, append_extension: bool) {
+    for (sidecar, photos) in shared_sidecars(photos, append_extension) {
+        tracing::warn!(
+            "{} share the sidecar {}, use --append-extension to keep them apart",
+            photos
+                .iter()
+                .map(|photo| relative(photo, root).to_string())
+                .collect::<Vec<_>>()
+                .join(", "),
+            relative(&sidecar, root)
+        );
+    }
+}
+
+pub(crate) async fn write_site_sidecars(
+    db: &DatabaseManager,
+    options: &XmpWriteSiteOptions,
+    config: &ApplicationConfig,
+    geocoder: Option<&dyn Geocoder>,
+) -> anyhow::Result<()> {
+    eprintln!(
+        "{} {}Fetching dive site from MacDive...",
+        style("[1/3]").bold().dim(),
+        DIVING_MASK
+    );
+    let sites = queries::sites(db.macdive()).await?;
+    let mut site = find_site(sites, &options.site)?;
+
+    eprintln!(
+        "{} {}Looking up address for dive site...",
+        style("[2/3]").bold().dim(),
+        SATELLITE
+    );
+    if let Some(geocoder) = geocoder {
+        site = geocoder
+            .reverse_geocode(site)
+            .await
+            .map_err(ConversionError::from)?;
+    }
+    site = geocoding::apply_overrides(site, &config.locations()).map_err(ConversionError::from)?;
+    if let Some(regions) = MarineRegions::from_config(&config.body_of_water)? {
+        site = regions.fill(site);
+    }
+    if let Some(tiles) = SrtmTiles::from_config(&config.elevation) {
+        site = tiles.fill(site)?;
+    }
+    let location = XmpLocation::from_site(site, &config.lightroom)?;
+
+    eprintln!(
+        "{} {}Writing XMP sidecars...",
+        style("[3/3]").bold().dim(),
+        CAMERA
+    );
+    let photos = find_photos(&options.directory, options.recursive)?;
+    if photos.is_empty() {
+        println!("No photos found in {}.", options.directory.display());
+        return Ok(());
+    }
+    warn_shared_sidecars(&photos, &options.directory, options.append_extension);
+
+    let mut table = new_table(&["Status", "Sidecar"]);
+    for photo in &photos {
+        let (path, status) =
+            write_sidecar(photo, &location, options.append_extension, options.dry_run)?;
+        table.add_row(vec![
+            status_cell(status),
+            Cell::new(relative(&path, &options.directory)),
+        ]);
+    }
+    println!("{table}");
+
+    Ok(())
+}
+
+/// Fetch all dives, skipping those that cannot be converted.
+pub(crate) async fn fetch_valid_dives(db: &DatabaseManager) -> anyhow::Result<Vec<Dive>> {
+    Ok(queries::dives(db.macdive())
+        .await?
+        .into_iter()
+        .filter_map(|model| {
+            let id = model.id;
+            dive_from_entity(model)
+                .inspect_err(|e| tracing::warn!("Skipping dive {id}: {e}"))
+                .ok()
+        })
+        .collect())
+}
+
+/// Format the distance between a photo and the nearest dive, e.g. `12m after`.
+fn describe_match(kind: DiveMatch) -> String {
+    match kind {
+        DiveMatch::During => String::from("during"),
+        DiveMatch::Nearest(distance) if distance < TimeDelta::zero() => {
+            format!("{}m before", distance.abs().num_minutes())
+        }
+        DiveMatch::Nearest(distance) => format!("{}m after", distance.num_minutes()),
+    }
+}
+
+/// Critters logged on each dive, keyed by the dive's primary key.
+async fn dive_critters(
+    db: &DatabaseManager,
+) -> anyhow::Result<HashMap<i64, Vec<entity::critter::Model>>> {
+    let critters = queries::critters(db.macdive())
+        .await?
+        .into_iter()
+        .map(|critter| (critter.id, critter))
+        .collect::<HashMap<_, _>>();
+
+    let mut sightings: HashMap<i64, Vec<entity::critter::Model>> = HashMap::new();
+    for sighting in queries::dive_critters(db.macdive()).await? {
+        if let Some(critter) = critters.get(&sighting.critter) {
+            sightings
+                .entry(sighting.dive)
+                .or_default()
+                .push(critter.clone());
+        }
+    }
+
+    Ok(sightings)
+}
+
+/// Candidate keywords for the critters logged on a dive.
+///
+/// Critters are placed below the candidate branch using the same hierarchy as
+/// `critters export-keywords`, so confirming a candidate in Lightroom only
+/// means moving it out of the branch. Critters without a scientific name or
+/// whose taxon cannot be resolved are skipped.
+async fn candidate_keywords(
+    cache: &sea_orm::DbConn,
+    critters: &[entity::critter::Model],
+    config: &CritterConfig,
+    offline: bool,
+) -> Vec<String> {
+    let mut keywords = vec![];
+    for critter in critters {
+        let Some(scientific_name) = critter.species.as_deref() else {
+            continue;
+        };
+        match keyword_path(
+            cache,
+            scientific_name,
+            critter.name.as_deref(),
+            config,
+            offline,
+        )
+        .await
+        {
+            Ok(path) => keywords.push(
+                std::iter::once(config.keywords.candidate_branch.as_str())
+                    .chain(path.iter().map(|keyword| keyword.name.as_str()))
+                    .collect::<Vec<_>>()
+                    .join(&KEYWORD_SEPARATOR.to_string()),
+            ),
+            Err(e) => tracing::error!(
+                scientific_name,
+                reason = e.to_string(),
+                "Taxon lookup failed"
+            ),
+        }
+    }
+    keywords
+}
+
+pub(crate) async fn geotag_photos(
+    db: &DatabaseManager,
+    options: &XmpGeotagOptions,
+    config: &ApplicationConfig,
+    geocoder: Option<&dyn Geocoder>,
+    offline: bool,
+) -> anyhow::Result<()> {
+    let offset = parse_clock_offset(&options.clock_offset)?;
+    let tolerance = TimeDelta::minutes(options.tolerance.into());
+    let overrides = config.locations();
+    let regions = MarineRegions::from_config(&config.body_of_water)?;
+    let tiles = SrtmTiles::from_config(&config.elevation);
+
+    eprintln!(
+        "{} {}Fetching dives and dive sites from MacDive...",
+        style("[1/3]").bold().dim(),
+        DIVING_MASK
+    );
+    let dives = fetch_valid_dives(db).await?;
+    let mut sites = queries::sites(db.macdive())
+        .await?
+        .into_iter()
+        .map(|site| (site.id, site))
+        .collect::<HashMap<_, _>>();
+    let mut sightings = if options.critters {
+        dive_critters(db).await?
+    } else {
+        HashMap::new()
+    };
+
+    eprintln!(
+        "{} {}Reading capture times of photos...",
+        style("[2/3]").bold().dim(),
+        STOPWATCH
+    );
+    let photos = find_photos(&options.directory, options.recursive)?;
+    if photos.is_empty() {
+        println!("No photos found in {}.", options.directory.display());
+        return Ok(());
+    }
+    warn_shared_sidecars(&photos, &options.directory, options.append_extension);
+
+    eprintln!(
+        "{} {}Writing XMP sidecars...",
+        style("[3/3]").bold().dim(),
+        CAMERA
+    );
+    // Sites are only converted (and geocoded) once they are needed.
+    let mut locations: HashMap<i64, Result<XmpLocation, String>> = HashMap::new();
+    let mut candidates: HashMap<i64, Vec<String>> = HashMap::new();
+    let mut matched = new_table(&["Status", "Photo", "Taken", "Dive", "Site", "Match"]);
+    let mut unmatched = new_table(&["Photo", "Taken", "Reason"]);
+    let mut unmatched_count = 0;
+
+    for photo in &photos {
+        let path = relative(photo, &options.directory);
+        let taken = match capture_time(photo) {
+            Ok(taken) => taken - offset,
+            Err(e) => {
+                unmatched.add_row(vec![Cell::new(path), Cell::new(""), Cell::new(e)]);
+                unmatched_count += 1;
+                continue;
+            }
+        };
+
+        let Some((dive, kind)) = match_dive(&dives, taken, tolerance) else {
+            unmatched.add_row(vec![
+                Cell::new(path),
+                Cell::new(taken),
+                Cell::new("no dive within tolerance"),
+            ]);
+            unmatched_count += 1;
+            continue;
+        };
+
+        let Some(site_id) = dive.site_id else {
+            unmatched.add_row(vec![
+                Cell::new(path),
+                Cell::new(taken),
+                Cell::new(format!(
+                    "dive #{} has no dive site",
+                    dive.number.unwrap_or_default()
+                )),
+            ]);
+            unmatched_count += 1;
+            continue;
+        };
+
+        if let Entry::Vacant(entry) = locations.entry(site_id) {
+            entry.insert(match sites.remove(&site_id) {
+                Some(model) => {
+                    site_location(
+                        model,
+                        geocoder,
+                        &overrides,
+                        regions.as_ref(),
+                        tiles.as_ref(),
+                        &config.lightroom,
+                    )
+                    .await
+                }
+                None => Err(String::from("dive site has no GPS coordinates")),
+            });
+        }
+        let location = match &locations[&site_id] {
+            Ok(location) => location,
+            Err(e) => {
+                unmatched.add_row(vec![Cell::new(path), Cell::new(taken), Cell::new(e)]);
+                unmatched_count += 1;
+                continue;
+            }
+        };
+
+        if let Entry::Vacant(entry) = candidates.entry(dive.dive_id) {
+            let critters = sightings.remove(&dive.dive_id).unwrap_or_default();
+            entry
+                .insert(candidate_keywords(db.cache(), &critters, &config.critters, offline).await);
+        }
+        let location = XmpLocation {
+            hierarchical_keywords: candidates[&dive.dive_id].clone(),
+            ..location.clone()
+        };
+
+        let (_, status) =
+            write_sidecar(photo, &location, options.append_extension, options.dry_run)?;
+        matched.add_row(vec![
+            status_cell(status),
+            Cell::new(path),
+            Cell::new(taken),
+            Cell::new(format!("#{}", dive.number.unwrap_or_default())),
+            Cell::new(&location.location),
+            Cell::new(describe_match(kind)),
+        ]);
+    }
+
+    if unmatched_count < photos.len() {
+        println!("{matched}");
+    }
+    if unmatched_count > 0 {
+        println!("{unmatched}");
+        println!(
+            "{unmatched_count} of {} photos could not be matched to a dive.",
+            photos.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Convert (and optionally geocode) a dive site for use in sidecars.
+async fn site_location(
+    model: entity::dive_site::Model,
+    geocoder: Option<&dyn Geocoder>,
+    overrides: &[LocationOverride],
+    regions: Option<&MarineRegions>,
+    tiles: Option<&SrtmTiles>,
+    config: &LightroomConfig,
+) -> Result<XmpLocation, String> {
+    let mut site = dive_site_from_entity(model).map_err(|e| e.to_string())?;
+    if let Some(geocoder) = geocoder {
+        site = geocoder
+            .reverse_geocode(site)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+    site = geocoding::apply_overrides(site, overrides).map_err(|e| e.to_string())?;
+    if let Some(regions) = regions {
+        site = regions.fill(site);
+    }
+    if let Some(tiles) = tiles {
+        site = tiles.fill(site).map_err(|e| e.to_string())?;
+    }
+    XmpLocation::from_site(site, config).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model() -> entity::dive_site::Model {
+        entity::dive_site::Model {
+            id: 1,
+            ent: None,
+            opt: None,
+            altitude: None,
+            latitude: Some(12.083),
+            longitude: Some(-68.283),
+            modified_at: None,
+            body_of_water: Some(String::from("Caribbean Sea")),
+            country: Some(String::from("Bonaire")),
+            difficulty: None,
+            divelog_uuid: None,
+            flag: None,
+            image: None,
+            last_divelog_image_hash: None,
+            location: None,
+            name: Some(String::from("Salt Pier")),
+            notes: None,
+            uuid: Some(String::from("0C2F3A5E-6F4B-4B8E-9A51-3D1E2C4B5A69")),
+            water_type: None,
+            zoom: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_site_location_without_geocoder() {
+        let overrides = vec![LocationOverride {
+            area: vec![(-69.0, 12.0), (-68.0, 12.0), (-68.0, 13.0), (-69.0, 13.0)],
+            state: Some(String::from("Caribisch Nederland")),
+            locality: Some(String::from("Kralendijk")),
+            ..Default::default()
+        }];
+
+        let location = site_location(
+            model(),
+            None,
+            &overrides,
+            None,
+            None,
+            &LightroomConfig::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!("Caribisch Nederland", location.state);
+        assert_eq!("Kralendijk", location.city);
+        assert_eq!("Salt Pier", location.location);
+    }
+}
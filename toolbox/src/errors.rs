@@ -16,6 +16,8 @@ pub enum ConversionError {
     MissingLongitude,
     #[error("The MacDive dive site is missing a name")]
     MissingName,
+    #[error("The MacDive dive is missing its start date")]
+    MissingDiveDate,
     #[error("Error reverse geocoding the dive site")]
     GeocodingError(#[from] GeocodingError),
 }
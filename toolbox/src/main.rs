@@ -3,6 +3,7 @@ use clap::Parser;
 use indicatif::{ProgressState, ProgressStyle};
 use macdive_toolbox_core::db::DatabaseManager;
 use macdive_toolbox_core::domain::APPLICATION_NAME;
+use macdive_toolbox_core::services::geocoding::Geocoder;
 use migration::{Migrator, MigratorTrait};
 use std::time::Duration;
 use tracing::Level;
@@ -18,7 +19,7 @@ mod errors;
 mod progress;
 mod types;
 
-use crate::cli::{CritterCommands, LightroomCommands, MtpCommands};
+use crate::cli::{CritterCommands, LightroomCommands, MtpCommands, SiteCommands, XmpCommands};
 use cli::{Cli, Commands};
 
 fn setup_logging(verbose: u8) -> Result<()> {
@@ -130,12 +131,45 @@ async fn main() -> Result<()> {
 
     match &args.command {
         Commands::Lightroom { command, options } => match command {
-            LightroomCommands::ExportSites { force } => {
+            LightroomCommands::ExportSites(export) => {
+                let config = args.config()?;
+                let geocoder =
+                    options
+                        .geocoder
+                        .geocoder(db.cache(), &config.geocoding, args.offline)?;
                 commands::lightroom::export_lightroom_metadata_presets(
                     &db,
                     options,
-                    &args.config()?.locations(),
-                    *force,
+                    &config,
+                    export,
+                    geocoder.as_ref().map(|g| g as &dyn Geocoder),
+                )
+                .await?
+            }
+            LightroomCommands::PruneSites { apply, quarantine } => {
+                commands::lightroom::prune_lightroom_metadata_presets(
+                    &db,
+                    options,
+                    *apply,
+                    quarantine.as_deref(),
+                )
+                .await?
+            }
+            LightroomCommands::AuditCatalog {
+                catalog,
+                clock_offset,
+                tolerance,
+                max_distance,
+                format,
+            } => {
+                commands::lightroom::audit_lightroom_catalog(
+                    &db,
+                    options,
+                    catalog,
+                    clock_offset,
+                    *tolerance,
+                    *max_distance,
+                    *format,
                 )
                 .await?
             }
@@ -161,6 +195,126 @@ async fn main() -> Result<()> {
                 )
                 .await?
             }
+            CritterCommands::ExportKeywords { output } => {
+                commands::critters::export_keywords(
+                    &db,
+                    &args.config()?.into(),
+                    output.as_deref(),
+                    args.offline,
+                )
+                .await?
+            }
+        },
+        Commands::Xmp { command } => match command {
+            XmpCommands::WriteSite(options) => {
+                let config = args.config()?;
+                let geocoder =
+                    options
+                        .geocoder
+                        .geocoder(db.cache(), &config.geocoding, args.offline)?;
+                commands::xmp::write_site_sidecars(
+                    &db,
+                    options,
+                    &config,
+                    geocoder.as_ref().map(|g| g as &dyn Geocoder),
+                )
+                .await?
+            }
+            XmpCommands::Geotag(options) => {
+                let config = args.config()?;
+                let geocoder =
+                    options
+                        .geocoder
+                        .geocoder(db.cache(), &config.geocoding, args.offline)?;
+                commands::xmp::geotag_photos(
+                    &db,
+                    options,
+                    &config,
+                    geocoder.as_ref().map(|g| g as &dyn Geocoder),
+                    args.offline,
+                )
+                .await?
+            }
+        },
+        Commands::Sites { command } => match command {
+            SiteCommands::CheckOverrides { geojson } => {
+                commands::sites::check_overrides(&db, &args.config()?, geojson.as_deref()).await?
+            }
+            SiteCommands::BodyOfWater {
+                marine_regions,
+                all,
+            } => {
+                let mut config = args.config()?.body_of_water;
+                if let Some(path) = marine_regions {
+                    config.marine_regions = Some(path.to_owned());
+                }
+                commands::sites::check_body_of_water(&db, &config, *all).await?
+            }
+            SiteCommands::Export(options) => {
+                let config = args.config()?;
+                let geocoder =
+                    options
+                        .geocoder
+                        .geocoder(db.cache(), &config.geocoding, args.offline)?;
+                commands::sites::export_sites(
+                    &db,
+                    options,
+                    &config,
+                    geocoder.as_ref().map(|g| g as &dyn Geocoder),
+                )
+                .await?
+            }
+            SiteCommands::PrepareImport(options) => {
+                let config = args.config()?;
+                let geocoder =
+                    options
+                        .geocoder
+                        .geocoder(db.cache(), &config.geocoding, args.offline)?;
+                commands::sites::prepare_import(
+                    &db,
+                    options,
+                    &config,
+                    geocoder.as_ref().map(|g| g as &dyn Geocoder),
+                )
+                .await?
+            }
+            SiteCommands::Duplicates {
+                distance,
+                similarity,
+                format,
+            } => commands::sites::find_duplicates(&db, *distance, *similarity, *format).await?,
+            SiteCommands::Validate {
+                format,
+                check_country,
+                geocoder,
+            } => {
+                let config = args.config()?;
+                let geocoder = if *check_country {
+                    geocoder.geocoder(db.cache(), &config.geocoding, args.offline)?
+                } else {
+                    None
+                };
+                commands::sites::validate_sites(
+                    &db,
+                    geocoder.as_ref().map(|g| g as &dyn Geocoder),
+                    *format,
+                )
+                .await?
+            }
+            SiteCommands::SuggestOverrides(options) => {
+                let config = args.config()?;
+                let geocoder =
+                    options
+                        .geocoder
+                        .geocoder(db.cache(), &config.geocoding, args.offline)?;
+                commands::sites::suggest_overrides(
+                    &db,
+                    options,
+                    &config,
+                    geocoder.as_ref().map(|g| g as &dyn Geocoder),
+                )
+                .await?
+            }
         },
         Commands::Mtp { .. } => unreachable!(),
     }
@@ -1,10 +1,23 @@
 use std::str::FromStr;
 
-use macdive_toolbox_core::domain::DiveSite;
+use chrono::TimeDelta;
+use macdive_toolbox_core::domain::{Dive, DiveSite, nsdate_to_datetime};
+use macdive_toolbox_core::services::validation::{SiteIssue, SiteReport, check_coordinates};
 use uuid::Uuid;
 
 use crate::errors::ConversionError;
 
+/// Look up a MacDive country name, correcting names `celes` does not know.
+fn country_from_name(name: &str) -> Result<celes::Country, ConversionError> {
+    let corrected = match name {
+        "Netherlands Antilles" => "Bonaire",
+        "Solomon Islands" => "SolomonIslands",
+        _ => name,
+    };
+    celes::Country::from_str(corrected)
+        .map_err(|_result| ConversionError::UnknownCountry(name.to_string()))
+}
+
 /// Convert a SeaORM dive site entity into the domain `DiveSite` type.
 ///
 /// Maps the raw database model into the validated domain struct, applying
@@ -17,17 +30,9 @@ use crate::errors::ConversionError;
 pub fn dive_site_from_entity(model: entity::dive_site::Model) -> Result<DiveSite, ConversionError> {
     let country = model
         .country
-        .clone()
-        .map(|c| match c.as_str() {
-            "Netherlands Antilles" => String::from("Bonaire"),
-            "Solomon Islands" => String::from("SolomonIslands"),
-            _ => c,
-        })
+        .as_deref()
         .ok_or(ConversionError::MissingCountry)
-        .and_then(|v| {
-            celes::Country::from_str(&v)
-                .map_err(|_result| ConversionError::UnknownCountry(v.to_string()))
-        })?;
+        .and_then(country_from_name)?;
 
     Ok(DiveSite {
         uuid: model
@@ -44,8 +49,76 @@ pub fn dive_site_from_entity(model: entity::dive_site::Model) -> Result<DiveSite
         name: model.name.ok_or(ConversionError::MissingName)?,
         latitude: model.latitude.ok_or(ConversionError::MissingLatitude)?,
         longitude: model.longitude.ok_or(ConversionError::MissingLongitude)?,
-        altitude: 0.0,
+        // MacDive stores a missing altitude as zero, which the presets, sidecars
+        // and SRTM lookup treat as unknown.
+        altitude: model.altitude.unwrap_or_default() as f32,
         body_of_water: model.body_of_water,
+        water_type: model.water_type,
+        difficulty: model.difficulty,
+        notes: model.notes,
+        site_id: model.id,
+    })
+}
+
+/// Collect every problem of a dive site that can be found without looking
+/// up its coordinates.
+///
+/// Unlike [`dive_site_from_entity`] this does not stop at the first problem.
+pub fn dive_site_report(model: &entity::dive_site::Model) -> SiteReport {
+    let mut issues = vec![];
+    match model.uuid.as_deref() {
+        None => issues.push(SiteIssue::MissingUuid),
+        Some(uuid) if Uuid::parse_str(&uuid.to_lowercase()).is_err() => {
+            issues.push(SiteIssue::InvalidUuid {
+                uuid: uuid.to_string(),
+            })
+        }
+        Some(_) => {}
+    }
+    if model.name.as_deref().is_none_or(str::is_empty) {
+        issues.push(SiteIssue::MissingName);
+    }
+    match model.country.as_deref() {
+        None => issues.push(SiteIssue::MissingCountry),
+        Some(country) if country_from_name(country).is_err() => {
+            issues.push(SiteIssue::UnknownCountry {
+                country: country.to_string(),
+            })
+        }
+        Some(_) => {}
+    }
+    issues.extend(check_coordinates(model.latitude, model.longitude));
+
+    SiteReport {
         site_id: model.id,
+        name: model.name.clone(),
+        country: model.country.clone(),
+        latitude: model.latitude,
+        longitude: model.longitude,
+        issues,
+    }
+}
+
+/// Convert a SeaORM dive entity into the domain `Dive` type.
+///
+/// MacDive's raw date holds the local wall-clock time shown by the dive
+/// computer; the UTC date is only used as a fallback for dives without it.
+pub fn dive_from_entity(model: entity::dive::Model) -> Result<Dive, ConversionError> {
+    let start = model
+        .raw_date
+        .or(model.date)
+        .map(|timestamp| nsdate_to_datetime(timestamp).naive_utc())
+        .ok_or(ConversionError::MissingDiveDate)?;
+
+    Ok(Dive {
+        uuid: model
+            .uuid
+            .map(|v| Uuid::parse_str(&v.to_lowercase()).map_err(ConversionError::InvalidUuid))
+            .transpose()?,
+        number: model.number,
+        site_id: model.site,
+        start,
+        duration: TimeDelta::seconds(model.duration.unwrap_or_default() as i64),
+        dive_id: model.id,
     })
 }